@@ -0,0 +1,146 @@
+//! Writes top-down maps of a generated area as PNG files, without opening a window.
+//!
+//! ```text
+//! worldmap [--seed N] [--preset NAME] [--center X,Z] [--radius CHUNKS]
+//!          [--out DIR] [--set NAME=VALUE]...
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use minecraft::block::BlockRegistry;
+use minecraft::world::ChunkPos;
+use minecraft::worldgen::map::{BlockColors, MapKind, Survey};
+use minecraft::worldgen::{self, Generator, TerrainGenerator, TerrainSettings};
+
+const USAGE: &str = "\
+usage: worldmap [options]
+
+  --seed N           world seed
+  --preset NAME      world generator preset, `noise` by default
+  --center X,Z       block the maps are centred on, 0,0 by default
+  --radius CHUNKS    chunks mapped around the centre, 32 by default
+  --out DIR          where the maps are written, the current directory by default
+  --set NAME=VALUE   changes a terrain setting of the `noise` preset, like `sea_level=58`";
+
+struct Options {
+    seed: u64,
+    preset: String,
+    center: (i32, i32),
+    radius: i32,
+    out: PathBuf,
+    settings: Vec<(String, String)>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        seed: worldgen::DEFAULT_SEED,
+        preset: worldgen::DEFAULT_PRESET.into(),
+        center: (0, 0),
+        radius: 32,
+        out: PathBuf::from("."),
+        settings: Vec::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Err(String::new());
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("`{arg}` needs a value"))?;
+        let bad = || format!("bad value `{value}` for `{arg}`");
+
+        match arg.as_str() {
+            "--seed" => options.seed = value.parse().map_err(|_| bad())?,
+            "--preset" => options.preset = value,
+            "--center" => {
+                let (x, z) = value.split_once(',').ok_or_else(bad)?;
+                options.center = (
+                    x.trim().parse().map_err(|_| bad())?,
+                    z.trim().parse().map_err(|_| bad())?,
+                );
+            }
+            "--radius" => options.radius = value.parse().map_err(|_| bad())?,
+            "--out" => options.out = value.into(),
+            "--set" => {
+                let (name, setting) = value.split_once('=').ok_or_else(bad)?;
+                options
+                    .settings
+                    .push((name.trim().into(), setting.trim().into()));
+            }
+            _ => return Err(format!("unknown option `{arg}`")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|error| {
+        if !error.is_empty() {
+            eprintln!("{error}\n");
+        }
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    });
+
+    if let Err(error) = run(options) {
+        eprintln!("worldmap: {error}");
+        std::process::exit(1);
+    }
+}
+
+fn run(options: Options) -> std::io::Result<()> {
+    let registry = BlockRegistry::from_file("res/blocks.json")?;
+    let colors = BlockColors::load(&registry, "res/block".as_ref())?;
+
+    let generator: Arc<dyn Generator> = if options.settings.is_empty() {
+        worldgen::from_preset(&options.preset, options.seed, &registry)?
+    } else if options.preset == "noise" {
+        let mut settings = TerrainSettings::default();
+        for (name, value) in &options.settings {
+            settings.set(name, value)?;
+        }
+
+        Arc::new(TerrainGenerator::new(options.seed, &registry, settings))
+    } else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "`--set` only works with the `noise` preset",
+        ));
+    };
+
+    let center = ChunkPos::from_block(glam::ivec3(options.center.0, 0, options.center.1));
+    let radius = options.radius.max(0);
+    let min = ChunkPos::new(center.x - radius, center.z - radius);
+    let max = ChunkPos::new(center.x + radius, center.z + radius);
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let start = Instant::now();
+    let survey = Survey::new(generator.as_ref(), &registry, min, max, threads);
+
+    println!(
+        "generated {} chunks in {:.2?}",
+        (2 * radius + 1).pow(2),
+        start.elapsed()
+    );
+
+    std::fs::create_dir_all(&options.out)?;
+    for kind in MapKind::ALL {
+        let path = options.out.join(format!(
+            "{}-{}-{}.png",
+            options.preset.split(':').next().unwrap_or(""),
+            options.seed,
+            kind.name()
+        ));
+        survey.render(kind, &colors).save(&path)?;
+
+        println!("wrote {}", path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+mod model;
+mod registry;
+mod state;
+
+pub use model::*;
+pub use registry::*;
+pub use state::*;
+
+/// A side of a block, in the same order as the cube vertex data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    /// +Z
+    Front,
+    /// -Z
+    Back,
+    /// -X
+    Left,
+    /// +X
+    Right,
+    /// +Y
+    Up,
+    /// -Y
+    Down,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Front,
+        Face::Back,
+        Face::Left,
+        Face::Right,
+        Face::Up,
+        Face::Down,
+    ];
+
+    pub fn normal(&self) -> glam::IVec3 {
+        match self {
+            Face::Front => glam::IVec3::Z,
+            Face::Back => glam::IVec3::NEG_Z,
+            Face::Left => glam::IVec3::NEG_X,
+            Face::Right => glam::IVec3::X,
+            Face::Up => glam::IVec3::Y,
+            Face::Down => glam::IVec3::NEG_Y,
+        }
+    }
+
+    /// The face pointing along the normal, which must be a unit axis vector.
+    pub fn from_normal(normal: glam::IVec3) -> Option<Face> {
+        Self::ALL.into_iter().find(|face| face.normal() == normal)
+    }
+
+    /// The face turned around the vertical axis, quarter turns clockwise seen from above.
+    pub fn rotated(self, quarter_turns: u8) -> Face {
+        let mut normal = self.normal();
+        for _ in 0..quarter_turns % 4 {
+            normal = glam::ivec3(-normal.z, normal.y, normal.x);
+        }
+
+        Self::from_normal(normal).unwrap_or(self)
+    }
+
+    /// Texture `s` and `t` directions of the face, as seen from outside of the block.
+    pub fn texture_axes(self) -> (glam::IVec3, glam::IVec3) {
+        use glam::IVec3;
+
+        match self {
+            Face::Front => (IVec3::X, IVec3::Y),
+            Face::Back => (IVec3::NEG_X, IVec3::Y),
+            Face::Left => (IVec3::Z, IVec3::Y),
+            Face::Right => (IVec3::NEG_Z, IVec3::Y),
+            Face::Up => (IVec3::X, IVec3::NEG_Z),
+            Face::Down => (IVec3::X, IVec3::Z),
+        }
+    }
+
+    /// Corner of this face of the `min..max` box at fraction `s` along the texture `s`
+    /// direction and `t` along the `t` direction. Quads are wound through `(0, 0)`,
+    /// `(0, 1)`, `(1, 1)` and `(1, 0)`.
+    pub fn box_corner(self, min: glam::Vec3, max: glam::Vec3, s: f32, t: f32) -> glam::Vec3 {
+        let normal = self.normal();
+        let (u, v) = self.texture_axes();
+
+        // coordinate on `axis` at fraction `f` of the box size, walking in the sign of `dir`
+        let along = |axis: usize, dir: i32, f: f32| {
+            if dir >= 0 {
+                min[axis] + f * (max[axis] - min[axis])
+            } else {
+                max[axis] - f * (max[axis] - min[axis])
+            }
+        };
+
+        glam::Vec3::from_array(std::array::from_fn(|axis| {
+            if normal[axis] != 0 {
+                along(axis, normal[axis], 1.0)
+            } else if u[axis] != 0 {
+                along(axis, u[axis], s)
+            } else {
+                along(axis, v[axis], t)
+            }
+        }))
+    }
+}
+
+/// What's drawn on one face of a block state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FaceTexture {
+    /// Texture array layer.
+    pub layer: u32,
+    /// Quarter turns of the texture, clockwise seen from outside of the block.
+    pub rotation: u8,
+}
@@ -0,0 +1,502 @@
+//! Block models in the game's JSON format: boxes called elements with a texture and UVs on
+//! each of their faces, read from `res/models/<name>.json`. A model may name a `parent`,
+//! taking its elements when it has none of its own and overriding its texture variables.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+use glam::{Mat3, Vec3};
+use serde::Deserialize;
+
+use super::{Axis, Face};
+
+/// Where model files are read from.
+pub const MODELS_PATH: &str = "res/models";
+
+/// Chains of parents or texture variables longer than this are taken for loops.
+const MAX_DEPTH: usize = 16;
+
+/// A compiled model, as quads within the `0..1` cell.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockModel {
+    pub quads: Vec<ModelQuad>,
+}
+
+/// One textured face of a model element.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelQuad {
+    /// Corners going clockwise when seen from outside.
+    pub positions: [Vec3; 4],
+    /// Texture coordinates of each corner.
+    pub tex_coords: [[f32; 2]; 4],
+    pub normal: Vec3,
+    /// Texture array layer.
+    pub layer: u32,
+    /// Side of the cell whose neighbour hides the quad, `None` if it always shows.
+    pub cullface: Option<Face>,
+}
+
+impl BlockModel {
+    /// The model transformed around the centre of the cell. Quads of mirrored models are
+    /// wound the other way round, so they still face outwards.
+    pub fn transformed(&self, matrix: Mat3) -> Self {
+        let mirrored = matrix.determinant() < 0.0;
+        let center = Vec3::splat(0.5);
+
+        let quads = self
+            .quads
+            .iter()
+            .map(|quad| {
+                let mut positions = quad.positions.map(|p| matrix * (p - center) + center);
+                let mut tex_coords = quad.tex_coords;
+                if mirrored {
+                    positions.swap(1, 3);
+                    tex_coords.swap(1, 3);
+                }
+
+                ModelQuad {
+                    positions,
+                    tex_coords,
+                    normal: (matrix * quad.normal).normalize(),
+                    layer: quad.layer,
+                    cullface: quad.cullface.and_then(|face| {
+                        Face::from_normal((matrix * face.normal().as_vec3()).round().as_ivec3())
+                    }),
+                }
+            })
+            .collect();
+
+        Self { quads }
+    }
+}
+
+/// Reads model files by name and compiles them, keeping every file it has read.
+#[derive(Debug)]
+pub struct ModelLoader {
+    dir: PathBuf,
+    defs: HashMap<String, ModelDef>,
+}
+
+impl ModelLoader {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            defs: HashMap::new(),
+        }
+    }
+
+    /// Adds a model that isn't read from a file, replacing any with the same name.
+    pub fn insert(&mut self, name: &str, contents: &str) -> std::io::Result<()> {
+        let def = serde_json::from_str(contents)
+            .map_err(|e| invalid(format!("model `{name}` is invalid: {e}")))?;
+        self.defs.insert(name.to_owned(), def);
+        Ok(())
+    }
+
+    /// Compiles the model with the given name. `layer` gives the texture array layer of
+    /// a texture name, or of a `#variable` that the model and its parents leave unset.
+    pub fn compile(
+        &mut self,
+        name: &str,
+        mut layer: impl FnMut(&str) -> Option<u32>,
+    ) -> std::io::Result<BlockModel> {
+        // the model and its parents, child first
+        let mut chain = vec![];
+        let mut next = Some(name.to_owned());
+        while let Some(model) = next {
+            if chain.len() == MAX_DEPTH {
+                return Err(invalid(format!("model `{name}` has a loop of parents")));
+            }
+
+            next = self.load(&model)?.parent.clone();
+            chain.push(model);
+        }
+        let defs: Vec<&ModelDef> = chain.iter().map(|model| &self.defs[model]).collect();
+
+        let mut textures = HashMap::new();
+        for def in defs.iter().rev() {
+            textures.extend(def.textures.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        }
+
+        let elements = defs
+            .iter()
+            .find_map(|def| def.elements.as_deref())
+            .unwrap_or_default();
+
+        let mut quads = vec![];
+        for element in elements {
+            if let Some(rotation) = &element.rotation {
+                if !(-45.0..=45.0).contains(&rotation.angle) {
+                    return Err(invalid(format!(
+                        "model `{name}` turns an element by more than 45 degrees"
+                    )));
+                }
+            }
+
+            for (&side, face) in &element.faces {
+                let texture = resolve(&textures, &face.texture);
+                let layer = layer(texture).ok_or_else(|| {
+                    invalid(format!("model `{name}` has no texture for `{texture}`"))
+                })?;
+
+                quads.push(element.quad(side.face(), face, layer));
+            }
+        }
+
+        Ok(BlockModel { quads })
+    }
+
+    fn load(&mut self, name: &str) -> std::io::Result<&ModelDef> {
+        if !self.defs.contains_key(name) {
+            let path = self.dir.join(format!("{name}.json"));
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| Error::new(e.kind(), format!("can't read model `{name}`: {e}")))?;
+            self.insert(name, &contents)?;
+        }
+
+        Ok(&self.defs[name])
+    }
+}
+
+/// Follows `#variable` references through the texture variables, stopping at a texture
+/// name or at a variable nothing sets.
+fn resolve<'a>(textures: &HashMap<&'a str, &'a str>, mut texture: &'a str) -> &'a str {
+    for _ in 0..MAX_DEPTH {
+        match texture
+            .strip_prefix('#')
+            .and_then(|variable| textures.get(variable))
+        {
+            Some(next) => texture = next,
+            None => break,
+        }
+    }
+
+    texture
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelDef {
+    parent: Option<String>,
+    #[serde(default)]
+    textures: HashMap<String, String>,
+    elements: Option<Vec<ElementDef>>,
+}
+
+/// A box from `from` to `to`, in sixteenths of a block.
+#[derive(Debug, Deserialize)]
+struct ElementDef {
+    from: [f32; 3],
+    to: [f32; 3],
+    rotation: Option<RotationDef>,
+    #[serde(default)]
+    faces: BTreeMap<Side, FaceDef>,
+}
+
+impl ElementDef {
+    fn quad(&self, face: Face, def: &FaceDef, layer: u32) -> ModelQuad {
+        let (from, to) = (Vec3::from(self.from), Vec3::from(self.to));
+        let normal = face.normal();
+        let (u, v) = face.texture_axes();
+
+        // fractions along the texture `u` and `v` axes, in the order quads are wound
+        let corners = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+        let positions = corners.map(|(s, t)| face.box_corner(from, to, s, t));
+
+        // UVs count from the top left of the texture, by default the face shows the part
+        // of the texture it would cover on a full block
+        let [u1, v1, u2, v2] = def.uv.unwrap_or_else(|| {
+            let (u_axis, v_axis) = (u.abs().max_position(), v.abs().max_position());
+            let u_at = |p: Vec3| {
+                if u[u_axis] > 0 {
+                    p[u_axis]
+                } else {
+                    16.0 - p[u_axis]
+                }
+            };
+            let v_at = |p: Vec3| {
+                if v[v_axis] > 0 {
+                    16.0 - p[v_axis]
+                } else {
+                    p[v_axis]
+                }
+            };
+
+            [
+                u_at(positions[0]),
+                v_at(positions[1]),
+                u_at(positions[2]),
+                v_at(positions[0]),
+            ]
+        });
+        // textures are loaded bottom row first
+        let mut tex_coords = corners.map(|(s, t)| {
+            [
+                (u1 + s * (u2 - u1)) / 16.0,
+                1.0 - (v2 + t * (v1 - v2)) / 16.0,
+            ]
+        });
+        tex_coords.rotate_right(def.rotation as usize / 90 % 4);
+
+        let (positions, normal) = match &self.rotation {
+            Some(rotation) => (
+                positions.map(|p| rotation.apply(p)),
+                rotation.matrix() * normal.as_vec3(),
+            ),
+            None => (positions, normal.as_vec3()),
+        };
+
+        ModelQuad {
+            positions: positions.map(|p| p / 16.0),
+            tex_coords,
+            normal,
+            layer,
+            cullface: def.cullface.map(Side::face),
+        }
+    }
+}
+
+/// Turns an element around an axis through `origin`.
+#[derive(Debug, Deserialize)]
+struct RotationDef {
+    origin: [f32; 3],
+    axis: Axis,
+    /// Degrees, between -45 and 45.
+    angle: f32,
+    /// Stretches the element across the other two axes so it spans the cell again.
+    #[serde(default)]
+    rescale: bool,
+}
+
+impl RotationDef {
+    fn matrix(&self) -> Mat3 {
+        Mat3::from_axis_angle(Vec3::AXES[self.axis as usize], self.angle.to_radians())
+    }
+
+    fn apply(&self, p: Vec3) -> Vec3 {
+        let origin = Vec3::from(self.origin);
+        let mut p = self.matrix() * (p - origin);
+
+        if self.rescale {
+            let scale = 1.0 / self.angle.to_radians().cos();
+            for axis in 0..3 {
+                if axis != self.axis as usize {
+                    p[axis] *= scale;
+                }
+            }
+        }
+
+        p + origin
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FaceDef {
+    /// `[u1, v1, u2, v2]` in sixteenths of the texture.
+    uv: Option<[f32; 4]>,
+    /// A texture name or a `#variable`.
+    texture: String,
+    cullface: Option<Side>,
+    /// Clockwise turn of the texture, in degrees.
+    #[serde(default)]
+    rotation: u16,
+}
+
+/// Face names used by model files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Side {
+    #[serde(alias = "bottom")]
+    Down,
+    Up,
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Side {
+    fn face(self) -> Face {
+        match self {
+            Side::Down => Face::Down,
+            Side::Up => Face::Up,
+            Side::North => Face::Back,
+            Side::South => Face::Front,
+            Side::West => Face::Left,
+            Side::East => Face::Right,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTURES: [&str; 3] = ["stone", "plank", "torch"];
+
+    fn layer(texture: &str) -> Option<u32> {
+        TEXTURES
+            .iter()
+            .position(|&t| t == texture)
+            .map(|i| i as u32)
+    }
+
+    fn bounds(model: &BlockModel) -> (Vec3, Vec3) {
+        let points = model.quads.iter().flat_map(|quad| quad.positions);
+        (
+            points.clone().reduce(Vec3::min).unwrap(),
+            points.reduce(Vec3::max).unwrap(),
+        )
+    }
+
+    #[test]
+    fn children_fill_in_the_textures_of_their_parents() {
+        let mut loader = ModelLoader::new(MODELS_PATH);
+        loader
+            .insert(
+                "post",
+                r##"{
+                    "textures": { "side": "#all" },
+                    "elements": [{
+                        "from": [6, 0, 6], "to": [10, 16, 10],
+                        "faces": {
+                            "up": { "texture": "#top", "cullface": "up" },
+                            "north": { "texture": "#side" }
+                        }
+                    }]
+                }"##,
+            )
+            .unwrap();
+        loader
+            .insert(
+                "plank_post",
+                r#"{ "parent": "post", "textures": { "all": "plank", "top": "stone" } }"#,
+            )
+            .unwrap();
+
+        let model = loader.compile("plank_post", layer).unwrap();
+        let up = model.quads.iter().find(|q| q.normal == Vec3::Y).unwrap();
+        let north = model
+            .quads
+            .iter()
+            .find(|q| q.normal == Vec3::NEG_Z)
+            .unwrap();
+        assert_eq!((up.layer, up.cullface), (0, Some(Face::Up)));
+        assert_eq!((north.layer, north.cullface), (1, None));
+        assert_eq!(
+            bounds(&model),
+            (Vec3::new(0.375, 0.0, 0.375), Vec3::new(0.625, 1.0, 0.625))
+        );
+
+        // variables nothing sets are left to the caller
+        let model = loader
+            .compile("post", |texture| (texture == "#all").then_some(2))
+            .unwrap_err();
+        assert!(model.to_string().contains("#top"), "{model}");
+
+        loader.insert("a", r#"{ "parent": "b" }"#).unwrap();
+        loader.insert("b", r#"{ "parent": "a" }"#).unwrap();
+        assert!(loader.compile("a", layer).is_err());
+        assert!(loader.compile("missing", layer).is_err());
+    }
+
+    #[test]
+    fn faces_show_their_part_of_the_texture() {
+        let mut loader = ModelLoader::new(MODELS_PATH);
+        loader
+            .insert(
+                "torch",
+                r#"{
+                    "elements": [{
+                        "from": [7, 0, 7], "to": [9, 10, 9],
+                        "faces": {
+                            "south": { "texture": "torch", "uv": [7, 6, 9, 16] },
+                            "west": { "texture": "torch" },
+                            "up": { "texture": "torch", "uv": [7, 6, 9, 8], "rotation": 90 }
+                        }
+                    }]
+                }"#,
+            )
+            .unwrap();
+        let model = loader.compile("torch", layer).unwrap();
+        let face = |normal| model.quads.iter().find(|q| q.normal == normal).unwrap();
+
+        // corners start at the bottom left seen from outside, textures count rows upwards
+        assert_eq!(
+            face(Vec3::Z).tex_coords,
+            [
+                [0.4375, 0.0],
+                [0.4375, 0.625],
+                [0.5625, 0.625],
+                [0.5625, 0.0]
+            ]
+        );
+        assert_eq!(
+            face(Vec3::NEG_X).tex_coords,
+            [
+                [0.4375, 0.0],
+                [0.4375, 0.625],
+                [0.5625, 0.625],
+                [0.5625, 0.0]
+            ]
+        );
+        assert_eq!(
+            face(Vec3::Y).tex_coords,
+            [
+                [0.5625, 0.5],
+                [0.4375, 0.5],
+                [0.4375, 0.625],
+                [0.5625, 0.625]
+            ]
+        );
+    }
+
+    #[test]
+    fn cross_plants_span_the_diagonals() {
+        let mut loader = ModelLoader::new(MODELS_PATH);
+        let model = loader
+            .compile("cross", |texture| (texture == "#cross").then_some(0))
+            .unwrap();
+
+        assert_eq!(model.quads.len(), 4);
+        for quad in &model.quads {
+            assert!(quad.cullface.is_none());
+            for p in quad.positions {
+                // the corners of each plane sit in opposite corners of the cell
+                let near = |a: f32, b: f32| (a - b).abs() < 0.06;
+                assert!(near(p.x, 0.0) || near(p.x, 1.0), "{p}");
+                assert!(near(p.z, 0.0) || near(p.z, 1.0), "{p}");
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_models_still_face_outwards() {
+        let mut loader = ModelLoader::new(MODELS_PATH);
+        let model = loader
+            .compile("stairs", |texture| layer(texture).or(Some(0)))
+            .unwrap();
+        let flipped = model.transformed(Mat3::from_diagonal(Vec3::new(1.0, -1.0, 1.0)));
+
+        for quad in model.quads.iter().chain(&flipped.quads) {
+            // clockwise seen from outside
+            let [a, b, c, _] = quad.positions;
+            assert!((b - a).cross(c - a).dot(quad.normal) < 0.0, "{quad:?}");
+        }
+
+        let cullfaces = |model: &BlockModel, normal: Vec3| {
+            model
+                .quads
+                .iter()
+                .filter(|q| q.normal.abs_diff_eq(normal, 1e-6))
+                .map(|q| q.cullface)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cullfaces(&flipped, Vec3::NEG_Y), [None, Some(Face::Down)]);
+        assert_eq!(bounds(&flipped), (Vec3::ZERO, Vec3::ONE));
+    }
+}
@@ -0,0 +1,666 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use glam::{Mat3, Vec3};
+use serde::Deserialize;
+
+use super::{
+    Axis, BlockModel, Face, FaceTexture, Facing, Half, ModelLoader, Placement, Property,
+    PropertyValue, MODELS_PATH,
+};
+use crate::world::{BlockId, Light};
+
+/// A registered block type. Its states take consecutive ids, one for each combination
+/// of property values, with the last property changing fastest.
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// The default state, the one generators place.
+    pub id: BlockId,
+    pub name: String,
+    /// Texture array layer of each face, indexed by `Face as usize`, for the block facing
+    /// south and standing upright.
+    pub faces: [u32; 6],
+    /// Hides the faces of neighbouring blocks.
+    pub opaque: bool,
+    /// Collides with entities.
+    pub solid: bool,
+    /// Block light level the block gives off.
+    pub light: u8,
+    /// Colour of the light the block gives off, lit by the renderer as a point light.
+    pub light_color: Vec3,
+    /// How much light fades going through the block, on top of the one level per block
+    /// it always fades by. `15` blocks light entirely.
+    pub light_opacity: u8,
+    /// Name of the model drawn instead of a full cube.
+    pub model: Option<String>,
+    /// Properties with the number of values each takes.
+    properties: Vec<(Property, u8)>,
+    first_state: u16,
+}
+
+impl Block {
+    pub fn get_texture(&self, face: Face) -> u32 {
+        self.faces[face as usize]
+    }
+
+    pub fn get_properties(&self) -> impl Iterator<Item = Property> + '_ {
+        self.properties.iter().map(|&(property, _)| property)
+    }
+
+    /// Every state of the block, the first one having the first value of each property.
+    pub fn states(&self) -> impl Iterator<Item = BlockId> {
+        (self.first_state..self.first_state + self.state_count()).map(BlockId)
+    }
+
+    pub fn has_state(&self, state: BlockId) -> bool {
+        (self.first_state..self.first_state + self.state_count()).contains(&state.0)
+    }
+
+    /// Index of the property's value in the state, `None` if the block doesn't have it.
+    pub fn get_value(&self, state: BlockId, property: Property) -> Option<u8> {
+        if !self.has_state(state) {
+            return None;
+        }
+
+        let i = self.properties.iter().position(|&(p, _)| p == property)?;
+        let offset = state.0 - self.first_state;
+        Some((offset / self.stride(i) % self.properties[i].1 as u16) as u8)
+    }
+
+    /// The state with the property's value changed. States of other blocks, properties
+    /// the block doesn't have and values out of range leave the state as it is.
+    pub fn with_value(&self, state: BlockId, property: Property, value: u8) -> BlockId {
+        let Some(old) = self.get_value(state, property) else {
+            return state;
+        };
+
+        let i = self.properties.iter().position(|&(p, _)| p == property);
+        match i {
+            Some(i) if value < self.properties[i].1 => {
+                let stride = self.stride(i);
+                BlockId(state.0 - old as u16 * stride + value as u16 * stride)
+            }
+            _ => state,
+        }
+    }
+
+    pub fn get<P: PropertyValue>(&self, state: BlockId) -> Option<P> {
+        self.get_value(state, P::PROPERTY).map(P::from_index)
+    }
+
+    pub fn with<P: PropertyValue>(&self, state: BlockId, value: P) -> BlockId {
+        self.with_value(state, P::PROPERTY, value.to_index())
+    }
+
+    /// `name[key=value,...]`, properties in the order the block lists them.
+    pub fn describe(&self, state: BlockId) -> String {
+        if self.properties.is_empty() {
+            return self.name.clone();
+        }
+
+        let values: Vec<_> = self
+            .get_properties()
+            .map(|property| {
+                let value = self.get_value(state, property).unwrap_or(0);
+                format!("{}={}", property.name(), property.value_name(value))
+            })
+            .collect();
+        format!("{}[{}]", self.name, values.join(","))
+    }
+
+    /// The state to place the block in, lining it up with the face clicked on.
+    pub fn place(&self, placement: &Placement) -> BlockId {
+        let mut state = self.id;
+
+        for property in self.get_properties() {
+            state = match property {
+                Property::Axis => self.with(state, Axis::from_normal(placement.normal)),
+                Property::Facing => self.with(state, Facing::from_direction(placement.look)),
+                Property::Half => {
+                    let top = match placement.normal.y {
+                        1 => false,
+                        -1 => true,
+                        _ => placement.point.y > 0.5,
+                    };
+                    self.with(state, if top { Half::Top } else { Half::Bottom })
+                }
+                _ => state,
+            };
+        }
+
+        state
+    }
+
+    fn state_count(&self) -> u16 {
+        self.properties
+            .iter()
+            .map(|&(_, count)| count as u16)
+            .product()
+    }
+
+    /// How far apart states differing by one in the `i`th property are.
+    fn stride(&self, i: usize) -> u16 {
+        self.properties[i + 1..]
+            .iter()
+            .map(|&(_, count)| count as u16)
+            .product()
+    }
+
+    /// Face and texture rotation of the upright, south facing block drawn on `face`.
+    fn model_face(&self, state: BlockId, face: Face) -> (Face, u8) {
+        if let Some(axis) = self.get::<Axis>(state) {
+            return match (axis, face) {
+                (Axis::Y, face) => (face, 0),
+                (Axis::X, Face::Right) => (Face::Up, 0),
+                (Axis::X, Face::Left) => (Face::Down, 0),
+                (Axis::X, Face::Up) => (Face::Left, 1),
+                (Axis::X, Face::Down) => (Face::Right, 1),
+                (Axis::X, face) => (face, 1),
+                (Axis::Z, Face::Front) => (Face::Up, 0),
+                (Axis::Z, Face::Back) => (Face::Down, 0),
+                (Axis::Z, Face::Up) => (Face::Back, 0),
+                (Axis::Z, Face::Down) => (Face::Front, 0),
+                (Axis::Z, face) => (face, 1),
+            };
+        }
+
+        if let Some(facing) = self.get::<Facing>(state) {
+            let turns = facing.quarter_turns();
+            return match face {
+                Face::Up => (face, turns),
+                Face::Down => (face, (4 - turns) % 4),
+                face => (face.rotated((4 - turns) % 4), 0),
+            };
+        }
+
+        (face, 0)
+    }
+
+    /// How the state turns its model, which is drawn facing south, upright and in the
+    /// bottom half of the cell.
+    fn model_transform(&self, state: BlockId) -> Mat3 {
+        let mut matrix = Mat3::IDENTITY;
+
+        if self.get::<Half>(state) == Some(Half::Top) {
+            matrix = Mat3::from_diagonal(Vec3::new(1.0, -1.0, 1.0));
+        }
+
+        match self.get::<Axis>(state) {
+            Some(Axis::X) => matrix = Mat3::from_cols(Vec3::NEG_Y, Vec3::X, Vec3::Z) * matrix,
+            Some(Axis::Z) => matrix = Mat3::from_cols(Vec3::X, Vec3::Z, Vec3::NEG_Y) * matrix,
+            _ => {}
+        }
+
+        if let Some(facing) = self.get::<Facing>(state) {
+            // a quarter turn clockwise seen from above, the same way `Face::rotated` turns
+            let turn = Mat3::from_cols(Vec3::Z, Vec3::Y, Vec3::NEG_X);
+            for _ in 0..facing.quarter_turns() {
+                matrix = turn * matrix;
+            }
+        }
+
+        matrix
+    }
+}
+
+/// What the registry keeps for each state.
+#[derive(Debug, Clone)]
+struct State {
+    /// Index of the block the state belongs to.
+    block: u16,
+    faces: [FaceTexture; 6],
+    model: Option<BlockModel>,
+}
+
+/// Every block type, loaded from a data file.
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    by_name: HashMap<String, BlockId>,
+    textures: Vec<String>,
+    /// Indexed by state id.
+    states: Vec<State>,
+}
+
+impl BlockRegistry {
+    pub fn from_file(path: &str) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_str(&contents)
+    }
+
+    /// Reads block definitions, with the models they name from [`MODELS_PATH`].
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(contents: &str) -> std::io::Result<Self> {
+        Self::with_models(contents, &mut ModelLoader::new(MODELS_PATH))
+    }
+
+    pub fn with_models(contents: &str, models: &mut ModelLoader) -> std::io::Result<Self> {
+        let mut defs: Vec<BlockDef> = serde_json::from_str(contents)?;
+        defs.sort_by_key(|def| def.id);
+
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+
+        if defs.first().map(|def| (def.id, def.name.as_str())) != Some((0, "air")) {
+            return Err(invalid("block 0 must be `air`".into()));
+        }
+
+        let mut blocks = Vec::with_capacity(defs.len());
+        let mut block_models = Vec::with_capacity(defs.len());
+        let mut by_name = HashMap::new();
+        let mut textures: Vec<String> = vec![];
+        let mut state_count = 0u32;
+
+        for (expected, def) in defs.into_iter().enumerate() {
+            if def.id as usize != expected {
+                return Err(invalid(format!(
+                    "block ids must be contiguous, expected {expected} for `{}` but found {}",
+                    def.name, def.id
+                )));
+            }
+
+            let properties: Vec<_> = def
+                .properties
+                .iter()
+                .map(|&property| (property, property.value_count(def.max_age)))
+                .collect();
+            if properties
+                .iter()
+                .enumerate()
+                .any(|(i, (property, _))| properties[..i].iter().any(|(p, _)| p == property))
+            {
+                return Err(invalid(format!(
+                    "block `{}` lists a property twice",
+                    def.name
+                )));
+            }
+            // a value more than the last age has to fit in the state
+            if def.properties.contains(&Property::Age) && def.max_age == u8::MAX {
+                return Err(invalid(format!(
+                    "block `{}` can't age past {}",
+                    def.name,
+                    u8::MAX - 1
+                )));
+            }
+
+            let first_state = state_count;
+            state_count += properties
+                .iter()
+                .map(|&(_, count)| count as u32)
+                .product::<u32>();
+            if state_count > u16::MAX as u32 {
+                return Err(invalid("there are too many block states".into()));
+            }
+
+            let mut block = Block {
+                id: BlockId(first_state as u16),
+                name: def.name,
+                faces: [0; 6],
+                opaque: def.opaque,
+                solid: def.solid,
+                light: def.light.min(Light::MAX),
+                light_color: Vec3::from(def.light_color),
+                light_opacity: def
+                    .light_opacity
+                    .unwrap_or(if def.opaque { Light::MAX } else { 0 })
+                    .min(Light::MAX),
+                model: def.model,
+                properties,
+                first_state: first_state as u16,
+            };
+            block.id = block.get_properties().fold(block.id, |state, property| {
+                block.with_value(state, property, property.default_value())
+            });
+
+            if by_name.insert(block.name.clone(), block.id).is_some() {
+                return Err(invalid(format!("block `{}` is defined twice", block.name)));
+            }
+
+            let mut faces = [0; 6];
+
+            // air has no faces to texture
+            if block.id != BlockId::AIR {
+                for face in Face::ALL {
+                    let name = def.textures.resolve(face).ok_or_else(|| {
+                        invalid(format!(
+                            "block `{}` has no texture for {face:?}",
+                            block.name
+                        ))
+                    })?;
+
+                    faces[face as usize] = texture_layer(&mut textures, name);
+                }
+            }
+
+            // model textures the model leaves to the block are read like face textures
+            let model = match &block.model {
+                Some(name) => Some(models.compile(name, |texture| {
+                    let name = match texture.strip_prefix('#') {
+                        Some(variable) => def.textures.variable(variable)?,
+                        None => texture,
+                    };
+                    Some(texture_layer(&mut textures, name))
+                })?),
+                None => None,
+            };
+
+            block.faces = faces;
+            blocks.push(block);
+            block_models.push(model);
+        }
+
+        let states = blocks
+            .iter()
+            .enumerate()
+            .zip(&block_models)
+            .flat_map(|((i, block), model)| {
+                block.states().map(move |state| {
+                    let faces = Face::ALL.map(|face| {
+                        let (model_face, rotation) = block.model_face(state, face);
+                        FaceTexture {
+                            layer: block.get_texture(model_face),
+                            rotation,
+                        }
+                    });
+                    State {
+                        block: i as u16,
+                        faces,
+                        model: model
+                            .as_ref()
+                            .map(|model| model.transformed(block.model_transform(state))),
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            blocks,
+            by_name,
+            textures,
+            states,
+        })
+    }
+
+    /// The block the state belongs to.
+    pub fn get(&self, id: BlockId) -> Option<&Block> {
+        let state = self.states.get(id.0 as usize)?;
+        self.blocks.get(state.block as usize)
+    }
+
+    /// Default state of the block with the given name.
+    pub fn get_id(&self, name: &str) -> Option<BlockId> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Reads a state written by [`describe_state`](Self::describe_state). Properties left
+    /// out keep their default value, unknown ones or values make it `None`.
+    pub fn parse_state(&self, state: &str) -> Option<BlockId> {
+        let (name, properties) = match state.split_once('[') {
+            Some((name, properties)) => (name, properties.strip_suffix(']')?),
+            None => (state, ""),
+        };
+
+        let mut id = self.get_id(name)?;
+        let block = self.get(id)?;
+
+        for pair in properties.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let property = Property::from_name(key.trim())?;
+            let value = property.parse_value(value.trim())?;
+
+            let changed = block.with_value(id, property, value);
+            if block.get_value(changed, property) != Some(value) {
+                return None;
+            }
+            id = changed;
+        }
+
+        Some(id)
+    }
+
+    /// `name[key=value,...]`, the canonical name of a state.
+    pub fn describe_state(&self, id: BlockId) -> Option<String> {
+        Some(self.get(id)?.describe(id))
+    }
+
+    /// Texture drawn on the face of the state, turned the way the state lies.
+    pub fn get_face(&self, id: BlockId, face: Face) -> FaceTexture {
+        self.states
+            .get(id.0 as usize)
+            .map_or_else(FaceTexture::default, |state| state.faces[face as usize])
+    }
+
+    /// Model the state is drawn with, turned the way the state lies. `None` for full cubes.
+    pub fn get_model(&self, id: BlockId) -> Option<&BlockModel> {
+        self.states.get(id.0 as usize)?.model.as_ref()
+    }
+
+    /// Every state id, in order.
+    pub fn states(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.states.len() as u16).map(BlockId)
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Texture names, the position of a name is its texture array layer.
+    pub fn get_textures(&self) -> &[String] {
+        &self.textures
+    }
+}
+
+#[derive(Deserialize)]
+struct BlockDef {
+    id: u16,
+    name: String,
+    #[serde(default)]
+    textures: TextureDef,
+    #[serde(default = "default_true")]
+    opaque: bool,
+    #[serde(default = "default_true")]
+    solid: bool,
+    #[serde(default)]
+    light: u8,
+    #[serde(default = "default_light_color")]
+    light_color: [f32; 3],
+    /// Opaque blocks block light entirely, others let it through unless told otherwise.
+    light_opacity: Option<u8>,
+    model: Option<String>,
+    #[serde(default)]
+    properties: Vec<Property>,
+    /// Last growth stage, for blocks with an age.
+    #[serde(default = "default_max_age")]
+    max_age: u8,
+}
+
+/// The most specific entry wins: a face name, then `top`/`bottom`/`side`, then `all`.
+#[derive(Deserialize, Default)]
+struct TextureDef {
+    all: Option<String>,
+    top: Option<String>,
+    bottom: Option<String>,
+    side: Option<String>,
+    front: Option<String>,
+    back: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+impl TextureDef {
+    fn resolve(&self, face: Face) -> Option<&str> {
+        let (specific, group) = match face {
+            Face::Front => (&self.front, &self.side),
+            Face::Back => (&self.back, &self.side),
+            Face::Left => (&self.left, &self.side),
+            Face::Right => (&self.right, &self.side),
+            Face::Up => (&None, &self.top),
+            Face::Down => (&None, &self.bottom),
+        };
+
+        specific
+            .as_ref()
+            .or(group.as_ref())
+            .or(self.all.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Texture for a model's `#variable`, falling back the way face textures do.
+    fn variable(&self, name: &str) -> Option<&str> {
+        let specific = match name {
+            "top" => &self.top,
+            "bottom" => &self.bottom,
+            "side" => &self.side,
+            "front" => &self.front,
+            "back" => &self.back,
+            "left" => &self.left,
+            "right" => &self.right,
+            _ => &None,
+        };
+        let group = match name {
+            "front" | "back" | "left" | "right" => &self.side,
+            _ => &None,
+        };
+
+        specific
+            .as_ref()
+            .or(group.as_ref())
+            .or(self.all.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// Texture array layer of the texture, giving it the next one if it's new.
+fn texture_layer(textures: &mut Vec<String>, name: &str) -> u32 {
+    match textures.iter().position(|t| t == name) {
+        Some(layer) => layer as u32,
+        None => {
+            textures.push(name.to_string());
+            textures.len() as u32 - 1
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_age() -> u8 {
+    7
+}
+
+/// A warm white, like a torch.
+fn default_light_color() -> [f32; 3] {
+    [1.0, 0.9, 0.75]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Age, Waterlogged};
+
+    const BLOCKS: &str = r#"[
+        { "id": 0, "name": "air", "opaque": false, "solid": false },
+        { "id": 1, "name": "log", "textures": { "side": "bark", "top": "rings", "bottom": "rings" }, "properties": ["axis"] },
+        { "id": 2, "name": "crop", "textures": { "all": "crop" }, "properties": ["age", "waterlogged"], "max_age": 3 },
+        { "id": 3, "name": "furnace", "textures": { "all": "stone", "front": "furnace" }, "properties": ["facing"] },
+        { "id": 4, "name": "stone", "textures": { "all": "stone" } }
+    ]"#;
+
+    #[test]
+    fn states_take_consecutive_ids() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let id = |name| registry.get_id(name).unwrap();
+
+        // 1 + 3 + 4 * 2 + 4 + 1
+        assert_eq!(registry.states().count(), 17);
+        assert_eq!(id("log"), BlockId(2));
+        assert_eq!(id("stone"), BlockId(16));
+
+        let crop = registry.get(id("crop")).unwrap();
+        assert_eq!(crop.states().count(), 8);
+        for state in crop.states() {
+            assert_eq!(registry.get(state).unwrap().name, "crop");
+
+            let name = registry.describe_state(state).unwrap();
+            assert_eq!(registry.parse_state(&name), Some(state), "{name}");
+        }
+
+        let grown = crop.with(crop.with(id("crop"), Age(3)), Waterlogged(true));
+        assert_eq!(
+            registry.describe_state(grown).unwrap(),
+            "crop[age=3,waterlogged=true]"
+        );
+        assert_eq!(crop.get::<Age>(grown), Some(Age(3)));
+        assert_eq!(crop.with(grown, Age(4)), grown);
+        assert_eq!(crop.get::<Axis>(grown), None);
+
+        assert_eq!(
+            registry.parse_state("crop[age=2]"),
+            Some(crop.with(id("crop"), Age(2)))
+        );
+        assert_eq!(registry.parse_state("crop[axis=x]"), None);
+        assert_eq!(registry.parse_state("crop[age=9]"), None);
+        assert_eq!(registry.parse_state("stone[]"), Some(id("stone")));
+    }
+
+    #[test]
+    fn ages_must_fit_in_a_byte() {
+        let blocks = |max_age| {
+            format!(
+                r#"[
+                    {{ "id": 0, "name": "air" }},
+                    {{ "id": 1, "name": "crop", "textures": {{ "all": "crop" }}, "properties": ["age"], "max_age": {max_age} }}
+                ]"#
+            )
+        };
+
+        let registry = BlockRegistry::from_str(&blocks(254)).unwrap();
+        assert_eq!(registry.states().count(), 1 + 255);
+        assert!(BlockRegistry::from_str(&blocks(255)).is_err());
+    }
+
+    #[test]
+    fn faces_turn_with_the_state() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let layer = |name: &str| {
+            registry
+                .get_textures()
+                .iter()
+                .position(|t| t == name)
+                .unwrap() as u32
+        };
+
+        let lying = registry.parse_state("log[axis=x]").unwrap();
+        assert_eq!(registry.get_face(lying, Face::Right).layer, layer("rings"));
+        assert_eq!(registry.get_face(lying, Face::Up).layer, layer("bark"));
+        assert_eq!(registry.get_face(lying, Face::Up).rotation, 1);
+
+        let furnace = registry.parse_state("furnace[facing=east]").unwrap();
+        assert_eq!(
+            registry.get_face(furnace, Face::Right).layer,
+            layer("furnace")
+        );
+        assert_eq!(
+            registry.get_face(furnace, Face::Front).layer,
+            layer("stone")
+        );
+    }
+
+    #[test]
+    fn placing_lines_blocks_up_with_the_click() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let log = registry.get(registry.get_id("log").unwrap()).unwrap();
+        let furnace = registry.get(registry.get_id("furnace").unwrap()).unwrap();
+
+        let placement = Placement {
+            normal: glam::IVec3::NEG_Z,
+            point: glam::vec3(0.5, 0.8, 1.0),
+            look: glam::vec3(0.3, -0.2, -0.9),
+        };
+        assert_eq!(log.get::<Axis>(log.place(&placement)), Some(Axis::Z));
+        assert_eq!(
+            furnace.get::<Facing>(furnace.place(&placement)),
+            Some(Facing::North)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use glam::{IVec3, Vec3};
+use serde::Deserialize;
+
+/// Something that sets states of a block apart, like which way a log lies.
+/// Every combination of values of a block's properties is a state with its own id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Property {
+    /// Horizontal direction the block faces.
+    Facing,
+    /// Axis the block lies along.
+    Axis,
+    /// Whether the block takes the top or bottom of its cell.
+    Half,
+    /// Whether water shares the cell with the block.
+    Waterlogged,
+    /// Growth stage, counting up from 0.
+    Age,
+}
+
+impl Property {
+    pub const ALL: [Property; 5] = [
+        Property::Facing,
+        Property::Axis,
+        Property::Half,
+        Property::Waterlogged,
+        Property::Age,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Property::Facing => "facing",
+            Property::Axis => "axis",
+            Property::Half => "half",
+            Property::Waterlogged => "waterlogged",
+            Property::Age => "age",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|property| property.name() == name)
+    }
+
+    /// Number of values the property takes, ages count up to the block's own maximum,
+    /// which the registry keeps below 255.
+    pub(crate) fn value_count(self, max_age: u8) -> u8 {
+        match self {
+            Property::Facing => 4,
+            Property::Axis => 3,
+            Property::Half | Property::Waterlogged => 2,
+            Property::Age => max_age.saturating_add(1),
+        }
+    }
+
+    /// Value a newly placed or generated block takes.
+    pub(crate) fn default_value(self) -> u8 {
+        match self {
+            Property::Axis => Axis::Y.to_index(),
+            _ => 0,
+        }
+    }
+
+    /// How the value is written in state names.
+    pub fn value_name(self, value: u8) -> String {
+        match self {
+            Property::Facing => Facing::from_index(value).name().into(),
+            Property::Axis => Axis::from_index(value).name().into(),
+            Property::Half => Half::from_index(value).name().into(),
+            Property::Waterlogged => (value != 0).to_string(),
+            Property::Age => value.to_string(),
+        }
+    }
+
+    pub fn parse_value(self, name: &str) -> Option<u8> {
+        match self {
+            Property::Facing => Facing::ALL
+                .into_iter()
+                .find(|facing| facing.name() == name)
+                .map(Facing::to_index),
+            Property::Axis => Axis::ALL
+                .into_iter()
+                .find(|axis| axis.name() == name)
+                .map(Axis::to_index),
+            Property::Half => [Half::Bottom, Half::Top]
+                .into_iter()
+                .find(|half| half.name() == name)
+                .map(Half::to_index),
+            Property::Waterlogged => name.parse::<bool>().ok().map(u8::from),
+            Property::Age => name.parse().ok(),
+        }
+    }
+}
+
+/// Typed values of a property, read and set through [`Block::get`](super::Block::get)
+/// and [`Block::with`](super::Block::with).
+pub trait PropertyValue: Copy {
+    const PROPERTY: Property;
+
+    fn from_index(index: u8) -> Self;
+    fn to_index(self) -> u8;
+}
+
+/// A horizontal direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Facing {
+    /// -Z
+    North,
+    /// +Z
+    South,
+    /// -X
+    West,
+    /// +X
+    East,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 4] = [Facing::North, Facing::South, Facing::West, Facing::East];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Facing::North => "north",
+            Facing::South => "south",
+            Facing::West => "west",
+            Facing::East => "east",
+        }
+    }
+
+    pub fn normal(self) -> IVec3 {
+        match self {
+            Facing::North => IVec3::NEG_Z,
+            Facing::South => IVec3::Z,
+            Facing::West => IVec3::NEG_X,
+            Facing::East => IVec3::X,
+        }
+    }
+
+    /// The direction closest to the given one, ignoring its height.
+    pub fn from_direction(direction: Vec3) -> Self {
+        if direction.x.abs() > direction.z.abs() {
+            if direction.x > 0.0 {
+                Facing::East
+            } else {
+                Facing::West
+            }
+        } else if direction.z > 0.0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+
+    /// Quarter turns clockwise, seen from above, that take south to this direction.
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Facing::South => 0,
+            Facing::West => 1,
+            Facing::North => 2,
+            Facing::East => 3,
+        }
+    }
+}
+
+impl PropertyValue for Facing {
+    const PROPERTY: Property = Property::Facing;
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL[index as usize % 4]
+    }
+
+    fn to_index(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+
+    /// Axis the normal points along.
+    pub fn from_normal(normal: IVec3) -> Self {
+        Self::ALL[normal.abs().max_position()]
+    }
+}
+
+impl PropertyValue for Axis {
+    const PROPERTY: Property = Property::Axis;
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL[index as usize % 3]
+    }
+
+    fn to_index(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+impl Half {
+    pub fn name(self) -> &'static str {
+        match self {
+            Half::Bottom => "bottom",
+            Half::Top => "top",
+        }
+    }
+}
+
+impl PropertyValue for Half {
+    const PROPERTY: Property = Property::Half;
+
+    fn from_index(index: u8) -> Self {
+        if index == 0 {
+            Half::Bottom
+        } else {
+            Half::Top
+        }
+    }
+
+    fn to_index(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Waterlogged(pub bool);
+
+impl PropertyValue for Waterlogged {
+    const PROPERTY: Property = Property::Waterlogged;
+
+    fn from_index(index: u8) -> Self {
+        Self(index != 0)
+    }
+
+    fn to_index(self) -> u8 {
+        self.0 as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Age(pub u8);
+
+impl PropertyValue for Age {
+    const PROPERTY: Property = Property::Age;
+
+    fn from_index(index: u8) -> Self {
+        Self(index)
+    }
+
+    fn to_index(self) -> u8 {
+        self.0
+    }
+}
+
+/// Where and how a block is being placed, deciding the state it's placed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Normal of the face clicked on, pointing towards the new block.
+    pub normal: IVec3,
+    /// Point clicked on, relative to the new block's lowest corner.
+    pub point: Vec3,
+    /// Direction the player looks in.
+    pub look: Vec3,
+}
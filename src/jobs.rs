@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::block::BlockRegistry;
+use crate::light;
+use crate::mesh::{self, MeshMode, Vertex};
+use crate::renderer::{self, PointLight};
+use crate::save::WorldSave;
+use crate::world::{Chunk, ChunkPos, World};
+use crate::worldgen::Generator;
+
+pub enum Job {
+    /// Loads the chunk from the save, or generates it if it was never saved, and lights
+    /// it on its own.
+    Generate(ChunkPos),
+    Mesh {
+        pos: ChunkPos,
+        /// The chunk and its neighbours, see `World::neighbourhood`.
+        world: World,
+        mode: MeshMode,
+        /// Handed back with the result so outdated meshes can be told apart.
+        version: u64,
+    },
+}
+
+pub enum JobResult {
+    Generated(Box<Chunk>),
+    Meshed {
+        pos: ChunkPos,
+        vertices: Vec<Vertex>,
+        /// Lights of the blocks of the chunk giving off light.
+        lights: Vec<PointLight>,
+        version: u64,
+    },
+}
+
+/// The latest mesh job sent off for each chunk, so meshes outdated by a later edit
+/// or by the chunk unloading are dropped.
+#[derive(Debug, Default)]
+pub struct MeshVersions {
+    latest: HashMap<ChunkPos, u64>,
+    next: u64,
+}
+
+impl MeshVersions {
+    /// Version of a new mesh job for the chunk, outdating the earlier ones.
+    pub fn next(&mut self, pos: ChunkPos) -> u64 {
+        let version = self.next;
+        self.next += 1;
+        self.latest.insert(pos, version);
+        version
+    }
+
+    /// Whether the mesh came from the latest job for the chunk, which is then done with.
+    pub fn finish(&mut self, pos: ChunkPos, version: u64) -> bool {
+        if self.latest.get(&pos) != Some(&version) {
+            return false;
+        }
+
+        self.latest.remove(&pos);
+        true
+    }
+
+    /// Outdates every job for the chunk.
+    pub fn forget(&mut self, pos: ChunkPos) {
+        self.latest.remove(&pos);
+    }
+}
+
+/// Worker threads running chunk generation and meshing.
+/// Results only hold CPU data, uploading them is left to the main thread.
+pub struct JobSystem {
+    sender: Option<mpsc::Sender<Job>>,
+    receiver: mpsc::Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+    generator: Arc<dyn Generator>,
+}
+
+impl JobSystem {
+    pub fn new(
+        worker_count: usize,
+        registry: Arc<BlockRegistry>,
+        generator: Arc<dyn Generator>,
+        save: Option<Arc<WorldSave>>,
+    ) -> Self {
+        let (sender, jobs) = mpsc::channel::<Job>();
+        let (results, receiver) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let workers = (0..worker_count.max(1))
+            .map(|i| {
+                let jobs = Arc::clone(&jobs);
+                let results = results.clone();
+                let registry = Arc::clone(&registry);
+                let generator = Arc::clone(&generator);
+                let save = save.clone();
+                let running = Arc::clone(&running);
+
+                std::thread::Builder::new()
+                    .name(format!("chunk-worker-{i}"))
+                    .spawn(move || {
+                        // the lock is released as soon as a job is taken
+                        while let Ok(job) = { jobs.lock().unwrap().recv() } {
+                            // skip whatever is still queued when shutting down
+                            if !running.load(Ordering::Relaxed) {
+                                break;
+                            }
+
+                            let result = run(job, &registry, generator.as_ref(), save.as_deref());
+
+                            // the receiving end is gone, the system is shutting down
+                            if results.send(result).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("failed to spawn chunk worker")
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            receiver,
+            workers,
+            running,
+            generator,
+        }
+    }
+
+    /// One worker per core, leaving one for the main thread.
+    pub fn default_worker_count() -> usize {
+        std::thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1))
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    pub fn submit(&self, job: Job) {
+        if let Some(sender) = &self.sender {
+            sender.send(job).expect("all chunk workers died");
+        }
+    }
+
+    /// Lets the generator forget about a chunk that was unloaded.
+    pub fn unload(&self, pos: ChunkPos) {
+        self.generator.unload(pos);
+    }
+
+    /// Next finished job, if any, without blocking.
+    pub fn try_recv(&self) -> Option<JobResult> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        // closing the channel wakes up idle workers so they can exit
+        self.running.store(false, Ordering::Relaxed);
+        self.sender.take();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run(
+    job: Job,
+    registry: &BlockRegistry,
+    generator: &dyn Generator,
+    save: Option<&WorldSave>,
+) -> JobResult {
+    match job {
+        Job::Generate(pos) => {
+            let saved = save.and_then(|save| {
+                save.load_chunk(pos).unwrap_or_else(|error| {
+                    // regenerating beats leaving a hole in the world
+                    eprintln!("failed to load chunk {}, {}: {error}", pos.x, pos.z);
+                    None
+                })
+            });
+
+            let mut chunk = saved.unwrap_or_else(|| generator.generate(pos));
+            light::light_chunk(&mut chunk, registry);
+            JobResult::Generated(Box::new(chunk))
+        }
+
+        Job::Mesh {
+            pos,
+            world,
+            mode,
+            version,
+        } => JobResult::Meshed {
+            pos,
+            vertices: mesh::mesh_chunk(&world, registry, pos, mode),
+            lights: world
+                .get_chunk(pos)
+                .map(|chunk| renderer::block_lights(chunk, registry))
+                .unwrap_or_default(),
+            version,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    use glam::IVec3;
+
+    use super::*;
+    use crate::worldgen::FlatGenerator;
+
+    fn registry() -> Arc<BlockRegistry> {
+        Arc::new(BlockRegistry::from_file("res/blocks.json").unwrap())
+    }
+
+    /// Waits for the next finished job, failing the test if it takes too long.
+    fn recv(jobs: &JobSystem) -> JobResult {
+        let start = Instant::now();
+        loop {
+            if let Some(result) = jobs.try_recv() {
+                return result;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10), "no job finished");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Takes a while over every chunk and counts them.
+    struct SlowGenerator(Arc<AtomicUsize>);
+
+    impl Generator for SlowGenerator {
+        fn generate(&self, pos: ChunkPos) -> Chunk {
+            std::thread::sleep(Duration::from_millis(20));
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Chunk::new(pos)
+        }
+
+        fn get_spawn(&self) -> IVec3 {
+            IVec3::ZERO
+        }
+    }
+
+    #[test]
+    fn submitted_jobs_come_back_finished() {
+        let registry = registry();
+        let generator = FlatGenerator::parse(FlatGenerator::DEFAULT_LAYERS, &registry).unwrap();
+        let jobs = JobSystem::new(2, Arc::clone(&registry), Arc::new(generator), None);
+        assert!(jobs.try_recv().is_none());
+
+        let pos = ChunkPos::new(3, -1);
+        jobs.submit(Job::Generate(pos));
+        let JobResult::Generated(chunk) = recv(&jobs) else {
+            panic!("expected a generated chunk");
+        };
+        assert_eq!(chunk.get_position(), pos);
+
+        let mut world = World::new();
+        world.insert_chunk(*chunk);
+        jobs.submit(Job::Mesh {
+            pos,
+            world,
+            mode: MeshMode::Greedy,
+            version: 7,
+        });
+        let JobResult::Meshed {
+            pos: meshed,
+            vertices,
+            lights,
+            version,
+        } = recv(&jobs)
+        else {
+            panic!("expected a mesh");
+        };
+        assert_eq!((meshed, version), (pos, 7));
+        assert!(!vertices.is_empty());
+        assert!(lights.is_empty());
+    }
+
+    #[test]
+    fn dropping_stops_the_workers() {
+        let generated = Arc::new(AtomicUsize::new(0));
+        let generator = SlowGenerator(Arc::clone(&generated));
+        let jobs = JobSystem::new(2, registry(), Arc::new(generator), None);
+
+        for x in 0..100 {
+            jobs.submit(Job::Generate(ChunkPos::new(x, 0)));
+        }
+        drop(jobs);
+
+        // the workers were joined, skipping what was still queued
+        let count = generated.load(Ordering::Relaxed);
+        assert!(count < 100);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(generated.load(Ordering::Relaxed), count);
+    }
+
+    #[test]
+    fn only_the_latest_mesh_of_a_chunk_is_taken() {
+        let mut versions = MeshVersions::default();
+        let (a, b) = (ChunkPos::new(0, 0), ChunkPos::new(1, 0));
+
+        let old = versions.next(a);
+        let new = versions.next(a);
+        let other = versions.next(b);
+
+        assert!(!versions.finish(a, old));
+        assert!(versions.finish(a, new));
+        // taken once only
+        assert!(!versions.finish(a, new));
+
+        versions.forget(b);
+        assert!(!versions.finish(b, other));
+    }
+}
@@ -0,0 +1,11 @@
+//! World model, generation, meshing and saving, kept apart from the window and renderer
+//! so tools like the map exporter run without a GPU.
+
+pub mod block;
+pub mod light;
+pub mod mesh;
+pub mod nbt;
+pub mod save;
+pub mod schematic;
+pub mod world;
+pub mod worldgen;
@@ -0,0 +1,466 @@
+//! Sky and block light, spread from cell to cell by flood fill. Light fades by a level
+//! for every block it goes and by the opacity of the blocks it goes through, only sky
+//! light coming straight down through clear blocks doesn't fade at all.
+
+use std::collections::{HashSet, VecDeque};
+
+use glam::{ivec3, IVec3};
+
+use crate::block::BlockRegistry;
+use crate::world::{
+    BlockId, Chunk, ChunkPos, Light, LightKind, World, CHUNK_SIZE, MAX_Y, MIN_Y, SECTION_COUNT,
+};
+
+const KINDS: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Works out the light of a chunk on its own, as if its neighbours were dark. Chunks are
+/// lit like this when they're generated or loaded, [`join_chunk`] then lets light in
+/// from and out to their neighbours.
+pub fn light_chunk(chunk: &mut Chunk, registry: &BlockRegistry) {
+    let origin = chunk.get_position().origin();
+    chunk.fill_light(Light::DARK);
+
+    // the sky reaches straight down to the first block that holds any of it back
+    let mut heights = [[MAX_Y; CHUNK_SIZE as usize]; CHUNK_SIZE as usize];
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let mut y = MAX_Y;
+            while y > MIN_Y && opacity(registry, chunk.get_block(x, y - 1, z)) == 0 {
+                y -= 1;
+                chunk.set_light(x, y, z, Light::SKY);
+            }
+            heights[x as usize][z as usize] = y;
+        }
+    }
+
+    // the lowest cells of each column light what lies below them, and cells next to
+    // columns the sky doesn't reach as far down in light those from the side
+    let mut queue = VecDeque::new();
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let height = heights[x as usize][z as usize];
+            let top = [(x - 1, z), (x + 1, z), (x, z - 1), (x, z + 1)]
+                .into_iter()
+                .filter(|&(x, z)| (0..CHUNK_SIZE).contains(&x) && (0..CHUNK_SIZE).contains(&z))
+                .map(|(x, z)| heights[x as usize][z as usize])
+                .fold(height + 1, i32::max)
+                .min(MAX_Y);
+
+            queue.extend((height..top).map(|y| ivec3(origin.x + x, y, origin.z + z)));
+        }
+    }
+
+    let mut grid = ChunkGrid { chunk, origin };
+    spread(&mut grid, registry, LightKind::Sky, &mut queue);
+
+    for pos in emitters(grid.chunk, registry) {
+        let (x, y, z) = (pos.x - origin.x, pos.y, pos.z - origin.z);
+        let level = emission(registry, grid.chunk.get_block(x, y, z));
+        let light = grid.chunk.get_light(x, y, z);
+        grid.chunk
+            .set_light(x, y, z, light.with(LightKind::Block, level));
+        queue.push_back(pos);
+    }
+    spread(&mut grid, registry, LightKind::Block, &mut queue);
+
+    grid.chunk.compact_light();
+}
+
+/// World positions of the blocks in the chunk that give off light.
+pub fn emitters(chunk: &Chunk, registry: &BlockRegistry) -> Vec<IVec3> {
+    let origin = chunk.get_position().origin();
+    let mut emitters = vec![];
+
+    for index in 0..SECTION_COUNT {
+        let glowing = chunk
+            .get_section(index)
+            .is_some_and(|section| section.palette().any(|block| emission(registry, block) > 0));
+        if !glowing {
+            continue;
+        }
+
+        let bottom = MIN_Y + index as i32 * CHUNK_SIZE;
+        for y in bottom..bottom + CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if emission(registry, chunk.get_block(x, y, z)) > 0 {
+                        emitters.push(ivec3(origin.x + x, y, origin.z + z));
+                    }
+                }
+            }
+        }
+    }
+
+    emitters
+}
+
+/// Lets light flow between a chunk lit on its own with [`light_chunk`] and its loaded
+/// neighbours. Returns the chunks whose meshes show light that changed.
+pub fn join_chunk(world: &mut World, registry: &BlockRegistry, pos: ChunkPos) -> HashSet<ChunkPos> {
+    let origin = pos.origin();
+    let mut grid = WorldGrid::new(world);
+    let last = CHUNK_SIZE - 1;
+
+    for kind in KINDS {
+        let mut queue = VecDeque::new();
+
+        for (direction, border) in [
+            (IVec3::X, ivec3(last, 0, 0)),
+            (IVec3::NEG_X, IVec3::ZERO),
+            (IVec3::Z, ivec3(0, 0, last)),
+            (IVec3::NEG_Z, IVec3::ZERO),
+        ] {
+            let neighbour = ChunkPos::from_block(origin + direction * CHUNK_SIZE);
+            if grid.world.get_chunk(neighbour).is_none() {
+                continue;
+            }
+
+            // cells along the shared border, the brighter side of each pair lights the other
+            let along = IVec3::ONE - direction.abs() - IVec3::Y;
+            for y in MIN_Y..MAX_Y {
+                for i in 0..CHUNK_SIZE {
+                    let inside = ivec3(origin.x, y, origin.z) + border + along * i;
+                    let outside = inside + direction;
+
+                    let a = grid.world.get_light(inside).get(kind);
+                    let b = grid.world.get_light(outside).get(kind);
+                    if a > b + 1 {
+                        queue.push_back(inside);
+                    } else if b > a + 1 {
+                        queue.push_back(outside);
+                    }
+                }
+            }
+        }
+
+        spread(&mut grid, registry, kind, &mut queue);
+    }
+
+    grid.changed
+}
+
+/// Relights the world around a cell whose block changed, taking away the light the old
+/// block let through or gave off. Returns the chunks whose meshes show light that changed.
+pub fn update_block(world: &mut World, registry: &BlockRegistry, pos: IVec3) -> HashSet<ChunkPos> {
+    let mut grid = WorldGrid::new(world);
+    let Some((block, _)) = grid.get(pos) else {
+        return grid.changed;
+    };
+
+    for kind in KINDS {
+        let Some((_, light)) = grid.get(pos) else {
+            continue;
+        };
+
+        let source = match kind {
+            LightKind::Sky => 0,
+            LightKind::Block => emission(registry, block),
+        };
+        let mut removed = VecDeque::from([(pos, light.get(kind))]);
+        let mut refill = VecDeque::new();
+
+        grid.set_light(pos, light.with(kind, source));
+        if source > 0 {
+            refill.push_back(pos);
+        }
+        unspread(&mut grid, registry, kind, &mut removed, &mut refill);
+
+        // light around the cell flows back in, as far as the new block lets it
+        refill.extend(DIRECTIONS.map(|direction| pos + direction));
+        spread(&mut grid, registry, kind, &mut refill);
+    }
+
+    grid.changed
+}
+
+/// Cells light spreads through.
+trait Grid {
+    /// Block and light of the cell, `None` where light can't go.
+    fn get(&self, pos: IVec3) -> Option<(BlockId, Light)>;
+    fn set_light(&mut self, pos: IVec3, light: Light);
+}
+
+/// A single chunk, as if surrounded by darkness.
+struct ChunkGrid<'a> {
+    chunk: &'a mut Chunk,
+    origin: IVec3,
+}
+
+impl Grid for ChunkGrid<'_> {
+    fn get(&self, pos: IVec3) -> Option<(BlockId, Light)> {
+        let local = pos - self.origin;
+        let inside = (0..CHUNK_SIZE).contains(&local.x) && (0..CHUNK_SIZE).contains(&local.z);
+        if !inside || pos.y < MIN_Y {
+            return None;
+        }
+
+        Some((
+            self.chunk.get_block(local.x, pos.y, local.z),
+            self.chunk.get_light(local.x, pos.y, local.z),
+        ))
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: Light) {
+        let local = pos - self.origin;
+        self.chunk.set_light(local.x, pos.y, local.z, light);
+    }
+}
+
+/// Loaded chunks, keeping track of which meshes the changes show up in.
+struct WorldGrid<'a> {
+    world: &'a mut World,
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'a> WorldGrid<'a> {
+    fn new(world: &'a mut World) -> Self {
+        Self {
+            world,
+            changed: HashSet::new(),
+        }
+    }
+}
+
+impl Grid for WorldGrid<'_> {
+    fn get(&self, pos: IVec3) -> Option<(BlockId, Light)> {
+        let chunk = self.world.get_chunk(ChunkPos::from_block(pos))?;
+        if pos.y < MIN_Y {
+            return None;
+        }
+
+        let (x, z) = (pos.x.rem_euclid(CHUNK_SIZE), pos.z.rem_euclid(CHUNK_SIZE));
+        Some((chunk.get_block(x, pos.y, z), chunk.get_light(x, pos.y, z)))
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: Light) {
+        self.world.set_light(pos, light);
+
+        // faces of the neighbouring chunk show the light of cells along the border
+        let chunk = ChunkPos::from_block(pos);
+        self.changed.insert(chunk);
+        for direction in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+            let neighbour = ChunkPos::from_block(pos + direction);
+            if neighbour != chunk && self.world.get_chunk(neighbour).is_some() {
+                self.changed.insert(neighbour);
+            }
+        }
+    }
+}
+
+/// Spreads the light of the queued cells outwards, brightening every cell it reaches
+/// that was darker.
+fn spread(
+    grid: &mut impl Grid,
+    registry: &BlockRegistry,
+    kind: LightKind,
+    queue: &mut VecDeque<IVec3>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let Some((_, light)) = grid.get(pos) else {
+            continue;
+        };
+        let level = light.get(kind);
+        if level <= 1 {
+            continue;
+        }
+
+        for direction in DIRECTIONS {
+            let next = pos + direction;
+            let Some((block, next_light)) = grid.get(next) else {
+                continue;
+            };
+
+            let reached = faded(registry, kind, level, block, direction);
+            if reached > next_light.get(kind) {
+                grid.set_light(next, next_light.with(kind, reached));
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Darkens the cells lit through the removed ones, queued with the level they had. Cells
+/// lit some other way are queued in `refill` to spread their light back.
+fn unspread(
+    grid: &mut impl Grid,
+    registry: &BlockRegistry,
+    kind: LightKind,
+    removed: &mut VecDeque<(IVec3, u8)>,
+    refill: &mut VecDeque<IVec3>,
+) {
+    while let Some((pos, level)) = removed.pop_front() {
+        for direction in DIRECTIONS {
+            let next = pos + direction;
+            let Some((block, light)) = grid.get(next) else {
+                continue;
+            };
+            let next_level = light.get(kind);
+            if next_level == 0 {
+                continue;
+            }
+
+            let lit_by_removed = next_level < level
+                || next_level == Light::MAX
+                    && faded(registry, kind, level, block, direction) == Light::MAX;
+            if !lit_by_removed {
+                refill.push_back(next);
+                continue;
+            }
+
+            // blocks giving off light keep their own
+            let source = match kind {
+                LightKind::Sky => 0,
+                LightKind::Block => emission(registry, block),
+            };
+            grid.set_light(next, light.with(kind, source));
+            if source > 0 {
+                refill.push_back(next);
+            }
+            removed.push_back((next, next_level));
+        }
+    }
+}
+
+/// Level light lit with `level` reaches the neighbouring `block` in `direction` with.
+fn faded(
+    registry: &BlockRegistry,
+    kind: LightKind,
+    level: u8,
+    block: BlockId,
+    direction: IVec3,
+) -> u8 {
+    let opacity = opacity(registry, block);
+    if opacity >= Light::MAX {
+        return 0;
+    }
+
+    let straight_down = kind == LightKind::Sky && direction == IVec3::NEG_Y;
+    if straight_down && level == Light::MAX && opacity == 0 {
+        return Light::MAX;
+    }
+
+    level.saturating_sub(1 + opacity)
+}
+
+fn opacity(registry: &BlockRegistry, block: BlockId) -> u8 {
+    registry.get(block).map_or(0, |block| block.light_opacity)
+}
+
+fn emission(registry: &BlockRegistry, block: BlockId) -> u8 {
+    registry.get(block).map_or(0, |block| block.light)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::noise::Rng;
+
+    fn lit_world(registry: &BlockRegistry, world: &World) -> World {
+        let mut lit = World::new();
+        let positions: Vec<_> = world.chunks().map(Chunk::get_position).collect();
+
+        for chunk in world.chunks() {
+            let mut chunk = chunk.clone();
+            light_chunk(&mut chunk, registry);
+            lit.insert_chunk(chunk);
+        }
+        for pos in positions {
+            join_chunk(&mut lit, registry, pos);
+        }
+
+        lit
+    }
+
+    #[test]
+    fn sky_light_fades_under_a_roof() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let stone = registry.get_id("stone").unwrap();
+        let leaves = registry.get_id("leaves").unwrap();
+
+        // a roof over the western half of the chunk, with a leaf in the open half
+        let mut world = World::new();
+        for x in 0..8 {
+            for z in 0..16 {
+                world.set_block(ivec3(x, 10, z), stone);
+            }
+        }
+        world.set_block(ivec3(12, 10, 8), leaves);
+        let world = lit_world(&registry, &world);
+
+        let sky = |x, y, z| world.get_light(ivec3(x, y, z)).get_sky();
+        assert_eq!(sky(3, 11, 3), 15);
+        assert_eq!(sky(3, 10, 3), 0);
+        assert_eq!(sky(7, 9, 3), 14);
+        assert_eq!(sky(3, 9, 3), 10);
+        assert_eq!(sky(3, -60, 3), 10);
+        // the leaf takes a level off the light going through it, the sides light what's below
+        assert_eq!(sky(12, 10, 8), 13);
+        assert_eq!(sky(12, 9, 8), 14);
+        assert_eq!(sky(12, 9, 9), 15);
+    }
+
+    #[test]
+    fn edits_light_the_world_like_lighting_it_from_scratch() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let blocks =
+            ["stone", "torch", "leaves", "stone_slab"].map(|name| registry.get_id(name).unwrap());
+
+        // four chunks of caves under a floor, with a few torches
+        let mut rng = Rng::new(21);
+        let mut world = World::new();
+        for x in -16..16 {
+            for z in -16..16 {
+                world.set_block(ivec3(x, 0, z), blocks[0]);
+                for y in -8..0 {
+                    if rng.range_i32(0, 3) == 0 {
+                        world.set_block(ivec3(x, y, z), blocks[0]);
+                    }
+                }
+            }
+        }
+        world.set_block(ivec3(0, -4, 0), blocks[1]);
+        world.set_block(ivec3(-3, 2, 5), blocks[1]);
+        let mut world = lit_world(&registry, &world);
+
+        for i in 0..300 {
+            let pos = ivec3(
+                rng.range_i32(-16, 16),
+                rng.range_i32(-9, 3),
+                rng.range_i32(-16, 16),
+            );
+            let block = match rng.range_i32(0, 8) {
+                0..=3 => BlockId::AIR,
+                i => blocks[i as usize - 4],
+            };
+
+            world.set_block(pos, block);
+            update_block(&mut world, &registry, pos);
+
+            if i % 50 == 49 {
+                let expected = lit_world(&registry, &world);
+                for x in -16..16 {
+                    for y in -12..6 {
+                        for z in -16..16 {
+                            let pos = ivec3(x, y, z);
+                            assert_eq!(
+                                world.get_light(pos),
+                                expected.get_light(pos),
+                                "{pos} after {} edits",
+                                i + 1
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
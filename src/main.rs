@@ -1,15 +1,33 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 
 use glfw::*;
 use glow::*;
 
+use minecraft::schematic::{Clipboard, Rotation};
+use minecraft::{block, light, mesh, save, world, worldgen};
+
 mod defer;
+mod jobs;
 mod renderer;
 mod window;
 
 const WIDTH: u32 = 1000;
 const HEIGHT: u32 = 1000;
 
+/// How far away blocks can be edited from.
+const REACH: f32 = 8.0;
+/// Radius of loaded chunks around the camera, in chunks.
+const RENDER_DISTANCE: i32 = 10;
+/// Where the world is saved, it's picked up again on the next start.
+const WORLD_DIR: &str = "saves/world";
+/// Where the selection is exported to.
+const EXPORT_PATH: &str = "schematics/selection.schem";
+/// The lantern L lights around the camera.
+const LANTERN_COLOR: glam::Vec3 = glam::vec3(1.0, 0.8, 0.5);
+const LANTERN_RADIUS: f32 = 12.0;
+
 fn main() {
     let window::CreateWindowOutput {
         mut window,
@@ -39,49 +57,117 @@ fn main() {
 
     unsafe { gl.viewport(0, 0, WIDTH as _, HEIGHT as _) };
 
-    let mut instance_positions: Vec<f32> = vec![];
-    let mut instance_texture_ids: Vec<i32> = vec![];
-
-    for block_x in 0..20 {
-        for block_y in 0..20 {
-            for block_z in 0..20 {
-                instance_texture_ids.push(if block_y == 0 { 0 } else { 1 });
-                instance_positions.extend_from_slice(&[
-                    block_x as f32,
-                    -block_y as f32,
-                    block_z as f32,
-                ]);
+    let registry = Arc::new(
+        block::BlockRegistry::from_file("res/blocks.json").expect("failed to load block registry"),
+    );
+
+    // the scroll wheel picks the block middle click places, starting with dirt
+    let mut placed_block = registry
+        .blocks()
+        .iter()
+        .position(|block| block.name == "dirt")
+        .unwrap();
+    let mapping = save::VanillaMapping::from_file(save::VANILLA_MAPPING_PATH, &registry)
+        .expect("failed to load the vanilla block mapping");
+
+    let mut args = std::env::args().skip(1);
+    let seed_arg = args.next();
+    let preset_arg = args.next();
+
+    // edits to vanilla worlds are kept apart, the game's own files are never touched
+    let world_dir = match preset_arg.as_deref().and_then(|p| p.strip_prefix("anvil:")) {
+        Some(folder) => {
+            let name = Path::new(folder).file_name().unwrap_or_default();
+            Path::new(WORLD_DIR).with_file_name(format!("anvil-{}", name.to_string_lossy()))
+        }
+        None => PathBuf::from(WORLD_DIR),
+    };
+
+    let world_save = Arc::new(
+        save::WorldSave::open(&world_dir, Arc::clone(&registry))
+            .expect("failed to open the world save"),
+    );
+    let level = world_save
+        .load_level()
+        .expect("failed to read the level data");
+
+    // the seed and preset only matter for new worlds
+    let (seed, preset) = match &level {
+        Some(level) => {
+            if seed_arg.is_some() {
+                println!(
+                    "continuing the world in {}, the seed and preset are ignored",
+                    world_dir.display()
+                );
             }
+            (level.seed, level.generator.clone())
         }
-    }
+        None => (
+            seed_arg
+                .map(|arg| arg.parse().expect("the seed must be a number"))
+                .unwrap_or(worldgen::DEFAULT_SEED),
+            preset_arg.unwrap_or_else(|| worldgen::DEFAULT_PRESET.into()),
+        ),
+    };
+
+    let generator = worldgen::from_preset(&preset, seed, &registry)
+        .expect("failed to set up the world generator");
+    let spawn_block = generator.get_spawn();
+
+    // eyes two blocks above the ground
+    let spawn = match &level {
+        Some(level) => glam::Vec3::from(level.player.position),
+        None => spawn_block.as_vec3() + glam::vec3(0.5, 2.0, 0.5),
+    };
+    // T skips to the next sunrise, noon, sunset or midnight and F freezes time
+    let mut world_clock = world::WorldClock::new(level.as_ref().map_or(0, |level| level.time));
+    world_clock.set_frozen(level.as_ref().is_some_and(|level| level.time_frozen));
 
     let (width, height) = window.get_size();
 
     let mut keyboard_state = renderer::KeyboardState::default();
     let mut pointer_state = renderer::PointerState::default();
 
-    let light_position = glam::vec3(-2.0, 8.0, -5.0);
-    let light_color = glam::vec3(1.0, 1.0, 1.0);
-
     let mut clock = Instant::now();
     let mut click_start_position = None;
 
+    // dropped schematics are pasted with P and turned with R,
+    // 1 and 2 mark the corners of the selection X exports
+    let mut clipboard: Option<Clipboard> = None;
+    let mut rotation = Rotation::None;
+    let mut selection: [Option<glam::IVec3>; 2] = [None; 2];
+    let mut lantern: Option<renderer::LightId> = None;
+
     let mut renderer = renderer::Renderer::new(
         &gl,
         renderer::Camera::new(
-            (0.0, 2.0, 10.0).into(),
+            spawn,
             45_f32.to_radians(),
             0.1,
-            100.0,
+            1000.0,
             width as _,
             height as _,
         ),
-        &instance_positions,
-        &instance_texture_ids,
-        light_color,
-        light_position,
+        world::World::new(),
+        Arc::clone(&registry),
+        jobs::JobSystem::new(
+            jobs::JobSystem::default_worker_count(),
+            Arc::clone(&registry),
+            generator,
+            Some(Arc::clone(&world_save)),
+        ),
+        mesh::MeshMode::Greedy,
+        renderer::Sky::at(world_clock.get_day_fraction()),
     );
 
+    renderer.set_render_distance(RENDER_DISTANCE);
+    renderer.set_save(Arc::clone(&world_save));
+    if let Some(level) = &level {
+        renderer
+            .get_camera_mut()
+            .set_forward_direction(level.player.forward.into());
+    }
+
     while !window.should_close() {
         let dt = clock.elapsed().as_nanos() as f32 / 1e9;
         clock = Instant::now();
@@ -93,6 +179,89 @@ fn main() {
             match event {
                 WindowEvent::Key(Key::Escape, _, Action::Press, _) => window.set_should_close(true),
 
+                WindowEvent::Key(
+                    key @ (Key::P | Key::R | Key::X | Key::Num1 | Key::Num2),
+                    _,
+                    Action::Press,
+                    _,
+                ) => {
+                    let hit = targeted_block(&renderer, pointer_state);
+
+                    match (key, hit) {
+                        (Key::P, Some(hit)) => {
+                            if let Some(clipboard) = &clipboard {
+                                paste(
+                                    &mut renderer,
+                                    &clipboard.rotated(rotation, &registry),
+                                    hit.position + hit.normal,
+                                );
+                            }
+                        }
+
+                        (Key::R, _) => {
+                            rotation = rotation.next();
+                            println!("pasting turned {rotation:?}");
+                        }
+
+                        (Key::Num1 | Key::Num2, Some(hit)) => {
+                            let corner = if key == Key::Num1 { 0 } else { 1 };
+                            selection[corner] = Some(hit.position);
+                            println!("selection corner {} at {}", corner + 1, hit.position);
+                        }
+
+                        (Key::X, _) => match selection {
+                            [Some(a), Some(b)] => {
+                                let copied = Clipboard::copy(renderer.get_world(), a, b);
+                                let path = Path::new(EXPORT_PATH);
+                                let result = std::fs::create_dir_all(path.parent().unwrap())
+                                    .and_then(|_| copied.save(path, &mapping));
+
+                                match result {
+                                    Ok(()) => println!(
+                                        "exported {} blocks to {EXPORT_PATH}",
+                                        copied.get_size()
+                                    ),
+                                    Err(err) => eprintln!("failed to export the selection: {err}"),
+                                }
+                            }
+                            _ => println!("mark both corners of the selection with 1 and 2 first"),
+                        },
+
+                        _ => {}
+                    }
+                }
+
+                WindowEvent::Key(Key::T, _, Action::Press, _) => {
+                    let quarter = world::TICKS_PER_DAY / 4;
+                    let next = (world_clock.get_time_of_day() / quarter + 1) * quarter;
+                    world_clock.set_time_of_day(next);
+                    println!("time of day set to {}", world_clock.get_time_of_day());
+                }
+
+                WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                    world_clock.set_frozen(!world_clock.is_frozen());
+                    let state = if world_clock.is_frozen() {
+                        "frozen"
+                    } else {
+                        "running"
+                    };
+                    println!("time {state} at {}", world_clock.get_time_of_day());
+                }
+
+                WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                    lantern = match lantern {
+                        Some(id) => {
+                            renderer.remove_point_light(id);
+                            None
+                        }
+                        None => Some(renderer.add_point_light(renderer::PointLight::new(
+                            *renderer.get_camera().get_position(),
+                            LANTERN_COLOR,
+                            LANTERN_RADIUS,
+                        ))),
+                    };
+                }
+
                 WindowEvent::Key(key, _, action, _) => {
                     let value = matches!(action, Action::Press | Action::Repeat);
                     match key {
@@ -115,12 +284,71 @@ fn main() {
                         pointer_state.secondary_down = value;
                         click_start_position = Some(glam::DVec2::from(window.get_cursor_pos()));
                     }
+
+                    // left click breaks the block under the cursor, middle click places on it
+                    if value {
+                        match (btn, targeted_block(&renderer, pointer_state)) {
+                            (MouseButtonLeft, Some(hit)) => {
+                                renderer.remove_block(hit.position);
+                            }
+
+                            (MouseButtonMiddle, Some(hit)) => {
+                                let camera = renderer.get_camera();
+                                let ray =
+                                    camera.get_ray_direction(pointer_state.pos.unwrap_or_default());
+                                let position = hit.position + hit.normal;
+
+                                let block = &registry.blocks()[placed_block];
+                                let state = block.place(&block::Placement {
+                                    normal: hit.normal,
+                                    point: *camera.get_position() + ray.normalize() * hit.distance
+                                        - position.as_vec3(),
+                                    look: *camera.get_forward_direction(),
+                                });
+                                renderer.set_block(position, state);
+                            }
+
+                            _ => {}
+                        }
+                    }
+                }
+
+                WindowEvent::Scroll(_, y) if y != 0.0 => {
+                    // air is never picked
+                    let count = registry.blocks().len() - 1;
+                    let step = if y > 0.0 { 1 } else { count - 1 };
+                    placed_block = (placed_block - 1 + step) % count + 1;
+                    println!("placing {}", registry.blocks()[placed_block].name);
                 }
 
                 WindowEvent::CursorPos(x, y) => {
                     pointer_state.pos = Some(glam::vec2(x as _, y as _));
                 }
 
+                WindowEvent::FileDrop(paths) => {
+                    for path in paths {
+                        match Clipboard::load(&path, &mapping) {
+                            Ok(loaded) => {
+                                println!(
+                                    "loaded {} blocks from {}, press P to paste",
+                                    loaded.get_size(),
+                                    path.display()
+                                );
+                                if !loaded.get_unknown_states().is_empty() {
+                                    println!(
+                                        "  pasted as placeholders: {}",
+                                        loaded.get_unknown_states().join(", ")
+                                    );
+                                }
+
+                                clipboard = Some(loaded);
+                                rotation = Rotation::None;
+                            }
+                            Err(err) => eprintln!("failed to load {}: {err}", path.display()),
+                        }
+                    }
+                }
+
                 // window resize event
                 WindowEvent::Size(w, h) => {
                     renderer.resize_camera(w as _, h as _);
@@ -132,6 +360,16 @@ fn main() {
         });
 
         renderer.update(dt, pointer_state, keyboard_state);
+        world_clock.advance(dt);
+        renderer.set_sky(renderer::Sky::at(world_clock.get_day_fraction()));
+
+        if let Some(id) = lantern {
+            let position = *renderer.get_camera().get_position();
+            renderer.set_point_light(
+                id,
+                renderer::PointLight::new(position, LANTERN_COLOR, LANTERN_RADIUS),
+            );
+        }
 
         if pointer_state.secondary_down {
             window.set_cursor_mode(CursorMode::Hidden);
@@ -145,7 +383,8 @@ fn main() {
         }
 
         unsafe {
-            gl.clear_color(0.2, 0.2, 0.2, 1.0);
+            let sky = renderer.get_sky().color;
+            gl.clear_color(sky.x, sky.y, sky.z, 1.0);
             gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
 
             renderer.render();
@@ -153,4 +392,52 @@ fn main() {
 
         window.swap_buffers();
     }
+
+    renderer
+        .save_chunks()
+        .expect("failed to save the edited chunks");
+
+    let camera = renderer.get_camera();
+    world_save
+        .save_level(&save::LevelData {
+            seed,
+            generator: preset,
+            spawn: spawn_block.to_array(),
+            time: world_clock.get_time(),
+            time_frozen: world_clock.is_frozen(),
+            player: save::PlayerData {
+                position: camera.get_position().to_array(),
+                forward: camera.get_forward_direction().to_array(),
+            },
+        })
+        .expect("failed to save the level data");
+}
+
+/// The block under the cursor, if it's within reach.
+fn targeted_block(
+    renderer: &renderer::Renderer<'_>,
+    pointer_state: renderer::PointerState,
+) -> Option<world::RaycastHit> {
+    let camera = renderer.get_camera();
+    renderer.get_world().raycast(
+        *camera.get_position(),
+        camera.get_ray_direction(pointer_state.pos?),
+        REACH,
+    )
+}
+
+/// Pastes the clipboard with its lowest corner at the position. Blocks landing in chunks
+/// that aren't loaded are left out, those chunks would otherwise never be generated.
+fn paste(renderer: &mut renderer::Renderer<'_>, clipboard: &Clipboard, position: glam::IVec3) {
+    let mut skipped = 0;
+
+    for (offset, block) in clipboard.blocks() {
+        if renderer.set_block(position + offset, block).is_none() {
+            skipped += 1;
+        }
+    }
+
+    if skipped > 0 {
+        println!("{skipped} blocks fell outside the loaded chunks and weren't pasted");
+    }
 }
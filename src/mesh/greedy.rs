@@ -0,0 +1,128 @@
+use super::{face_occlusion, get_block, get_light, is_face_visible, push_model, push_quad, Vertex};
+use crate::block::{BlockRegistry, Face};
+use crate::world::{Chunk, World, CHUNK_SIZE, SECTION_COUNT};
+
+const S: usize = CHUNK_SIZE as usize;
+
+/// Merges faces slice by slice within each section, a slice being one layer of
+/// blocks perpendicular to the face normal.
+pub fn mesh(world: &World, registry: &BlockRegistry, chunk: &Chunk) -> Vec<Vertex> {
+    let mut vertices = vec![];
+
+    for index in 0..SECTION_COUNT {
+        if chunk.get_section(index).is_none() {
+            continue;
+        }
+
+        let origin = chunk.get_position().origin() + glam::IVec3::Y * (index as i32 * CHUNK_SIZE);
+
+        for face in Face::ALL {
+            let normal = face.normal();
+            let normal_axis = normal.abs().max_position();
+            let (a, b) = ((normal_axis + 1) % 3, (normal_axis + 2) % 3);
+
+            for depth in 0..S {
+                // texture, light and corner occlusion of the visible face at each cell of
+                // the slice, only faces that agree on all three merge
+                let mut mask = [[None; S]; S];
+
+                for (i, row) in mask.iter_mut().enumerate() {
+                    for (j, cell) in row.iter_mut().enumerate() {
+                        let mut local = glam::IVec3::ZERO;
+                        local[normal_axis] = depth as i32;
+                        local[a] = i as i32;
+                        local[b] = j as i32;
+
+                        let pos = origin + local;
+                        let id = chunk.get_block(local.x, pos.y, local.z);
+                        if id.is_air() {
+                            continue;
+                        }
+
+                        if let Some(model) = registry.get_model(id) {
+                            // drawn on their own, once
+                            if face == Face::Up {
+                                push_model(&mut vertices, world, registry, chunk, pos, id, model);
+                            }
+                            continue;
+                        }
+
+                        let neighbour = get_block(world, chunk, pos + normal);
+                        if is_face_visible(registry, id, neighbour) {
+                            let light = get_light(world, chunk, pos + normal);
+                            let ao = face_occlusion(world, registry, chunk, pos, face);
+                            *cell = Some((registry.get_face(id, face), light, ao));
+                        }
+                    }
+                }
+
+                for (i, j, width, height, (texture, light, ao)) in merge(&mut mask) {
+                    let mut min = glam::IVec3::ZERO;
+                    min[normal_axis] = depth as i32;
+                    min[a] = i as i32;
+                    min[b] = j as i32;
+
+                    let mut size = glam::IVec3::ONE;
+                    size[a] = width as i32;
+                    size[b] = height as i32;
+
+                    let min = origin + min;
+                    push_quad(
+                        &mut vertices,
+                        face,
+                        min.as_vec3(),
+                        (min + size).as_vec3(),
+                        texture,
+                        light,
+                        ao,
+                    );
+                }
+            }
+        }
+    }
+
+    vertices
+}
+
+/// Covers every filled cell of the mask with rectangles of equal cells, growing each
+/// rectangle along `j` first and then along `i`. Consumes the mask.
+/// Returns `(i, j, width along i, height along j, value)` for each rectangle.
+fn merge<T: Copy + PartialEq>(
+    mask: &mut [[Option<T>; S]; S],
+) -> Vec<(usize, usize, usize, usize, T)> {
+    let mut rects = vec![];
+
+    for i in 0..S {
+        let mut j = 0;
+
+        while j < S {
+            let Some(value) = mask[i][j] else {
+                j += 1;
+                continue;
+            };
+
+            let mut height = 1;
+            while j + height < S && mask[i][j + height] == Some(value) {
+                height += 1;
+            }
+
+            let mut width = 1;
+            while i + width < S
+                && mask[i + width][j..j + height]
+                    .iter()
+                    .all(|c| *c == Some(value))
+            {
+                width += 1;
+            }
+
+            for row in &mut mask[i..i + width] {
+                row[j..j + height].fill(None);
+            }
+
+            rects.push((i, j, width, height, value));
+            j += height;
+        }
+    }
+
+    rects
+}
@@ -0,0 +1,456 @@
+use crate::block::{BlockModel, BlockRegistry, Face, FaceTexture};
+use crate::world::{BlockId, Chunk, ChunkPos, Light, World, CHUNK_SIZE};
+
+/// A single vertex of a chunk mesh, in world space.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+    /// Texture array layer.
+    pub texture: u32,
+    /// Sky and block light the vertex is lit with, `0..=1`.
+    pub light: [f32; 2],
+    /// How open the corner is to the blocks around it, `0..=1`. See [`face_occlusion`].
+    pub ao: f32,
+}
+
+mod greedy;
+mod naive;
+
+/// How faces are turned into quads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshMode {
+    /// One quad per visible block face.
+    Naive,
+    /// Coplanar faces sharing a texture, light and corner shading are merged into larger
+    /// quads.
+    /// Blocks drawn with a model are still drawn one by one.
+    #[default]
+    Greedy,
+}
+
+/// Builds the vertices of every block face in the chunk that isn't hidden by a neighbour.
+/// Blocks of neighbouring chunks are read from `world`, unloaded chunks count as air.
+pub fn mesh_chunk(
+    world: &World,
+    registry: &BlockRegistry,
+    pos: ChunkPos,
+    mode: MeshMode,
+) -> Vec<Vertex> {
+    let Some(chunk) = world.get_chunk(pos) else {
+        return vec![];
+    };
+
+    match mode {
+        MeshMode::Naive => naive::mesh(world, registry, chunk),
+        MeshMode::Greedy => greedy::mesh(world, registry, chunk),
+    }
+}
+
+/// Whether a face of `block` can be seen through `neighbour`.
+/// Faces between two blocks of the same transparent type are hidden as well.
+pub(crate) fn is_face_visible(
+    registry: &BlockRegistry,
+    block: BlockId,
+    neighbour: BlockId,
+) -> bool {
+    if neighbour.is_air() {
+        return true;
+    }
+
+    let opaque = registry.get(neighbour).is_some_and(|b| b.opaque);
+    !opaque && neighbour != block
+}
+
+/// Reads from `chunk` directly when possible, avoiding the chunk lookup.
+pub(crate) fn get_block(world: &World, chunk: &Chunk, pos: glam::IVec3) -> BlockId {
+    let local = pos - chunk.get_position().origin();
+
+    if (0..CHUNK_SIZE).contains(&local.x) && (0..CHUNK_SIZE).contains(&local.z) {
+        chunk.get_block(local.x, pos.y, local.z)
+    } else {
+        world.get_block(pos)
+    }
+}
+
+/// Ambient occlusion at the corners of the `face` side of the block at `pos`, in the
+/// order [`push_quad`] puts them. Each corner is darkened by the opaque blocks in front
+/// of the face touching it: `3` is open, `0` is boxed in by both sides.
+pub(crate) fn face_occlusion(
+    world: &World,
+    registry: &BlockRegistry,
+    chunk: &Chunk,
+    pos: glam::IVec3,
+    face: Face,
+) -> [u8; 4] {
+    let (u, v) = face.texture_axes();
+    let front = pos + face.normal();
+    let occludes = |pos| {
+        registry
+            .get(get_block(world, chunk, pos))
+            .is_some_and(|b| b.opaque)
+    };
+
+    [(0, 0), (0, 1), (1, 1), (1, 0)].map(|(s, t)| {
+        let u = if s == 0 { -u } else { u };
+        let v = if t == 0 { -v } else { v };
+
+        let (side1, side2) = (occludes(front + u), occludes(front + v));
+        if side1 && side2 {
+            0
+        } else {
+            3 - side1 as u8 - side2 as u8 - occludes(front + u + v) as u8
+        }
+    })
+}
+
+/// Reads from `chunk` directly when possible, like [`get_block`].
+pub(crate) fn get_light(world: &World, chunk: &Chunk, pos: glam::IVec3) -> Light {
+    let local = pos - chunk.get_position().origin();
+
+    if (0..CHUNK_SIZE).contains(&local.x) && (0..CHUNK_SIZE).contains(&local.z) {
+        chunk.get_light(local.x, pos.y, local.z)
+    } else {
+        world.get_light(pos)
+    }
+}
+
+/// Pushes the quads of a block drawn with a model. Quads with a cullface are hidden by
+/// neighbours like the faces of full blocks, the others always show.
+pub(crate) fn push_model(
+    vertices: &mut Vec<Vertex>,
+    world: &World,
+    registry: &BlockRegistry,
+    chunk: &Chunk,
+    pos: glam::IVec3,
+    id: BlockId,
+    model: &BlockModel,
+) {
+    let origin = pos.as_vec3();
+
+    for quad in &model.quads {
+        if let Some(face) = quad.cullface {
+            let neighbour = get_block(world, chunk, pos + face.normal());
+            if !is_face_visible(registry, id, neighbour) {
+                continue;
+            }
+        }
+
+        // light lets models through, quads inside the cell show the brighter of it and
+        // the cell they face, which slabs and stairs shade
+        let facing = glam::IVec3::from_array(std::array::from_fn(|axis| {
+            if axis == quad.normal.abs().max_position() {
+                quad.normal[axis].signum() as i32
+            } else {
+                0
+            }
+        }));
+        let light = get_light(world, chunk, pos).max(get_light(world, chunk, pos + facing));
+
+        let corners: [Vertex; 4] = std::array::from_fn(|i| Vertex {
+            position: (origin + quad.positions[i]).to_array(),
+            tex_coord: quad.tex_coords[i],
+            normal: quad.normal.to_array(),
+            texture: quad.layer,
+            light: light_levels(light),
+            ao: 1.0,
+        });
+
+        vertices.extend_from_slice(&[
+            corners[0], corners[1], corners[2], corners[0], corners[2], corners[3],
+        ]);
+    }
+}
+
+/// Pushes two triangles covering the `face` side of the `min..max` box.
+/// Texture coordinates span one unit per block, so textures repeat across larger quads.
+/// `ao` is the occlusion of each corner, as returned by [`face_occlusion`].
+pub(crate) fn push_quad(
+    vertices: &mut Vec<Vertex>,
+    face: Face,
+    min: glam::Vec3,
+    max: glam::Vec3,
+    texture: FaceTexture,
+    light: Light,
+    ao: [u8; 4],
+) {
+    let (u, v) = face.texture_axes();
+    let normal = face.normal();
+    let size = max - min;
+
+    let width = size.dot(u.abs().as_vec3());
+    let height = size.dot(v.abs().as_vec3());
+
+    // corner at fraction `s` along the texture `u` axis and `t` along the `v` axis
+    let corner = |s: f32, t: f32, ao: u8| {
+        let p = face.box_corner(min, max, s, t);
+
+        // each quarter turn of the texture swaps the extents of the quad
+        let (mut tex_coord, mut extent) = ([s * width, t * height], [width, height]);
+        for _ in 0..texture.rotation % 4 {
+            tex_coord = [extent[1] - tex_coord[1], tex_coord[0]];
+            extent = [extent[1], extent[0]];
+        }
+
+        Vertex {
+            position: p.to_array(),
+            tex_coord,
+            normal: normal.as_vec3().to_array(),
+            texture: texture.layer,
+            light: light_levels(light),
+            ao: ao as f32 / 3.0,
+        }
+    };
+
+    let corners = [
+        corner(0.0, 0.0, ao[0]),
+        corner(0.0, 1.0, ao[1]),
+        corner(1.0, 1.0, ao[2]),
+        corner(1.0, 0.0, ao[3]),
+    ];
+
+    // split along the darker diagonal, otherwise the shading of one triangle depends on
+    // which way the quad happens to be turned
+    let [a, b, c, d] = if ao[0] + ao[2] > ao[1] + ao[3] {
+        [1, 2, 3, 0]
+    } else {
+        [0, 1, 2, 3]
+    };
+
+    // clockwise when seen from outside, the renderer culls front faces
+    vertices.extend_from_slice(&[
+        corners[a], corners[b], corners[c], corners[a], corners[c], corners[d],
+    ]);
+}
+
+fn light_levels(light: Light) -> [f32; 2] {
+    let max = Light::MAX as f32;
+    [light.get_sky() as f32 / max, light.get_block() as f32 / max]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const BLOCKS: &str = r#"[
+        { "id": 0, "name": "air", "opaque": false, "solid": false },
+        { "id": 1, "name": "stone", "textures": { "all": "stone" } },
+        { "id": 2, "name": "grass", "textures": { "top": "grass_top", "side": "grass_side", "bottom": "dirt" } },
+        { "id": 3, "name": "glass", "textures": { "all": "glass" }, "opaque": false },
+        { "id": 4, "name": "slab", "textures": { "all": "stone" }, "opaque": false, "model": "slab", "properties": ["half"] }
+    ]"#;
+
+    const STONE: BlockId = BlockId(1);
+    const GRASS: BlockId = BlockId(2);
+    const GLASS: BlockId = BlockId(3);
+
+    fn mesh_world(world: &World, registry: &BlockRegistry, mode: MeshMode) -> Vec<Vertex> {
+        world
+            .chunks()
+            .flat_map(|chunk| mesh_chunk(world, registry, chunk.get_position(), mode))
+            .collect()
+    }
+
+    /// Splits every quad into the unit block faces it covers, as `(normal, block, texture)`.
+    fn unit_faces(vertices: &[Vertex]) -> Vec<([i32; 3], [i32; 3], u32)> {
+        assert_eq!(vertices.len() % 6, 0);
+
+        let mut faces = vec![];
+        for quad in vertices.chunks(6) {
+            let normal = glam::Vec3::from(quad[0].normal).as_ivec3();
+            let min = quad
+                .iter()
+                .map(|v| glam::Vec3::from(v.position))
+                .reduce(glam::Vec3::min)
+                .unwrap();
+            let max = quad
+                .iter()
+                .map(|v| glam::Vec3::from(v.position))
+                .reduce(glam::Vec3::max)
+                .unwrap();
+
+            // the quad is flat along the normal, step back into the block it belongs to
+            let min = min.as_ivec3() - normal.max(glam::IVec3::ZERO);
+            let max = max.as_ivec3() - normal.max(glam::IVec3::ZERO) + normal.abs();
+
+            for x in min.x..max.x {
+                for y in min.y..max.y {
+                    for z in min.z..max.z {
+                        faces.push((normal.to_array(), [x, y, z], quad[0].texture));
+                    }
+                }
+            }
+        }
+
+        faces
+    }
+
+    #[test]
+    fn single_block_has_six_faces() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+        world.set_block(glam::ivec3(3, 5, 7), GRASS);
+
+        for mode in [MeshMode::Naive, MeshMode::Greedy] {
+            let vertices = mesh_world(&world, &registry, mode);
+            assert_eq!(vertices.len(), 36, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn faces_across_chunk_borders_are_culled() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+        world.set_block(glam::ivec3(15, 0, 0), STONE);
+        world.set_block(glam::ivec3(16, 0, 0), STONE);
+
+        assert_eq!(mesh_world(&world, &registry, MeshMode::Naive).len(), 10 * 6);
+        assert_eq!(
+            mesh_world(&world, &registry, MeshMode::Greedy).len(),
+            10 * 6
+        );
+    }
+
+    #[test]
+    fn greedy_merges_flat_layer_into_six_quads() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                world.set_block(glam::ivec3(x, 0, z), STONE);
+            }
+        }
+
+        let naive = mesh_world(&world, &registry, MeshMode::Naive);
+        let greedy = mesh_world(&world, &registry, MeshMode::Greedy);
+
+        assert_eq!(naive.len(), (256 * 2 + 16 * 4) * 6);
+        assert_eq!(greedy.len(), 6 * 6);
+
+        // textures tile once per block across the merged quad
+        let max_uv = greedy
+            .iter()
+            .map(|v| v.tex_coord[0].max(v.tex_coord[1]))
+            .fold(0.0, f32::max);
+        assert_eq!(max_uv, 16.0);
+    }
+
+    #[test]
+    fn greedy_does_not_merge_different_textures() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+        for x in 0..4 {
+            world.set_block(glam::ivec3(x, 0, 0), if x % 2 == 0 { STONE } else { GRASS });
+        }
+
+        let greedy = mesh_world(&world, &registry, MeshMode::Greedy);
+
+        // 4 tops and 4 bottoms that alternate textures, 2 long sides each split in 4, 2 ends
+        assert_eq!(greedy.len(), (4 + 4 + 4 + 4 + 2) * 6);
+    }
+
+    #[test]
+    fn greedy_covers_the_same_faces_as_naive() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+
+        // deterministic noise spanning several chunks and sections
+        let mut seed = 0x2545_f491_u32;
+        for x in -12..20 {
+            for y in -20..12 {
+                for z in -12..20 {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+
+                    let block = match seed % 10 {
+                        0..=3 => STONE,
+                        4 | 5 => GRASS,
+                        6 => GLASS,
+                        _ => continue,
+                    };
+                    world.set_block(glam::ivec3(x, y, z), block);
+                }
+            }
+        }
+
+        let naive = unit_faces(&mesh_world(&world, &registry, MeshMode::Naive));
+        let greedy_vertices = mesh_world(&world, &registry, MeshMode::Greedy);
+        let greedy = unit_faces(&greedy_vertices);
+
+        let naive_set: HashSet<_> = naive.iter().copied().collect();
+        let greedy_set: HashSet<_> = greedy.iter().copied().collect();
+
+        assert_eq!(
+            naive.len(),
+            naive_set.len(),
+            "naive mesh has overlapping faces"
+        );
+        assert_eq!(
+            greedy.len(),
+            greedy_set.len(),
+            "greedy mesh has overlapping quads"
+        );
+        assert_eq!(naive_set, greedy_set);
+        assert!(greedy_vertices.len() < naive.len() * 6);
+    }
+
+    #[test]
+    fn corners_against_blocks_are_darker() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+        let mut world = World::new();
+        world.set_block(glam::ivec3(0, 0, 0), STONE);
+        // touches only the top corner of the stone at (1, 1, 1)
+        world.set_block(glam::ivec3(1, 1, 1), STONE);
+
+        for mode in [MeshMode::Naive, MeshMode::Greedy] {
+            let vertices = mesh_world(&world, &registry, mode);
+            let top = vertices
+                .chunks(6)
+                .find(|quad| quad[0].normal == [0.0, 1.0, 0.0] && quad[0].position[1] == 1.0)
+                .unwrap();
+
+            for v in top {
+                let expected = if v.position == [1.0, 1.0, 1.0] {
+                    2.0 / 3.0
+                } else {
+                    1.0
+                };
+                assert_eq!(v.ao, expected, "{mode:?}");
+            }
+            // the quad is split through the dark corner, so both triangles share it
+            let dark = top.iter().filter(|v| v.ao < 1.0).count();
+            assert_eq!(dark, 2, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn slabs_take_the_half_of_their_state() {
+        let registry = BlockRegistry::from_str(BLOCKS).unwrap();
+
+        for (state, range) in [
+            ("slab[half=bottom]", 0.0..=0.5),
+            ("slab[half=top]", 0.5..=1.0),
+        ] {
+            let mut world = World::new();
+            world.set_block(glam::ivec3(0, 0, 0), registry.parse_state(state).unwrap());
+            // the slab's face against the stone is hidden, the stone's face still shows
+            world.set_block(glam::ivec3(1, 0, 0), STONE);
+
+            for mode in [MeshMode::Naive, MeshMode::Greedy] {
+                let vertices = mesh_world(&world, &registry, mode);
+                assert_eq!(vertices.len(), (5 + 6) * 6, "{state} {mode:?}");
+
+                let slab: Vec<_> = vertices.iter().filter(|v| v.position[0] < 1.0).collect();
+                assert!(
+                    slab.iter().all(|v| range.contains(&v.position[1])),
+                    "{state}"
+                );
+            }
+        }
+    }
+}
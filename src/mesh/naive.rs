@@ -0,0 +1,33 @@
+use super::{face_occlusion, get_block, get_light, is_face_visible, push_model, push_quad, Vertex};
+use crate::block::{BlockRegistry, Face};
+use crate::world::{Chunk, World};
+
+pub fn mesh(world: &World, registry: &BlockRegistry, chunk: &Chunk) -> Vec<Vertex> {
+    let mut vertices = vec![];
+
+    for (block_pos, id) in chunk.blocks() {
+        if let Some(model) = registry.get_model(id) {
+            push_model(&mut vertices, world, registry, chunk, block_pos, id, model);
+            continue;
+        }
+
+        for face in Face::ALL {
+            let neighbour = get_block(world, chunk, block_pos + face.normal());
+
+            if is_face_visible(registry, id, neighbour) {
+                let min = block_pos.as_vec3();
+                push_quad(
+                    &mut vertices,
+                    face,
+                    min,
+                    min + 1.0,
+                    registry.get_face(id, face),
+                    get_light(world, chunk, block_pos + face.normal()),
+                    face_occlusion(world, registry, chunk, block_pos, face),
+                );
+            }
+        }
+    }
+
+    vertices
+}
@@ -0,0 +1,353 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use super::{Compound, Tag};
+use crate::save::Compression;
+
+/// Tags nested deeper than this are refused, so hostile files can't overflow the stack.
+pub const MAX_DEPTH: usize = 512;
+
+const END: u8 = 0;
+
+/// Reads a named root tag, working out whether the data is gzip, zlib or not compressed.
+pub fn from_bytes(data: &[u8]) -> std::io::Result<(String, Tag)> {
+    let compression = match data {
+        [0x1f, 0x8b, ..] => Compression::Gzip,
+        [0x78, ..] => Compression::Zlib,
+        _ => return read_uncompressed(data),
+    };
+
+    read_uncompressed(&compression.decompress(data)?)
+}
+
+/// Reads a named root tag from uncompressed data, which must hold nothing else.
+pub fn read_uncompressed(data: &[u8]) -> std::io::Result<(String, Tag)> {
+    let mut reader = Reader { data };
+
+    let id = reader.read_u8()?;
+    if id == END {
+        return Err(invalid("the root tag is missing"));
+    }
+
+    let name = reader.read_string()?;
+    let tag = reader.read_payload(id, 0)?;
+
+    if !reader.data.is_empty() {
+        return Err(invalid("trailing data after the root tag"));
+    }
+
+    Ok((name, tag))
+}
+
+/// Writes a named root tag.
+pub fn to_bytes(name: &str, tag: &Tag, compression: Compression) -> std::io::Result<Vec<u8>> {
+    let mut out = vec![tag.get_id()];
+    write_string(&mut out, name)?;
+    write_payload(&mut out, tag)?;
+
+    compression.compress(&out)
+}
+
+pub fn read_file(path: &Path) -> std::io::Result<(String, Tag)> {
+    from_bytes(&std::fs::read(path)?)
+}
+
+/// Vanilla writes `level.dat`, structures and schematics gzip compressed.
+pub fn write_file(
+    path: &Path,
+    name: &str,
+    tag: &Tag,
+    compression: Compression,
+) -> std::io::Result<()> {
+    std::fs::write(path, to_bytes(name, tag, compression)?)
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.into())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "NBT data ends early"));
+        }
+
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.read::<1>()?[0])
+    }
+
+    /// Array and list lengths, checked against what's left so a bad length can't
+    /// allocate more than the data could hold.
+    fn read_length(&mut self, element_size: usize) -> std::io::Result<usize> {
+        let length = i32::from_be_bytes(self.read()?);
+        if length < 0 {
+            return Err(invalid(format!("negative length {length}")));
+        }
+
+        let length = length as usize;
+        if length.saturating_mul(element_size) > self.data.len() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "NBT data ends early"));
+        }
+
+        Ok(length)
+    }
+
+    fn read_string(&mut self) -> std::io::Result<String> {
+        let length = u16::from_be_bytes(self.read()?) as usize;
+        decode_modified_utf8(self.take(length)?)
+    }
+
+    fn read_array<const N: usize, T>(
+        &mut self,
+        convert: fn([u8; N]) -> T,
+    ) -> std::io::Result<Vec<T>> {
+        let length = self.read_length(N)?;
+        let bytes = self.take(length * N)?;
+
+        Ok(bytes
+            .chunks_exact(N)
+            .map(|chunk| convert(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    fn read_payload(&mut self, id: u8, depth: usize) -> std::io::Result<Tag> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("tags are nested too deep"));
+        }
+
+        Ok(match id {
+            1 => Tag::Byte(i8::from_be_bytes(self.read()?)),
+            2 => Tag::Short(i16::from_be_bytes(self.read()?)),
+            3 => Tag::Int(i32::from_be_bytes(self.read()?)),
+            4 => Tag::Long(i64::from_be_bytes(self.read()?)),
+            5 => Tag::Float(f32::from_be_bytes(self.read()?)),
+            6 => Tag::Double(f64::from_be_bytes(self.read()?)),
+            7 => Tag::ByteArray(self.read_array(i8::from_be_bytes)?),
+            8 => Tag::String(self.read_string()?),
+            9 => {
+                let element = self.read_u8()?;
+                // empty payloads still take a byte in the length check
+                let length = self.read_length(1)?;
+                if element == END && length > 0 {
+                    return Err(invalid("list of end tags"));
+                }
+
+                let mut list = Vec::with_capacity(length);
+                for _ in 0..length {
+                    list.push(self.read_payload(element, depth + 1)?);
+                }
+                Tag::List(list)
+            }
+            10 => {
+                let mut compound = Compound::new();
+                loop {
+                    let id = self.read_u8()?;
+                    if id == END {
+                        break;
+                    }
+
+                    let name = self.read_string()?;
+                    let tag = self.read_payload(id, depth + 1)?;
+                    compound.insert(name, tag);
+                }
+                Tag::Compound(compound)
+            }
+            11 => Tag::IntArray(self.read_array(i32::from_be_bytes)?),
+            12 => Tag::LongArray(self.read_array(i64::from_be_bytes)?),
+            _ => return Err(invalid(format!("unknown tag type {id}"))),
+        })
+    }
+}
+
+fn write_payload(out: &mut Vec<u8>, tag: &Tag) -> std::io::Result<()> {
+    match tag {
+        Tag::Byte(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::Short(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::Int(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::Long(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::Float(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::Double(v) => out.extend_from_slice(&v.to_be_bytes()),
+        Tag::ByteArray(v) => {
+            write_length(out, v.len())?;
+            out.extend(v.iter().map(|&b| b as u8));
+        }
+        Tag::String(v) => write_string(out, v)?,
+        Tag::List(list) => {
+            let element = list.first().map_or(END, Tag::get_id);
+            if list.iter().any(|tag| tag.get_id() != element) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "list elements must all be of the same type",
+                ));
+            }
+
+            out.push(element);
+            write_length(out, list.len())?;
+            for tag in list {
+                write_payload(out, tag)?;
+            }
+        }
+        Tag::Compound(compound) => {
+            for (name, tag) in compound.iter() {
+                out.push(tag.get_id());
+                write_string(out, name)?;
+                write_payload(out, tag)?;
+            }
+            out.push(END);
+        }
+        Tag::IntArray(v) => {
+            write_length(out, v.len())?;
+            v.iter()
+                .for_each(|i| out.extend_from_slice(&i.to_be_bytes()));
+        }
+        Tag::LongArray(v) => {
+            write_length(out, v.len())?;
+            v.iter()
+                .for_each(|l| out.extend_from_slice(&l.to_be_bytes()));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_length(out: &mut Vec<u8>, length: usize) -> std::io::Result<()> {
+    let length = i32::try_from(length)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "NBT array is too long"))?;
+    out.extend_from_slice(&length.to_be_bytes());
+    Ok(())
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> std::io::Result<()> {
+    let bytes = encode_modified_utf8(value);
+    let length = u16::try_from(bytes.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "NBT string is too long"))?;
+
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&bytes);
+    Ok(())
+}
+
+/// Java's flavour of UTF-8: nul takes two bytes and characters outside the basic plane
+/// are written as two three-byte surrogates.
+fn encode_modified_utf8(value: &str) -> Vec<u8> {
+    if !value.bytes().any(|b| b == 0 || b >= 0xf0) {
+        return value.as_bytes().to_vec();
+    }
+
+    let mut out = Vec::with_capacity(value.len() + 2);
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7f => out.push(unit as u8),
+            0x00 | 0x80..=0x7ff => {
+                out.push(0xc0 | (unit >> 6) as u8);
+                out.push(0x80 | (unit & 0x3f) as u8);
+            }
+            _ => {
+                out.push(0xe0 | (unit >> 12) as u8);
+                out.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                out.push(0x80 | (unit & 0x3f) as u8);
+            }
+        }
+    }
+    out
+}
+
+fn decode_modified_utf8(bytes: &[u8]) -> std::io::Result<String> {
+    if let Ok(value) = std::str::from_utf8(bytes) {
+        return Ok(value.to_owned());
+    }
+
+    let bad = || invalid("string is not valid modified UTF-8");
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+
+    while let Some((&first, tail)) = rest.split_first() {
+        let continuation = |i: usize| match tail.get(i) {
+            Some(&b) if b & 0xc0 == 0x80 => Ok((b & 0x3f) as u16),
+            _ => Err(bad()),
+        };
+
+        let (unit, size) = match first {
+            0x00..=0x7f => (first as u16, 1),
+            0xc0..=0xdf => (((first & 0x1f) as u16) << 6 | continuation(0)?, 2),
+            0xe0..=0xef => (
+                ((first & 0x0f) as u16) << 12 | continuation(0)? << 6 | continuation(1)?,
+                3,
+            ),
+            _ => return Err(bad()),
+        };
+
+        units.push(unit);
+        rest = &rest[size..];
+    }
+
+    String::from_utf16(&units).map_err(|_| bad())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_spec_example() {
+        // hello_world.nbt from the original format description
+        let data = [
+            0x0a, 0x00, 0x0b, b'h', b'e', b'l', b'l', b'o', b' ', b'w', b'o', b'r', b'l', b'd',
+            0x08, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x00, 0x09, b'B', b'a', b'n', b'a', b'n',
+            b'r', b'a', b'm', b'a', 0x00,
+        ];
+
+        let (name, tag) = read_uncompressed(&data).unwrap();
+        assert_eq!(name, "hello world");
+        assert_eq!(
+            tag,
+            Tag::Compound([("name", "Bananrama")].into_iter().collect())
+        );
+
+        for compression in [Compression::None, Compression::Gzip, Compression::Zlib] {
+            let bytes = to_bytes(&name, &tag, compression).unwrap();
+            assert_eq!(from_bytes(&bytes).unwrap(), (name.clone(), tag.clone()));
+        }
+        assert_eq!(to_bytes(&name, &tag, Compression::None).unwrap(), data);
+    }
+
+    #[test]
+    fn strings_use_modified_utf8() {
+        for value in ["plain", "nul\0inside", "ünïcödé", "emoji 🧱 block"] {
+            let bytes = encode_modified_utf8(value);
+            assert!(!bytes.contains(&0));
+            assert!(!bytes.iter().any(|&b| b >= 0xf0));
+            assert_eq!(decode_modified_utf8(&bytes).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn mixed_lists_are_refused() {
+        let tag = Tag::List(vec![Tag::Byte(1), Tag::Int(2)]);
+        assert!(to_bytes("", &tag, Compression::None).is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let mut data = vec![9, 0, 0];
+        for _ in 0..MAX_DEPTH + 8 {
+            data.extend_from_slice(&[9, 0, 0, 0, 1]);
+        }
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+
+        assert!(read_uncompressed(&data).is_err());
+    }
+}
@@ -0,0 +1,706 @@
+//! Serde support, so level data and the like can be plain structs.
+//!
+//! Structs and maps become compounds, sequences lists and `None` fields are left out.
+//! Unsigned integers are stored as the signed tag of the same width, bit for bit, the
+//! way Java does. Unit enum variants are strings, others a compound holding a single
+//! entry named after the variant.
+
+use std::fmt::{self, Display};
+use std::io::ErrorKind;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+use super::{Compound, Tag};
+
+/// Turns the value into a tag, failing for lists of mixed types or maps without string keys.
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> std::io::Result<Tag> {
+    value
+        .serialize(TagSerializer)?
+        .ok_or_else(|| Error::new("nothing to write").into())
+}
+
+/// Builds the value from a tag, failing when its shape doesn't match.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> std::io::Result<T> {
+    Ok(T::deserialize(TagDeserializer(tag))?)
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Error {
+    fn new(msg: impl Display) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        std::io::Error::new(ErrorKind::InvalidData, error.0)
+    }
+}
+
+/// Gives `None` for values that aren't stored, like `None` and `()`.
+struct TagSerializer;
+
+impl ser::Serializer for TagSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|&b| b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let mut compound = Compound::new();
+        if let Some(tag) = value.serialize(self)? {
+            compound.insert(variant, tag);
+        }
+        Ok(Some(Tag::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(CompoundSerializer::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(CompoundSerializer::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(CompoundSerializer {
+            variant: Some(variant),
+            ..Default::default()
+        })
+    }
+}
+
+/// Wraps the tag of an enum variant in a compound named after the variant.
+fn wrap_variant(variant: Option<&'static str>, tag: Tag) -> Tag {
+    match variant {
+        Some(variant) => Tag::Compound([(variant, tag)].into_iter().collect()),
+        None => tag,
+    }
+}
+
+struct ListSerializer {
+    items: Vec<Tag>,
+    variant: Option<&'static str>,
+}
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let tag = value
+            .serialize(TagSerializer)?
+            .ok_or_else(|| Error::new("lists can't hold missing values"))?;
+
+        if self
+            .items
+            .first()
+            .is_some_and(|first| first.get_id() != tag.get_id())
+        {
+            return Err(Error::new("list elements must all be of the same type"));
+        }
+
+        self.items.push(tag);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, Error> {
+        Ok(Some(wrap_variant(self.variant, Tag::List(self.items))))
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+#[derive(Default)]
+struct CompoundSerializer {
+    compound: Compound,
+    /// Key of the map entry whose value comes next.
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, name: String, value: &T) -> Result<(), Error> {
+        if let Some(tag) = value.serialize(TagSerializer)? {
+            self.compound.insert(name, tag);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, Error> {
+        Ok(Some(wrap_variant(
+            self.variant,
+            Tag::Compound(self.compound),
+        )))
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(TagSerializer)? {
+            Some(Tag::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::new("compound names must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().expect("value serialized before its key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(name.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(name.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        self.finish()
+    }
+}
+
+struct TagDeserializer(Tag);
+
+impl<'de> de::Deserializer<'de> for TagDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Byte(v) => visitor.visit_i8(v),
+            Tag::Short(v) => visitor.visit_i16(v),
+            Tag::Int(v) => visitor.visit_i32(v),
+            Tag::Long(v) => visitor.visit_i64(v),
+            Tag::Float(v) => visitor.visit_f32(v),
+            Tag::Double(v) => visitor.visit_f64(v),
+            Tag::String(v) => visitor.visit_string(v),
+            Tag::ByteArray(v) => visit_list(visitor, v.into_iter().map(Tag::Byte).collect()),
+            Tag::IntArray(v) => visit_list(visitor, v.into_iter().map(Tag::Int).collect()),
+            Tag::LongArray(v) => visit_list(visitor, v.into_iter().map(Tag::Long).collect()),
+            Tag::List(list) => visit_list(visitor, list),
+            Tag::Compound(compound) => visitor.visit_map(CompoundAccess {
+                entries: compound.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Byte(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Long(v) => visitor.visit_u64(v as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::ByteArray(v) => visitor.visit_byte_buf(v.into_iter().map(|b| b as u8).collect()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Missing values never get here, serde turns missing fields into `None` by itself.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(VariantAccess { variant, value })
+            }
+            _ => Err(Error::new(
+                "enums must be a string or a compound with a single entry",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn visit_list<'de, V: Visitor<'de>>(visitor: V, list: Vec<Tag>) -> Result<V::Value, Error> {
+    let length = list.len();
+    let mut access = ListAccess(list.into_iter());
+    let value = visitor.visit_seq(&mut access)?;
+
+    if access.0.len() > 0 {
+        return Err(de::Error::invalid_length(length, &"fewer elements"));
+    }
+    Ok(value)
+}
+
+struct ListAccess(std::vec::IntoIter<Tag>);
+
+impl<'de> de::SeqAccess<'de> for ListAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .next()
+            .map(|tag| seed.deserialize(TagDeserializer(tag)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct CompoundAccess {
+    entries: std::vec::IntoIter<(String, Tag)>,
+    value: Option<Tag>,
+}
+
+impl<'de> de::MapAccess<'de> for CompoundAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((name, tag)) = self.entries.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some(tag);
+        seed.deserialize(name.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, Error> {
+        let tag = self.value.take().expect("value read before its key");
+        seed.deserialize(TagDeserializer(tag))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct VariantAccess {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess {
+    type Error = Error;
+    type Variant = TagDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, TagDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for TagDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Cube,
+        Slab(bool),
+        Stairs { facing: String, upside_down: bool },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Level {
+        seed: u64,
+        name: String,
+        spawn: [i32; 3],
+        time: Option<u64>,
+        weather: Option<String>,
+        speed: f32,
+        shape: Shape,
+        /// Unit variants are strings, so they can't share a list with the others.
+        shapes: Vec<Shape>,
+        gamerules: BTreeMap<String, bool>,
+        tint: (u8, u8, u8),
+    }
+
+    #[test]
+    fn structs_round_trip() {
+        let level = Level {
+            seed: u64::MAX - 3,
+            name: "Test world".into(),
+            spawn: [0, 64, -12],
+            time: Some(6000),
+            weather: None,
+            speed: 1.25,
+            shape: Shape::Cube,
+            shapes: vec![
+                Shape::Slab(true),
+                Shape::Stairs {
+                    facing: "north".into(),
+                    upside_down: false,
+                },
+            ],
+            gamerules: [("doDaylightCycle".to_owned(), true)].into(),
+            tint: (255, 128, 0),
+        };
+
+        let tag = to_tag(&level).unwrap();
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(compound.get("seed"), Some(&Tag::Long(-4)));
+        assert_eq!(compound.get("weather"), None);
+        assert_eq!(compound.get("shape").unwrap().as_str(), Some("Cube"));
+        assert_eq!(compound.get("time"), Some(&Tag::Long(6000)));
+        assert_eq!(
+            compound.get("spawn"),
+            Some(&Tag::List(vec![Tag::Int(0), Tag::Int(64), Tag::Int(-12)]))
+        );
+
+        assert_eq!(from_tag::<Level>(tag).unwrap(), level);
+    }
+
+    #[test]
+    fn arrays_read_as_sequences() {
+        let tag = Tag::LongArray(vec![1, -2, 3]);
+        assert_eq!(from_tag::<Vec<i64>>(tag).unwrap(), [1, -2, 3]);
+
+        let tag = Tag::Compound([("a", 1i32)].into_iter().collect());
+        assert!(from_tag::<Level>(tag).is_err());
+        assert!(to_tag(&vec![Some(1), None]).is_err());
+    }
+}
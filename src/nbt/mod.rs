@@ -0,0 +1,324 @@
+//! Named Binary Tag, the format of vanilla worlds, structures and schematics.
+//!
+//! Tags are read and written in the big-endian binary form, optionally gzip or zlib
+//! compressed, and in the SNBT text form through `Display` and `FromStr`.
+//! [`to_tag`] and [`from_tag`] convert between tags and anything implementing serde's traits.
+
+mod binary;
+mod convert;
+mod snbt;
+
+pub use binary::*;
+pub use convert::*;
+
+/// A single value. Lists of elements of different types can't be written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// Id the type is stored as, `0` is kept for the end of compounds.
+    pub fn get_id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    /// Any integer tag, widened.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(v) => Some(v as i64),
+            Tag::Short(v) => Some(v as i64),
+            Tag::Int(v) => Some(v as i64),
+            Tag::Long(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Any number tag, widened.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Tag::Float(v) => Some(v as f64),
+            Tag::Double(v) => Some(v),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Tag::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[i8]> {
+        match self {
+            Tag::ByteArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_int_array(&self) -> Option<&[i32]> {
+        match self {
+            Tag::IntArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Tag::LongArray(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($type:ty => $variant:ident),* $(,)?) => {
+        $(impl From<$type> for Tag {
+            fn from(value: $type) -> Self {
+                Tag::$variant(value.into())
+            }
+        })*
+    };
+}
+
+impl_from! {
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    Vec<i8> => ByteArray,
+    String => String,
+    &str => String,
+    Vec<Tag> => List,
+    Compound => Compound,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray,
+}
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::Byte(value as i8)
+    }
+}
+
+/// Named tags, kept in the order they were inserted in so files are written back unchanged.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Compound {
+    entries: Vec<(String, Tag)>,
+}
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, tag)| tag)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Tag> {
+        self.entries
+            .iter_mut()
+            .find(|(key, _)| key == name)
+            .map(|(_, tag)| tag)
+    }
+
+    /// Replaces the tag of the same name in place, returning it.
+    pub fn insert(&mut self, name: impl Into<String>, tag: impl Into<Tag>) -> Option<Tag> {
+        let name = name.into();
+        let tag = tag.into();
+
+        match self.get_mut(&name) {
+            Some(slot) => Some(std::mem::replace(slot, tag)),
+            None => {
+                self.entries.push((name, tag));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Tag> {
+        let index = self.entries.iter().position(|(key, _)| key == name)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Tag)> {
+        self.entries.iter().map(|(key, tag)| (key.as_str(), tag))
+    }
+}
+
+impl IntoIterator for Compound {
+    type Item = (String, Tag);
+    type IntoIter = std::vec::IntoIter<(String, Tag)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<Tag>> FromIterator<(K, V)> for Compound {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut compound = Compound::new();
+        for (name, tag) in iter {
+            compound.insert(name, tag);
+        }
+        compound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::Compression;
+    use crate::worldgen::noise::Rng;
+
+    /// Any tag, with names and strings that need quoting or modified UTF-8 now and then.
+    fn random_tag(rng: &mut Rng, depth: usize) -> Tag {
+        let leaf = depth >= 4;
+        let kind = rng.range_i32(1, if leaf { 9 } else { 13 });
+
+        let length = rng.range_i32(0, 6) as usize;
+        let small = |rng: &mut Rng| rng.next_u64() as i64 >> rng.range_i32(0, 64);
+
+        match kind {
+            1 => Tag::Byte(small(rng) as i8),
+            2 => Tag::Short(small(rng) as i16),
+            3 => Tag::Int(small(rng) as i32),
+            4 => Tag::Long(small(rng)),
+            5 => Tag::Float(rng.range(-1e6, 1e6) as f32),
+            6 => Tag::Double(rng.range(-1e12, 1e12)),
+            7 => Tag::ByteArray((0..length).map(|_| small(rng) as i8).collect()),
+            8 => Tag::String(random_string(rng)),
+            9 => {
+                let first = random_tag(rng, depth + 1);
+                let mut list = vec![first];
+                while list.len() < length {
+                    let tag = random_tag(rng, depth + 1);
+                    if tag.get_id() == list[0].get_id() {
+                        list.push(tag);
+                    }
+                }
+                list.truncate(length);
+                Tag::List(list)
+            }
+            10 => Tag::Compound(
+                (0..length)
+                    .map(|_| (random_string(rng), random_tag(rng, depth + 1)))
+                    .collect(),
+            ),
+            11 => Tag::IntArray((0..length).map(|_| small(rng) as i32).collect()),
+            _ => Tag::LongArray((0..length).map(|_| small(rng)).collect()),
+        }
+    }
+
+    fn random_string(rng: &mut Rng) -> String {
+        const PIECES: [&str; 8] = ["a", "Z9", "_-.+", " ", "\\\"'", "\0", "é", "🧱"];
+        (0..rng.range_i32(0, 5))
+            .map(|_| PIECES[rng.range_i32(0, PIECES.len() as i32) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn random_tags_round_trip() {
+        let mut rng = Rng::new(15);
+
+        for _ in 0..500 {
+            let tag = random_tag(&mut rng, 0);
+            let name = random_string(&mut rng);
+
+            for compression in [Compression::None, Compression::Gzip, Compression::Zlib] {
+                let bytes = to_bytes(&name, &tag, compression).unwrap();
+                assert_eq!(from_bytes(&bytes).unwrap(), (name.clone(), tag.clone()));
+            }
+
+            let text = tag.to_string();
+            assert_eq!(text.parse::<Tag>().unwrap(), tag, "{text}");
+        }
+    }
+
+    #[test]
+    fn damaged_data_is_an_error_not_a_panic() {
+        let mut rng = Rng::new(16);
+
+        for _ in 0..500 {
+            let tag = Tag::Compound([("root", random_tag(&mut rng, 0))].into_iter().collect());
+            let mut bytes = to_bytes("", &tag, Compression::None).unwrap();
+
+            let cut = rng.range_i32(0, bytes.len() as i32) as usize;
+            assert!(read_uncompressed(&bytes[..cut]).is_err());
+
+            for _ in 0..4 {
+                let i = rng.range_i32(0, bytes.len() as i32) as usize;
+                bytes[i] = rng.next_u64() as u8;
+            }
+            let _ = read_uncompressed(&bytes);
+
+            let mut text = tag.to_string().into_bytes();
+            let i = rng.range_i32(0, text.len() as i32) as usize;
+            const PUNCTUATION: &[u8] = b"{}[]:;,\"' 1bL";
+            text[i] = PUNCTUATION[rng.range_i32(0, PUNCTUATION.len() as i32) as usize];
+            if let Ok(text) = std::str::from_utf8(&text) {
+                let _ = text.parse::<Tag>();
+            }
+        }
+    }
+}
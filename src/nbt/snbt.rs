@@ -0,0 +1,389 @@
+//! The text form used by commands, like `{id:"minecraft:stone",Count:1b,Pos:[0.5d,64d]}`.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use super::binary::MAX_DEPTH;
+use super::{Compound, Tag};
+
+impl Display for Tag {
+    /// Floats that aren't finite have no SNBT form and won't parse back.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Tag::Byte(v) => write!(f, "{v}b"),
+            Tag::Short(v) => write!(f, "{v}s"),
+            Tag::Int(v) => write!(f, "{v}"),
+            Tag::Long(v) => write!(f, "{v}L"),
+            Tag::Float(v) => write!(f, "{v}f"),
+            Tag::Double(v) => write!(f, "{v}d"),
+            Tag::ByteArray(v) => write_array(f, "B", v.iter().map(|v| format!("{v}b"))),
+            Tag::String(v) => write_string(f, v),
+            Tag::List(list) => {
+                f.write_char('[')?;
+                for (i, tag) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{tag}")?;
+                }
+                f.write_char(']')
+            }
+            Tag::Compound(compound) => write!(f, "{compound}"),
+            Tag::IntArray(v) => write_array(f, "I", v.iter().map(i32::to_string)),
+            Tag::LongArray(v) => write_array(f, "L", v.iter().map(|v| format!("{v}L"))),
+        }
+    }
+}
+
+impl Display for Compound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char('{')?;
+        for (i, (name, tag)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+
+            if !name.is_empty() && name.chars().all(is_unquoted) {
+                f.write_str(name)?;
+            } else {
+                write_string(f, name)?;
+            }
+            write!(f, ":{tag}")?;
+        }
+        f.write_char('}')
+    }
+}
+
+fn write_array(
+    f: &mut Formatter<'_>,
+    prefix: &str,
+    values: impl Iterator<Item = String>,
+) -> fmt::Result {
+    write!(f, "[{prefix};")?;
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        f.write_str(&value)?;
+    }
+    f.write_char(']')
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+fn is_unquoted(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(text: &str) -> std::io::Result<Self> {
+        let mut parser = Parser { text, position: 0 };
+
+        let tag = parser.parse_tag(0)?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        Ok(tag)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("bad SNBT at character {}: {msg}", self.position),
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.position += c.len_utf8();
+        }
+    }
+
+    /// Skips whitespace, then takes the character if it's the expected one.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+
+        let found = self.peek() == Some(expected);
+        if found {
+            self.position += expected.len_utf8();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> std::io::Result<()> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{expected}`")))
+        }
+    }
+
+    fn parse_tag(&mut self, depth: usize) -> std::io::Result<Tag> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("tags are nested too deep"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_compound(depth).map(Tag::Compound),
+            Some('[') => self.parse_list(depth),
+            Some('"' | '\'') => self.parse_quoted().map(Tag::String),
+            Some(_) => {
+                let token = self.parse_unquoted()?;
+                Ok(parse_scalar(token))
+            }
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_compound(&mut self, depth: usize) -> std::io::Result<Compound> {
+        self.expect('{')?;
+
+        let mut compound = Compound::new();
+        if self.eat('}') {
+            return Ok(compound);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let name = match self.peek() {
+                Some('"' | '\'') => self.parse_quoted()?,
+                _ => self.parse_unquoted()?.to_owned(),
+            };
+
+            self.expect(':')?;
+            let tag = self.parse_tag(depth + 1)?;
+            compound.insert(name, tag);
+
+            if self.eat('}') {
+                return Ok(compound);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_list(&mut self, depth: usize) -> std::io::Result<Tag> {
+        self.expect('[')?;
+
+        // typed arrays start with `B;`, `I;` or `L;`
+        let rest = &self.text[self.position..];
+        if let Some(kind @ ('B' | 'I' | 'L')) = rest.chars().next() {
+            if rest[1..].trim_start().starts_with(';') {
+                self.position += 1;
+                self.expect(';')?;
+                return self.parse_array(kind, depth);
+            }
+        }
+
+        let mut list = Vec::new();
+        if self.eat(']') {
+            return Ok(Tag::List(list));
+        }
+
+        loop {
+            let tag = self.parse_tag(depth + 1)?;
+            if list
+                .first()
+                .is_some_and(|first: &Tag| first.get_id() != tag.get_id())
+            {
+                return Err(self.error("list elements must all be of the same type"));
+            }
+            list.push(tag);
+
+            if self.eat(']') {
+                return Ok(Tag::List(list));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_array(&mut self, kind: char, depth: usize) -> std::io::Result<Tag> {
+        let mut values = Vec::new();
+
+        if !self.eat(']') {
+            loop {
+                let value = self.parse_tag(depth + 1)?;
+                let value = match (kind, value) {
+                    ('B', Tag::Byte(v)) => v as i64,
+                    ('I', Tag::Int(v)) => v as i64,
+                    ('L', Tag::Long(v)) => v,
+                    _ => return Err(self.error(&format!("bad element in `{kind}` array"))),
+                };
+                values.push(value);
+
+                if self.eat(']') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+
+        Ok(match kind {
+            'B' => Tag::ByteArray(values.into_iter().map(|v| v as i8).collect()),
+            'I' => Tag::IntArray(values.into_iter().map(|v| v as i32).collect()),
+            _ => Tag::LongArray(values),
+        })
+    }
+
+    fn parse_quoted(&mut self) -> std::io::Result<String> {
+        let quote = self.peek().unwrap();
+        self.position += 1;
+
+        let mut value = String::new();
+        let mut chars = self.text[self.position..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c @ ('\\' | '"' | '\''))) => value.push(c),
+                    _ => {
+                        self.position += i;
+                        return Err(self.error("unknown escape"));
+                    }
+                },
+                c if c == quote => {
+                    self.position += i + 1;
+                    return Ok(value);
+                }
+                c => value.push(c),
+            }
+        }
+
+        self.position = self.text.len();
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_unquoted(&mut self) -> std::io::Result<&str> {
+        let start = self.position;
+        while let Some(c) = self.peek().filter(|&c| is_unquoted(c)) {
+            self.position += c.len_utf8();
+        }
+
+        if start == self.position {
+            return Err(self.error("expected a value"));
+        }
+        Ok(&self.text[start..self.position])
+    }
+}
+
+/// Numbers with an optional type suffix, booleans, or else a string.
+fn parse_scalar(token: &str) -> Tag {
+    match token {
+        "true" => return Tag::Byte(1),
+        "false" => return Tag::Byte(0),
+        _ => {}
+    }
+
+    let (body, suffix) = token.split_at(token.len() - 1);
+    let number = match suffix {
+        "b" | "B" => body.parse().ok().map(Tag::Byte),
+        "s" | "S" => body.parse().ok().map(Tag::Short),
+        "l" | "L" => body.parse().ok().map(Tag::Long),
+        "f" | "F" => parse_float(body).map(Tag::Float),
+        "d" | "D" => parse_float(body).map(Tag::Double),
+        _ => token.parse().ok().map(Tag::Int).or_else(|| {
+            token
+                .contains(['.', 'e', 'E'])
+                .then(|| parse_float(token).map(Tag::Double))
+                .flatten()
+        }),
+    };
+
+    number.unwrap_or_else(|| Tag::String(token.to_owned()))
+}
+
+/// Like `str::parse`, without the words Rust accepts for infinities and NaN.
+fn parse_float<T: FromStr>(text: &str) -> Option<T> {
+    let digits = text.trim_start_matches(['+', '-']);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    text.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_command_style_snbt() {
+        let tag: Tag = r#"{
+            id: "minecraft:chest", Count: 1b, 'Custom Name': 'it\'s "quoted"',
+            Pos: [0.5d, 64.0, -3e2], Flag: true, small: 3s, big: 9000000000L,
+            bytes: [B; 1b, -2b], ints: [I;], longs: [L; 5L], word: oak_planks, f: .5f
+        }"#
+        .parse()
+        .unwrap();
+
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(
+            compound.get("id").unwrap().as_str(),
+            Some("minecraft:chest")
+        );
+        assert_eq!(compound.get("Count"), Some(&Tag::Byte(1)));
+        assert_eq!(
+            compound.get("Custom Name").unwrap().as_str(),
+            Some(r#"it's "quoted""#)
+        );
+        assert_eq!(
+            compound.get("Pos"),
+            Some(&Tag::List(vec![
+                Tag::Double(0.5),
+                Tag::Double(64.0),
+                Tag::Double(-300.0)
+            ]))
+        );
+        assert_eq!(compound.get("Flag"), Some(&Tag::Byte(1)));
+        assert_eq!(compound.get("small"), Some(&Tag::Short(3)));
+        assert_eq!(compound.get("big"), Some(&Tag::Long(9_000_000_000)));
+        assert_eq!(compound.get("bytes"), Some(&Tag::ByteArray(vec![1, -2])));
+        assert_eq!(compound.get("ints"), Some(&Tag::IntArray(vec![])));
+        assert_eq!(compound.get("longs"), Some(&Tag::LongArray(vec![5])));
+        assert_eq!(compound.get("word").unwrap().as_str(), Some("oak_planks"));
+        assert_eq!(compound.get("f"), Some(&Tag::Float(0.5)));
+
+        let again: Tag = tag.to_string().parse().unwrap();
+        assert_eq!(again, tag);
+    }
+
+    #[test]
+    fn bad_snbt_is_an_error() {
+        for text in [
+            "",
+            "{",
+            "{a:}",
+            "{a 1}",
+            "[1, 2b]",
+            "[B; 1, 2]",
+            "\"open",
+            "{a:1}}",
+            "'\\n'",
+        ] {
+            assert!(text.parse::<Tag>().is_err(), "{text}");
+        }
+    }
+}
@@ -132,6 +132,14 @@ impl Camera {
         );
     }
 
+    pub fn get_near_plane(&self) -> f32 {
+        self.near_plane
+    }
+
+    pub fn get_far_plane(&self) -> f32 {
+        self.far_plane
+    }
+
     pub fn get_projection(&self) -> &glam::Mat4 {
         &self.projection
     }
@@ -143,6 +151,31 @@ impl Camera {
     pub fn get_position(&self) -> &glam::Vec3 {
         &self.position
     }
+
+    pub fn get_forward_direction(&self) -> &glam::Vec3 {
+        &self.forward_direction
+    }
+
+    /// Turns the camera to look along the direction, ignored if it has no length.
+    pub fn set_forward_direction(&mut self, forward_direction: glam::Vec3) {
+        if let Some(forward_direction) = forward_direction.try_normalize() {
+            self.forward_direction = forward_direction;
+            self.recalculate_view();
+        }
+    }
+
+    /// Direction of the ray leaving the eye through the given window position.
+    pub fn get_ray_direction(&self, window_position: glam::Vec2) -> glam::Vec3 {
+        let ndc = glam::vec2(
+            2.0 * window_position.x / self.viewport_width as f32 - 1.0,
+            1.0 - 2.0 * window_position.y / self.viewport_height as f32,
+        );
+
+        let inverse = (self.projection * self.view).inverse();
+        let far = inverse.project_point3(ndc.extend(1.0));
+
+        (far - self.position).normalize()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
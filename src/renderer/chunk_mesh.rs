@@ -0,0 +1,197 @@
+use crate::defer;
+use crate::mesh::Vertex;
+use glow::HasContext;
+
+const F32S: usize = std::mem::size_of::<f32>();
+const STRIDE: usize = std::mem::size_of::<Vertex>();
+
+/// GPU copy of a chunk mesh.
+pub struct ChunkMesh<'a> {
+    gl: &'a glow::Context,
+    vao: glow::NativeVertexArray,
+    vbo: glow::NativeBuffer,
+
+    vertex_count: usize,
+    /// Box around every vertex, lights are picked by how close they come to it.
+    bounds: (glam::Vec3, glam::Vec3),
+}
+
+impl<'a> ChunkMesh<'a> {
+    pub fn new(gl: &'a glow::Context, vertices: &[Vertex]) -> Self {
+        let mut mesh = Self::init(gl, vertices.len());
+        mesh.bounds = bounds(vertices);
+
+        {
+            mesh.bind_vao();
+            defer! { mesh.unbind_vao(); }
+
+            mesh.fill_buffer(vertices);
+            mesh.setup_attrib_ptrs();
+
+            mesh.unbind_vbo();
+        }
+
+        mesh
+    }
+
+    /// Replaces the vertex data, reusing the GPU buffers.
+    pub fn update(&mut self, vertices: &[Vertex]) {
+        self.vertex_count = vertices.len();
+        self.bounds = bounds(vertices);
+
+        self.bind_vao();
+        defer! { self.unbind_vao(); }
+
+        self.fill_buffer(vertices);
+        self.unbind_vbo();
+    }
+
+    pub fn get_bounds(&self) -> (glam::Vec3, glam::Vec3) {
+        self.bounds
+    }
+
+    pub fn render(&self) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        unsafe {
+            self.bind_vao();
+            self.gl
+                .draw_arrays(glow::TRIANGLES, 0, self.vertex_count as _);
+        }
+    }
+
+    fn init(gl: &'a glow::Context, vertex_count: usize) -> Self {
+        let vao = unsafe { gl.create_vertex_array().unwrap() };
+        let vbo = unsafe { gl.create_buffer().unwrap() };
+
+        Self {
+            gl,
+            vao,
+            vbo,
+            vertex_count,
+            bounds: (glam::Vec3::ZERO, glam::Vec3::ZERO),
+        }
+    }
+
+    fn bind_vao(&self) {
+        unsafe { self.gl.bind_vertex_array(Some(self.vao)) };
+    }
+
+    fn unbind_vao(&self) {
+        unsafe { self.gl.bind_vertex_array(None) };
+    }
+
+    fn unbind_vbo(&self) {
+        unsafe { self.gl.bind_buffer(glow::ARRAY_BUFFER, None) };
+    }
+
+    /// Upload vertex data.
+    /// VAO must be bound before calling this.
+    fn fill_buffer(&self, vertices: &[Vertex]) {
+        assert!(self.is_vao_bound(), "VAO not bound");
+
+        unsafe {
+            self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            self.gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(vertices),
+                glow::DYNAMIC_DRAW,
+            );
+        }
+    }
+
+    /// Setup attribute points.
+    /// VBO must be bound before calling this.
+    fn setup_attrib_ptrs(&self) {
+        assert!(self.is_vbo_bound(), "VBO not bound");
+
+        unsafe {
+            // position
+            self.gl.enable_vertex_attrib_array(0);
+            self.gl
+                .vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, STRIDE as i32, 0);
+
+            // tex coord
+            self.gl.enable_vertex_attrib_array(1);
+            self.gl.vertex_attrib_pointer_f32(
+                1,
+                2,
+                glow::FLOAT,
+                false,
+                STRIDE as i32,
+                3 * F32S as i32,
+            );
+
+            // normal
+            self.gl.enable_vertex_attrib_array(2);
+            self.gl.vertex_attrib_pointer_f32(
+                2,
+                3,
+                glow::FLOAT,
+                false,
+                STRIDE as i32,
+                5 * F32S as i32,
+            );
+
+            // texture index
+            self.gl.enable_vertex_attrib_array(3);
+            self.gl.vertex_attrib_pointer_i32(
+                3,
+                1,
+                glow::UNSIGNED_INT,
+                STRIDE as i32,
+                8 * F32S as i32,
+            );
+
+            // sky and block light
+            self.gl.enable_vertex_attrib_array(4);
+            self.gl.vertex_attrib_pointer_f32(
+                4,
+                2,
+                glow::FLOAT,
+                false,
+                STRIDE as i32,
+                9 * F32S as i32,
+            );
+
+            // ambient occlusion
+            self.gl.enable_vertex_attrib_array(5);
+            self.gl.vertex_attrib_pointer_f32(
+                5,
+                1,
+                glow::FLOAT,
+                false,
+                STRIDE as i32,
+                11 * F32S as i32,
+            );
+        }
+    }
+
+    fn is_vao_bound(&self) -> bool {
+        let glow::NativeVertexArray(vao) = self.vao;
+        unsafe { self.gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING) == vao.get() as i32 }
+    }
+
+    fn is_vbo_bound(&self) -> bool {
+        let glow::NativeBuffer(vbo) = self.vbo;
+        unsafe { self.gl.get_parameter_i32(glow::ARRAY_BUFFER_BINDING) == vbo.get() as i32 }
+    }
+}
+
+fn bounds(vertices: &[Vertex]) -> (glam::Vec3, glam::Vec3) {
+    vertices.iter().map(|v| glam::Vec3::from(v.position)).fold(
+        (glam::Vec3::INFINITY, glam::Vec3::NEG_INFINITY),
+        |(min, max), p| (min.min(p), max.max(p)),
+    )
+}
+
+impl Drop for ChunkMesh<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_vertex_array(self.vao);
+            self.gl.delete_buffer(self.vbo);
+        }
+    }
+}
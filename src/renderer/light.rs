@@ -1,42 +1,293 @@
 use glow::HasContext;
 
-pub struct Light<'a> {
-    gl: &'a glow::Context,
-    light_position: glam::Vec3,
-    light_color: glam::Vec3,
-    // no drawing for now
-    // vao: glow::NativeVertexArray,
-    // vbo: glow::NativeBuffer,
+use crate::block::BlockRegistry;
+use crate::world::Chunk;
+
+/// Light coming from far away along a single direction, like the sun's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// Unit vector pointing towards the light.
+    pub direction: glam::Vec3,
+    pub color: glam::Vec3,
 }
 
-impl<'a> Light<'a> {
-    pub fn new(gl: &'a glow::Context, light_position: glam::Vec3, light_color: glam::Vec3) -> Self {
+impl DirectionalLight {
+    pub fn new(direction: glam::Vec3, color: glam::Vec3) -> Self {
         Self {
-            gl,
-            light_position,
-            light_color,
+            direction: direction.normalize(),
+            color,
         }
     }
+}
+
+/// Most point lights uploaded per frame, the ones furthest from the camera are left out.
+/// Matches `MAX_POINT_LIGHTS` in `basic.glsl`.
+const MAX_POINT_LIGHTS: usize = 256;
+/// Most point lights a single draw is lit by. Matches `LIGHTS_PER_DRAW` in `basic.glsl`.
+const LIGHTS_PER_DRAW: usize = 8;
+/// Uniform buffer binding point of the point lights.
+const POINT_LIGHTS_BINDING: u32 = 0;
+
+/// A light shining in every direction, fading out to nothing at `radius` blocks.
+/// Point lights aren't blocked by blocks, walls only hold back the sky and block light
+/// baked into meshes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: glam::Vec3,
+    pub color: glam::Vec3,
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(position: glam::Vec3, color: glam::Vec3, radius: f32) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+        }
+    }
+
+    /// Distance from the light to the nearest point of the `min..max` box.
+    fn distance_to(&self, min: glam::Vec3, max: glam::Vec3) -> f32 {
+        self.position.clamp(min, max).distance(self.position)
+    }
+}
+
+/// Point lights of the blocks in the chunk giving off light, in their `light_color` and
+/// reaching as many blocks as their light level.
+pub fn block_lights(chunk: &Chunk, registry: &BlockRegistry) -> Vec<PointLight> {
+    let origin = chunk.get_position().origin();
+
+    crate::light::emitters(chunk, registry)
+        .into_iter()
+        .filter_map(|pos| {
+            let local = pos - origin;
+            let block = registry.get(chunk.get_block(local.x, pos.y, local.z))?;
+            let center = pos.as_vec3() + 0.5;
+            (block.light > 0)
+                .then(|| PointLight::new(center, block.light_color, block.light as f32))
+        })
+        .collect()
+}
+
+/// Handle of a point light added to the renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(pub(super) u32);
 
-    pub fn set_uniforms(&self, program: &super::Program) {
+/// std140 layout of a point light.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuPointLight {
+    position: [f32; 3],
+    radius: f32,
+    color: [f32; 3],
+    _padding: f32,
+}
+
+/// The point lights of a frame, uploaded to a uniform buffer once. Each draw then picks
+/// the nearest lights that reach it out of the buffer.
+pub struct PointLights<'a> {
+    gl: &'a glow::Context,
+    buffer: glow::NativeBuffer,
+    lights: Vec<PointLight>,
+}
+
+impl<'a> PointLights<'a> {
+    pub fn new(gl: &'a glow::Context, program: &super::Program) -> Self {
         unsafe {
-            // light position
-            let glam::Vec3 { x, y, z } = self.light_position;
-            self.gl.uniform_3_f32(
-                program.get_uniform_location("light_position").as_ref(),
-                x,
-                y,
-                z,
+            let buffer = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::UNIFORM_BUFFER, Some(buffer));
+            gl.buffer_data_size(
+                glow::UNIFORM_BUFFER,
+                (MAX_POINT_LIGHTS * std::mem::size_of::<GpuPointLight>()) as i32,
+                glow::DYNAMIC_DRAW,
             );
+            gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+
+            let program = program.get_program_id();
+            if let Some(index) = gl.get_uniform_block_index(program, "PointLights") {
+                gl.uniform_block_binding(program, index, POINT_LIGHTS_BINDING);
+            }
+
+            Self {
+                gl,
+                buffer,
+                lights: vec![],
+            }
+        }
+    }
+
+    /// Uploads the lights for this frame, keeping those whose reach comes closest to
+    /// `eye` when there are too many.
+    pub fn upload(&mut self, lights: impl IntoIterator<Item = PointLight>, eye: glam::Vec3) {
+        self.lights.clear();
+        self.lights.extend(lights);
+        cap_lights(&mut self.lights, eye, MAX_POINT_LIGHTS);
 
-            // light color
-            let glam::Vec3 { x: r, y: g, z: b } = self.light_color;
-            self.gl.uniform_3_f32(
-                program.get_uniform_location("light_color").as_ref(),
-                r,
-                g,
-                b,
+        let data: Vec<_> = self
+            .lights
+            .iter()
+            .map(|light| GpuPointLight {
+                position: light.position.to_array(),
+                radius: light.radius,
+                color: light.color.to_array(),
+                _padding: 0.0,
+            })
+            .collect();
+
+        unsafe {
+            self.gl.bind_buffer(glow::UNIFORM_BUFFER, Some(self.buffer));
+            self.gl
+                .buffer_sub_data_u8_slice(glow::UNIFORM_BUFFER, 0, bytemuck::cast_slice(&data));
+            self.gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+            self.gl.bind_buffer_base(
+                glow::UNIFORM_BUFFER,
+                POINT_LIGHTS_BINDING,
+                Some(self.buffer),
             );
         }
     }
+
+    /// Lights the next draw, covering the `min..max` box, with the nearest lights that
+    /// reach into it.
+    pub fn set_uniforms(&self, program: &super::Program, min: glam::Vec3, max: glam::Vec3) {
+        let indices: Vec<i32> = lights_reaching(&self.lights, min, max, LIGHTS_PER_DRAW)
+            .into_iter()
+            .map(|i| i as i32)
+            .collect();
+
+        unsafe {
+            self.gl.uniform_1_i32(
+                program.get_uniform_location("point_light_count").as_ref(),
+                indices.len() as i32,
+            );
+            if !indices.is_empty() {
+                self.gl.uniform_1_i32_slice(
+                    program.get_uniform_location("point_light_indices").as_ref(),
+                    &indices,
+                );
+            }
+        }
+    }
+}
+
+impl Drop for PointLights<'_> {
+    fn drop(&mut self) {
+        unsafe { self.gl.delete_buffer(self.buffer) };
+    }
+}
+
+/// Keeps the `count` lights whose reach comes closest to `eye`, in no particular order.
+fn cap_lights(lights: &mut Vec<PointLight>, eye: glam::Vec3, count: usize) {
+    if lights.len() <= count {
+        return;
+    }
+
+    let reach = |light: &PointLight| light.position.distance(eye) - light.radius;
+    lights.select_nth_unstable_by(count, |a, b| reach(a).total_cmp(&reach(b)));
+    lights.truncate(count);
+}
+
+/// Indices of at most `count` lights reaching into the `min..max` box, nearest first.
+/// Lights inside the box are ordered by how close they are to its center.
+fn lights_reaching(
+    lights: &[PointLight],
+    min: glam::Vec3,
+    max: glam::Vec3,
+    count: usize,
+) -> Vec<usize> {
+    let center = (min + max) / 2.0;
+
+    let mut reaching: Vec<_> = lights
+        .iter()
+        .enumerate()
+        .map(|(i, light)| {
+            (
+                light.distance_to(min, max),
+                light.position.distance(center),
+                i,
+            )
+        })
+        .filter(|&(distance, _, i)| distance < lights[i].radius)
+        .collect();
+    reaching.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    reaching
+        .into_iter()
+        .take(count)
+        .map(|(_, _, i)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::*;
+    use crate::world::ChunkPos;
+
+    fn light_at(x: f32, radius: f32) -> PointLight {
+        PointLight::new(vec3(x, 0.0, 0.0), Vec3::ONE, radius)
+    }
+
+    #[test]
+    fn capping_keeps_the_lights_reaching_closest_to_the_eye() {
+        let mut lights: Vec<_> = (0..10).map(|i| light_at(i as f32 * 10.0, 1.0)).collect();
+        // far away, but reaching all the way to the eye
+        lights.push(light_at(500.0, 600.0));
+
+        cap_lights(&mut lights, Vec3::ZERO, 3);
+
+        let mut kept: Vec<_> = lights.iter().map(|light| light.position.x).collect();
+        kept.sort_by(f32::total_cmp);
+        assert_eq!(kept, [0.0, 10.0, 500.0]);
+
+        // fewer lights than the cap are all kept
+        cap_lights(&mut lights, Vec3::ZERO, 8);
+        assert_eq!(lights.len(), 3);
+    }
+
+    #[test]
+    fn glowing_blocks_light_the_chunks_they_reach() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let glowstone = registry.get_id("glowstone").unwrap();
+
+        let mut chunk = Chunk::new(ChunkPos::new(1, 0));
+        chunk.set_block(2, 70, 3, glowstone);
+        chunk.set_block(3, 70, 3, registry.get_id("stone").unwrap());
+
+        let lights = block_lights(&chunk, &registry);
+        assert_eq!(
+            lights,
+            [PointLight::new(
+                vec3(18.5, 70.5, 3.5),
+                registry.get(glowstone).unwrap().light_color,
+                15.0
+            )]
+        );
+
+        // uploaded alongside a lantern, and reaching the chunk next door
+        let mut uploaded = vec![light_at(-100.0, 10.0)];
+        uploaded.extend(lights);
+        cap_lights(&mut uploaded, vec3(0.0, 70.0, 0.0), 8);
+        assert_eq!(uploaded.len(), 2);
+
+        let (min, max) = (vec3(0.0, 64.0, 0.0), vec3(16.0, 80.0, 16.0));
+        assert_eq!(lights_reaching(&uploaded, min, max, 8), [1]);
+    }
+
+    #[test]
+    fn draws_take_the_nearest_lights_that_reach_them() {
+        let lights = [
+            light_at(30.0, 5.0),  // doesn't reach the box
+            light_at(20.0, 15.0), // 4 blocks outside
+            light_at(12.0, 1.0),  // inside, off center
+            light_at(8.0, 1.0),   // inside, at the center
+            light_at(-3.0, 8.0),  // 3 blocks outside
+        ];
+        let (min, max) = (vec3(0.0, -8.0, -8.0), vec3(16.0, 8.0, 8.0));
+
+        assert_eq!(lights_reaching(&lights, min, max, 8), [3, 2, 4, 1]);
+        assert_eq!(lights_reaching(&lights, min, max, 2), [3, 2]);
+    }
 }
@@ -1,30 +1,81 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
 use glow::HasContext;
 
 mod camera;
-mod cube;
+mod chunk_mesh;
 mod light;
 mod program_manager;
+mod shadow;
+mod sky;
 mod texture;
 
-use cube::Cubes;
+use chunk_mesh::ChunkMesh;
 use program_manager::Program;
+use shadow::ShadowMaps;
 use texture::TextureData;
 
+use crate::block::BlockRegistry;
+use crate::jobs::{Job, JobResult, JobSystem, MeshVersions};
+use crate::mesh;
+use crate::save::WorldSave;
+use crate::world::{BlockId, ChunkPos, World, CHUNK_SIZE};
+
 pub use camera::*;
-pub use light::Light;
+use light::PointLights;
+pub use light::{block_lights, DirectionalLight, LightId, PointLight};
+pub use sky::Sky;
+
+const TEXTURE_WIDTH: usize = 16;
+const TEXTURE_HEIGHT: usize = 16;
+
+/// Chunk meshes uploaded to the GPU per frame at most, the rest wait for later frames.
+const MAX_UPLOADS_PER_FRAME: usize = 8;
 
-const TEXTURE_WIDTH: usize = 64;
-const TEXTURE_HEIGHT: usize = 48;
+/// Dirty chunks sent off to be meshed per frame at most. Chunks held by queued jobs are
+/// copied on the main thread when edited, so the queue is kept short.
+const MAX_MESH_JOBS_PER_FRAME: usize = 16;
+
+/// Chunks queued for generation at once, kept low so newly visible chunks
+/// can jump ahead when the camera moves.
+const MAX_PENDING_GENERATIONS: usize = 16;
+
+/// Radius in chunks around the camera that is kept loaded.
+const DEFAULT_RENDER_DISTANCE: i32 = 8;
 
 pub struct Renderer<'a> {
     gl: &'a glow::Context,
-    cubes: Cubes<'a>,
+    chunk_meshes: HashMap<ChunkPos, ChunkMesh<'a>>,
+
+    world: World,
+    registry: Arc<BlockRegistry>,
+    mesh_mode: mesh::MeshMode,
+    jobs: JobSystem,
+    /// Chunks whose mesh no longer matches the world, sent to the workers before the next draw.
+    dirty_chunks: HashSet<ChunkPos>,
+    /// Chunks being generated by the workers.
+    generating: HashSet<ChunkPos>,
+    /// Version of the latest mesh job of each chunk, older results are dropped.
+    mesh_versions: MeshVersions,
+    render_distance: i32,
+    /// Where edited chunks are written when they unload, if anywhere.
+    save: Option<Arc<WorldSave>>,
+    /// Loaded chunks edited since they were last saved.
+    unsaved_chunks: HashSet<ChunkPos>,
 
     // texture_id: glow::NativeTexture,
     texture_array_id: glow::NativeTexture,
 
     camera: Camera,
-    light: Light<'a>,
+    sky: Sky,
+    shadows: ShadowMaps<'a>,
+    point_lights: PointLights<'a>,
+    /// Lights of the blocks giving off light in each meshed chunk.
+    block_lights: HashMap<ChunkPos, Vec<PointLight>>,
+    /// Lights added with `add_point_light`.
+    dynamic_lights: HashMap<LightId, PointLight>,
+    next_light_id: u32,
     program: Program<'a>,
 }
 
@@ -32,10 +83,11 @@ impl<'a> Renderer<'a> {
     pub fn new(
         gl: &'a glow::Context,
         camera: Camera,
-        instance_positions: &[f32],
-        instance_texture_ids: &[i32],
-        light_color: glam::Vec3,
-        light_position: glam::Vec3,
+        world: World,
+        registry: Arc<BlockRegistry>,
+        jobs: JobSystem,
+        mesh_mode: mesh::MeshMode,
+        sky: Sky,
     ) -> Self {
         // let texture_data = TextureData::new("res/dirt.png");
         // let texture_id = unsafe {
@@ -70,21 +122,26 @@ impl<'a> Renderer<'a> {
         .expect("failed to create shader program");
 
         let texture_array_id = unsafe { gl.create_texture().unwrap() };
-        let texture_names = ["res/grass.png", "res/dirt.png"];
+        let texture_names = registry.get_textures();
 
         unsafe {
             gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(texture_array_id));
             gl.tex_storage_3d(
                 glow::TEXTURE_2D_ARRAY,
                 1,
-                glow::RGB8,
+                glow::RGBA8,
                 TEXTURE_WIDTH as _,
                 TEXTURE_HEIGHT as _,
                 texture_names.len() as _,
             );
 
             for (z_offset, name) in texture_names.iter().enumerate() {
-                let data = TextureData::new(name);
+                let data = TextureData::new(&format!("res/block/{name}.png"));
+                assert!(
+                    data.width == TEXTURE_WIDTH as i32 && data.height == TEXTURE_HEIGHT as i32,
+                    "texture `{name}` must be {TEXTURE_WIDTH}x{TEXTURE_HEIGHT}"
+                );
+
                 gl.tex_sub_image_3d(
                     glow::TEXTURE_2D_ARRAY,
                     0,
@@ -104,22 +161,189 @@ impl<'a> Renderer<'a> {
             texture::setup_texture_params(gl, glow::TEXTURE_2D_ARRAY);
         }
 
+        let dirty_chunks = world.chunks().map(|chunk| chunk.get_position()).collect();
+        let point_lights = PointLights::new(gl, &program);
+        let shadows = ShadowMaps::new(gl);
+
         Self {
             gl,
             texture_array_id,
             camera,
             program,
 
-            light: Light::new(gl, light_position, light_color),
-            cubes: Cubes::new(gl, instance_positions, instance_texture_ids),
+            sky,
+            shadows,
+            point_lights,
+            block_lights: HashMap::new(),
+            dynamic_lights: HashMap::new(),
+            next_light_id: 0,
+            chunk_meshes: HashMap::new(),
+
+            world,
+            registry,
+            jobs,
+            mesh_mode,
+            dirty_chunks,
+            generating: HashSet::new(),
+            mesh_versions: MeshVersions::default(),
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            save: None,
+            unsaved_chunks: HashSet::new(),
+        }
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn get_camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn get_sky(&self) -> &Sky {
+        &self.sky
+    }
+
+    pub fn set_sky(&mut self, sky: Sky) {
+        self.sky = sky;
+    }
+
+    /// Returns the block that was previously at the position, or `None` when its chunk
+    /// isn't loaded. Such edits are dropped, the chunk would otherwise be created empty and
+    /// keep its terrain from ever being generated. Only the chunk containing the block, the
+    /// chunks it borders and chunks whose light changed are re-meshed.
+    pub fn set_block(&mut self, pos: glam::IVec3, block: BlockId) -> Option<BlockId> {
+        let chunk_pos = ChunkPos::from_block(pos);
+        self.world.get_chunk(chunk_pos)?;
+
+        let old = self.world.set_block(pos, block);
+        if old == block {
+            return Some(old);
+        }
+
+        let relit = crate::light::update_block(&mut self.world, &self.registry, pos);
+        self.dirty_chunks.extend(relit);
+
+        self.dirty_chunks.insert(chunk_pos);
+        self.unsaved_chunks.insert(chunk_pos);
+
+        // faces of the neighbouring chunk may have been hidden or exposed
+        let (x, z) = (pos.x.rem_euclid(CHUNK_SIZE), pos.z.rem_euclid(CHUNK_SIZE));
+        let neighbours = [
+            (x == 0, ChunkPos::new(chunk_pos.x - 1, chunk_pos.z)),
+            (
+                x == CHUNK_SIZE - 1,
+                ChunkPos::new(chunk_pos.x + 1, chunk_pos.z),
+            ),
+            (z == 0, ChunkPos::new(chunk_pos.x, chunk_pos.z - 1)),
+            (
+                z == CHUNK_SIZE - 1,
+                ChunkPos::new(chunk_pos.x, chunk_pos.z + 1),
+            ),
+        ];
+
+        for (on_border, neighbour) in neighbours {
+            if on_border && self.world.get_chunk(neighbour).is_some() {
+                self.dirty_chunks.insert(neighbour);
+            }
         }
+
+        Some(old)
+    }
+
+    /// Replaces the block with air, returns the removed block like `set_block`.
+    pub fn remove_block(&mut self, pos: glam::IVec3) -> Option<BlockId> {
+        self.set_block(pos, BlockId::AIR)
+    }
+
+    /// Adds a light that doesn't belong to a block, like one carried around.
+    pub fn add_point_light(&mut self, light: PointLight) -> LightId {
+        let id = LightId(self.next_light_id);
+        self.next_light_id += 1;
+        self.dynamic_lights.insert(id, light);
+        id
+    }
+
+    /// Moves or changes a light added with `add_point_light`, unless it was removed.
+    pub fn set_point_light(&mut self, id: LightId, light: PointLight) {
+        if let Some(old) = self.dynamic_lights.get_mut(&id) {
+            *old = light;
+        }
+    }
+
+    pub fn remove_point_light(&mut self, id: LightId) {
+        self.dynamic_lights.remove(&id);
+    }
+
+    /// Chunks further than this from the camera, in chunks, are unloaded.
+    pub fn set_render_distance(&mut self, render_distance: i32) {
+        self.render_distance = render_distance.max(1);
+    }
+
+    /// Edited chunks are written to the save when they unload, or by `save_chunks`.
+    pub fn set_save(&mut self, save: Arc<WorldSave>) {
+        self.save = Some(save);
+    }
+
+    /// Writes every edited chunk that is still loaded.
+    pub fn save_chunks(&mut self) -> std::io::Result<()> {
+        let unsaved: Vec<_> = self.unsaved_chunks.iter().copied().collect();
+        for pos in unsaved {
+            self.save_chunk(pos)?;
+        }
+
+        Ok(())
+    }
+
+    fn save_chunk(&mut self, pos: ChunkPos) -> std::io::Result<()> {
+        if let (Some(save), Some(chunk)) = (&self.save, self.world.get_chunk(pos)) {
+            save.save_chunk(chunk)?;
+        }
+
+        self.unsaved_chunks.remove(&pos);
+        Ok(())
+    }
+
+    /// Generates the chunk in the background, unless it's already loaded or on its way.
+    fn request_chunk(&mut self, pos: ChunkPos) {
+        if self.world.get_chunk(pos).is_some() || !self.generating.insert(pos) {
+            return;
+        }
+
+        self.jobs.submit(Job::Generate(pos));
     }
 
     pub fn render(&mut self) {
+        self.update_chunk_meshes();
+
+        self.camera.recalculate_view();
+        self.camera.recalculate_projection();
+
+        self.bind_texture();
+        self.shadows.update(&self.camera, self.sky.light.direction);
+        self.shadows.render(self.chunk_meshes.values());
+
         self.program.use_program();
         self.set_uniforms();
-        self.bind_texture();
-        self.cubes.render();
+        self.shadows.set_uniforms(&self.program);
+
+        // capped to those reaching closest to the camera
+        let lights = self
+            .dynamic_lights
+            .values()
+            .chain(self.block_lights.values().flatten());
+        self.point_lights
+            .upload(lights.copied(), *self.camera.get_position());
+
+        for chunk_mesh in self.chunk_meshes.values() {
+            let (min, max) = chunk_mesh.get_bounds();
+            self.point_lights.set_uniforms(&self.program, min, max);
+            chunk_mesh.render();
+        }
     }
 
     pub fn resize_camera(&mut self, new_width: u32, new_height: u32) {
@@ -129,6 +353,183 @@ impl<'a> Renderer<'a> {
 
     pub fn update(&mut self, dt: f32, pointer_state: PointerState, keyboard_state: KeyboardState) {
         self.camera.update(dt, pointer_state, keyboard_state);
+        self.stream_chunks();
+    }
+
+    /// Unloads chunks that fell out of range and requests missing ones,
+    /// nearest first with chunks in front of the camera preferred over those behind it.
+    fn stream_chunks(&mut self) {
+        let center = self.get_camera_chunk();
+
+        let far_chunks: Vec<_> = self
+            .world
+            .chunks()
+            .map(|chunk| chunk.get_position())
+            .filter(|&pos| !self.is_in_range(center, pos, 1))
+            .collect();
+
+        for pos in far_chunks {
+            if self.unsaved_chunks.contains(&pos) {
+                if let Err(error) = self.save_chunk(pos) {
+                    eprintln!("failed to save chunk {}, {}: {error}", pos.x, pos.z);
+                }
+            }
+            self.unsaved_chunks.remove(&pos);
+
+            self.world.remove_chunk(pos);
+            self.jobs.unload(pos);
+            self.chunk_meshes.remove(&pos);
+            self.block_lights.remove(&pos);
+            self.mesh_versions.forget(pos);
+            self.dirty_chunks.remove(&pos);
+        }
+
+        let budget = MAX_PENDING_GENERATIONS.saturating_sub(self.generating.len());
+        if budget == 0 {
+            return;
+        }
+
+        let &glam::Vec3 { x, z, .. } = self.camera.get_forward_direction();
+        let forward = glam::vec2(x, z).normalize_or_zero();
+        let r = self.render_distance;
+
+        let mut missing = vec![];
+        for dx in -r..=r {
+            for dz in -r..=r {
+                let pos = ChunkPos::new(center.x + dx, center.z + dz);
+
+                if !self.is_in_range(center, pos, 0)
+                    || self.world.get_chunk(pos).is_some()
+                    || self.generating.contains(&pos)
+                {
+                    continue;
+                }
+
+                let offset = glam::vec2(dx as f32, dz as f32);
+                let facing = forward.dot(offset.normalize_or_zero());
+
+                // chunks straight ahead count as half as far away, the ones behind as 1.5 times
+                let priority = offset.length() * (1.0 - 0.5 * facing);
+                missing.push((priority, pos));
+            }
+        }
+
+        missing.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, pos) in missing.into_iter().take(budget) {
+            self.request_chunk(pos);
+        }
+    }
+
+    fn get_camera_chunk(&self) -> ChunkPos {
+        ChunkPos::from_block(self.camera.get_position().floor().as_ivec3())
+    }
+
+    /// Whether the chunk is within the render distance, plus `margin` chunks.
+    fn is_in_range(&self, center: ChunkPos, pos: ChunkPos, margin: i32) -> bool {
+        let (dx, dz) = (pos.x - center.x, pos.z - center.z);
+        let r = self.render_distance + margin;
+
+        dx * dx + dz * dz <= r * r
+    }
+
+    /// Sends at most `MAX_MESH_JOBS_PER_FRAME` dirty chunks off to be meshed, nearest
+    /// first, and takes in finished jobs, uploading at most `MAX_UPLOADS_PER_FRAME` meshes.
+    fn update_chunk_meshes(&mut self) {
+        let center = self.get_camera_chunk();
+        let mut dirty: Vec<_> = self.dirty_chunks.iter().copied().collect();
+        dirty.sort_by_key(|pos| (pos.x - center.x).pow(2) + (pos.z - center.z).pow(2));
+
+        for pos in dirty.into_iter().take(MAX_MESH_JOBS_PER_FRAME) {
+            self.dirty_chunks.remove(&pos);
+
+            if self.world.get_chunk(pos).is_none() {
+                self.chunk_meshes.remove(&pos);
+                self.block_lights.remove(&pos);
+                self.mesh_versions.forget(pos);
+                continue;
+            }
+
+            let version = self.mesh_versions.next(pos);
+            self.jobs.submit(Job::Mesh {
+                pos,
+                world: self.world.neighbourhood(pos),
+                mode: self.mesh_mode,
+                version,
+            });
+        }
+
+        let mut uploads = 0;
+        while uploads < MAX_UPLOADS_PER_FRAME {
+            let Some(result) = self.jobs.try_recv() else {
+                break;
+            };
+
+            match result {
+                JobResult::Generated(chunk) => {
+                    let pos = chunk.get_position();
+                    self.generating.remove(&pos);
+
+                    // edits made while the chunk was generating win, the loaded chunk
+                    // keeps what the generator knows about it
+                    if self.world.get_chunk(pos).is_some() {
+                        continue;
+                    }
+
+                    // the camera moved away in the meantime
+                    if !self.is_in_range(self.get_camera_chunk(), pos, 1) {
+                        self.jobs.unload(pos);
+                        continue;
+                    }
+
+                    self.world.insert_chunk(*chunk);
+                    let relit = crate::light::join_chunk(&mut self.world, &self.registry, pos);
+                    self.dirty_chunks.extend(relit);
+
+                    // border faces of the neighbours may be hidden by the new chunk
+                    for dx in -1..=1 {
+                        for dz in -1..=1 {
+                            let neighbour = ChunkPos::new(pos.x + dx, pos.z + dz);
+                            if self.world.get_chunk(neighbour).is_some() {
+                                self.dirty_chunks.insert(neighbour);
+                            }
+                        }
+                    }
+                }
+
+                JobResult::Meshed {
+                    pos,
+                    vertices,
+                    lights,
+                    version,
+                } => {
+                    if !self.mesh_versions.finish(pos, version) {
+                        continue;
+                    }
+
+                    if lights.is_empty() {
+                        self.block_lights.remove(&pos);
+                    } else {
+                        self.block_lights.insert(pos, lights);
+                    }
+
+                    uploads += 1;
+
+                    if vertices.is_empty() {
+                        self.chunk_meshes.remove(&pos);
+                        continue;
+                    }
+
+                    match self.chunk_meshes.get_mut(&pos) {
+                        Some(chunk_mesh) => chunk_mesh.update(&vertices),
+                        None => {
+                            self.chunk_meshes
+                                .insert(pos, ChunkMesh::new(self.gl, &vertices));
+                        }
+                    }
+                }
+            }
+        }
     }
 
     fn bind_texture(&self) {
@@ -139,15 +540,41 @@ impl<'a> Renderer<'a> {
     }
 
     fn set_uniforms(&mut self) {
-        self.camera.recalculate_view();
-        self.camera.recalculate_projection();
-
         let view = self.camera.get_view();
         let projection = self.camera.get_projection();
 
-        self.light.set_uniforms(&self.program);
-
         unsafe {
+            self.gl
+                .uniform_1_i32(self.program.get_uniform_location("tex_array").as_ref(), 0);
+
+            // sun or moon
+            let DirectionalLight { direction, color } = self.sky.light;
+            self.gl.uniform_3_f32(
+                self.program
+                    .get_uniform_location("light_direction")
+                    .as_ref(),
+                direction.x,
+                direction.y,
+                direction.z,
+            );
+            self.gl.uniform_3_f32(
+                self.program.get_uniform_location("light_color").as_ref(),
+                color.x,
+                color.y,
+                color.z,
+            );
+
+            self.gl.uniform_1_f32(
+                self.program
+                    .get_uniform_location("ambient_strength")
+                    .as_ref(),
+                self.sky.ambient,
+            );
+            self.gl.uniform_1_f32(
+                self.program.get_uniform_location("sky_light").as_ref(),
+                self.sky.sky_light,
+            );
+
             // eye position
             let &glam::Vec3 { x, y, z } = self.camera.get_position();
             self.gl.uniform_3_f32(
@@ -0,0 +1,233 @@
+use glow::HasContext;
+
+use super::{Camera, ChunkMesh, Program};
+
+/// Shadow maps covering ever larger slices of the view, nearest first.
+/// Matches `CASCADES` in `basic.glsl`.
+const CASCADES: usize = 3;
+/// How far from the camera each cascade reaches, in blocks. Nothing casts shadows
+/// further away than the last one.
+const CASCADE_ENDS: [f32; CASCADES] = [16.0, 48.0, 128.0];
+/// Width and height of each shadow map, in texels.
+const SHADOW_MAP_SIZE: i32 = 2048;
+/// How far towards the sun blocks outside a cascade still cast shadows into it.
+const CASTER_DISTANCE: f32 = 256.0;
+
+/// Cascaded shadow maps of the sun or moon, one layer of a depth texture array per cascade.
+pub struct ShadowMaps<'a> {
+    gl: &'a glow::Context,
+    texture: glow::NativeTexture,
+    framebuffer: glow::NativeFramebuffer,
+    program: Program<'a>,
+    /// Clip space of the light, for each cascade.
+    light_spaces: [glam::Mat4; CASCADES],
+    /// Size of a shadow map texel in blocks, for each cascade.
+    texel_sizes: [f32; CASCADES],
+}
+
+impl<'a> ShadowMaps<'a> {
+    pub fn new(gl: &'a glow::Context) -> Self {
+        let program = Program::from_str(
+            gl,
+            include_str!("../shader/shadow.glsl"),
+            "vertex",
+            "fragment",
+        )
+        .expect("failed to create shadow program");
+
+        unsafe {
+            let texture = gl.create_texture().unwrap();
+            gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(texture));
+            gl.tex_storage_3d(
+                glow::TEXTURE_2D_ARRAY,
+                1,
+                glow::DEPTH_COMPONENT24,
+                SHADOW_MAP_SIZE,
+                SHADOW_MAP_SIZE,
+                CASCADES as i32,
+            );
+
+            // linear filtering compares the four nearest texels, smoothing PCF further
+            for (param, value) in [
+                (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+                (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+                (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_BORDER),
+                (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_BORDER),
+                (glow::TEXTURE_COMPARE_MODE, glow::COMPARE_REF_TO_TEXTURE),
+                (glow::TEXTURE_COMPARE_FUNC, glow::LEQUAL),
+            ] {
+                gl.tex_parameter_i32(glow::TEXTURE_2D_ARRAY, param, value as i32);
+            }
+            // outside the maps everything is lit
+            gl.tex_parameter_f32_slice(
+                glow::TEXTURE_2D_ARRAY,
+                glow::TEXTURE_BORDER_COLOR,
+                &[1.0; 4],
+            );
+            gl.bind_texture(glow::TEXTURE_2D_ARRAY, None);
+
+            let framebuffer = gl.create_framebuffer().unwrap();
+
+            Self {
+                gl,
+                texture,
+                framebuffer,
+                program,
+                light_spaces: [glam::Mat4::IDENTITY; CASCADES],
+                texel_sizes: [0.0; CASCADES],
+            }
+        }
+    }
+
+    /// Fits each cascade around its slice of the camera's view, as seen from the light
+    /// shining along `-towards_light`.
+    pub fn update(&mut self, camera: &Camera, towards_light: glam::Vec3) {
+        let inverse = (*camera.get_projection() * *camera.get_view()).inverse();
+        let (near, far) = (camera.get_near_plane(), camera.get_far_plane());
+
+        // rays from the near to the far plane through the corners of the view
+        let rays = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+            let start = inverse.project_point3(glam::vec3(x, y, -1.0));
+            let end = inverse.project_point3(glam::vec3(x, y, 1.0));
+            (start, end)
+        });
+
+        let up = if towards_light.y.abs() > 0.99 {
+            glam::Vec3::Z
+        } else {
+            glam::Vec3::Y
+        };
+        let view = glam::Mat4::look_at_rh(glam::Vec3::ZERO, -towards_light, up);
+
+        let mut slice_start = near;
+        for (i, &slice_end) in CASCADE_ENDS.iter().enumerate() {
+            let corners: Vec<_> = rays
+                .iter()
+                .flat_map(|&(start, end)| {
+                    // depth grows linearly along each ray
+                    let at = |depth: f32| start.lerp(end, (depth - near) / (far - near));
+                    [at(slice_start), at(slice_end)]
+                })
+                .collect();
+
+            // a sphere around the slice keeps the cascade the same size as the camera turns
+            let center = corners.iter().sum::<glam::Vec3>() / corners.len() as f32;
+            let radius = corners
+                .iter()
+                .map(|corner| corner.distance(center))
+                .fold(0.0, f32::max)
+                .ceil();
+
+            // moved in whole texels, so shadow edges don't crawl as the camera moves
+            let texel = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+            let center = view.transform_point3(center);
+            let (x, y) = (
+                (center.x / texel).floor() * texel,
+                (center.y / texel).floor() * texel,
+            );
+
+            let projection = glam::Mat4::orthographic_rh_gl(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                -center.z - radius - CASTER_DISTANCE,
+                -center.z + radius,
+            );
+
+            self.light_spaces[i] = projection * view;
+            self.texel_sizes[i] = texel;
+            slice_start = slice_end;
+        }
+    }
+
+    /// Draws the depth of the meshes into every cascade. The block texture array must be
+    /// bound, cut out texels don't cast shadows.
+    pub fn render<'m>(&self, meshes: impl Iterator<Item = &'m ChunkMesh<'a>> + Clone)
+    where
+        'a: 'm,
+    {
+        let gl = self.gl;
+
+        unsafe {
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+            gl.draw_buffer(glow::NONE);
+            gl.read_buffer(glow::NONE);
+            gl.viewport(0, 0, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE);
+
+            // plants are single quads seen from both sides, and slopes need pushing back
+            // to not shadow themselves
+            gl.disable(glow::CULL_FACE);
+            gl.enable(glow::POLYGON_OFFSET_FILL);
+            gl.polygon_offset(2.0, 4.0);
+
+            self.program.use_program();
+            for (i, light_space) in self.light_spaces.iter().enumerate() {
+                gl.framebuffer_texture_layer(
+                    glow::FRAMEBUFFER,
+                    glow::DEPTH_ATTACHMENT,
+                    Some(self.texture),
+                    0,
+                    i as i32,
+                );
+                gl.clear(glow::DEPTH_BUFFER_BIT);
+
+                gl.uniform_matrix_4_f32_slice(
+                    self.program.get_uniform_location("light_space").as_ref(),
+                    false,
+                    &light_space.to_cols_array(),
+                );
+                meshes.clone().for_each(ChunkMesh::render);
+            }
+
+            gl.disable(glow::POLYGON_OFFSET_FILL);
+            gl.enable(glow::CULL_FACE);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+
+    /// Binds the shadow maps to texture unit 1 for `program`.
+    pub fn set_uniforms(&self, program: &Program) {
+        let gl = self.gl;
+
+        unsafe {
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(self.texture));
+            gl.active_texture(glow::TEXTURE0);
+
+            gl.uniform_1_i32(program.get_uniform_location("shadow_map").as_ref(), 1);
+
+            let light_spaces: Vec<f32> = self
+                .light_spaces
+                .iter()
+                .flat_map(|m| m.to_cols_array())
+                .collect();
+            gl.uniform_matrix_4_f32_slice(
+                program.get_uniform_location("light_space").as_ref(),
+                false,
+                &light_spaces,
+            );
+            gl.uniform_1_f32_slice(
+                program.get_uniform_location("cascade_ends").as_ref(),
+                &CASCADE_ENDS,
+            );
+            gl.uniform_1_f32_slice(
+                program.get_uniform_location("shadow_texel_sizes").as_ref(),
+                &self.texel_sizes,
+            );
+        }
+    }
+}
+
+impl Drop for ShadowMaps<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_framebuffer(self.framebuffer);
+            self.gl.delete_texture(self.texture);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use super::DirectionalLight;
+
+const SUN_COLOR: glam::Vec3 = glam::vec3(1.0, 0.97, 0.9);
+const MOON_COLOR: glam::Vec3 = glam::vec3(0.15, 0.18, 0.28);
+
+const DAY_SKY: glam::Vec3 = glam::vec3(0.55, 0.75, 1.0);
+const NIGHT_SKY: glam::Vec3 = glam::vec3(0.01, 0.01, 0.04);
+const TWILIGHT_SKY: glam::Vec3 = glam::vec3(0.95, 0.55, 0.3);
+
+/// Everything about the sky that changes over the day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    /// The sun by day and the moon by night, whichever is up lights the world and casts
+    /// shadows.
+    pub light: DirectionalLight,
+    /// Background colour.
+    pub color: glam::Vec3,
+    /// Light reaching every face the sky reaches, lit by the sun or not.
+    pub ambient: f32,
+    /// How bright sky light is compared to noon, block light doesn't change.
+    pub sky_light: f32,
+}
+
+impl Sky {
+    /// The sky `day_fraction` of the way through the day, `0` being sunrise. The sun rises
+    /// in the east and sets in the west, leaning south so there are shadows at noon, and
+    /// the moon is always across the sky from it.
+    pub fn at(day_fraction: f32) -> Self {
+        let angle = day_fraction * std::f32::consts::TAU;
+        let sun = glam::vec3(angle.cos(), angle.sin(), 0.3).normalize();
+
+        // both fade out around the horizon, so swapping between them doesn't show
+        let light = if sun.y >= 0.0 {
+            DirectionalLight::new(sun, SUN_COLOR * smoothstep(-0.05, 0.15, sun.y))
+        } else {
+            DirectionalLight::new(-sun, MOON_COLOR * smoothstep(-0.05, 0.15, -sun.y))
+        };
+
+        let daylight = smoothstep(-0.1, 0.2, sun.y);
+        // reddened while the sun is close to the horizon
+        let twilight = (1.0 - sun.y.abs() / 0.3).clamp(0.0, 1.0);
+        let color = NIGHT_SKY
+            .lerp(DAY_SKY, daylight)
+            .lerp(TWILIGHT_SKY, 0.5 * twilight * twilight);
+
+        Self {
+            light,
+            color,
+            ambient: 0.08 + 0.12 * daylight,
+            sky_light: 0.4 + 0.6 * daylight,
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
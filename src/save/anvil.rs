@@ -0,0 +1,998 @@
+//! Vanilla Java Edition worlds, read straight from the `region/*.mca` files of a save folder.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use glam::{ivec3, IVec3};
+use serde::Deserialize;
+
+use super::RegionFile;
+use crate::block::{BlockRegistry, Property};
+use crate::nbt::{self, Compound, Tag};
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::worldgen::{Biome, Generator};
+
+/// Which of our blocks and biomes stand in for the vanilla ones.
+pub const VANILLA_MAPPING_PATH: &str = "res/vanilla.json";
+
+/// First data version whose block indices never straddle two longs (20w17a).
+const UNSTRADDLED_DATA_VERSION: i64 = 2529;
+
+const SECTION_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+/// Biomes are stored for 4×4×4 cells.
+const BIOME_CELLS: usize = SECTION_VOLUME / 64;
+
+/// Biomes by the numeric id chunks stored them as before 1.18, under the names they were
+/// given in 1.18. Merged biomes take the name of the one they were merged into. Ids 129
+/// to 167 are variants of the biome 128 ids below, later biomes follow from 168.
+const LEGACY_BIOMES: &[(i32, &str)] = &[
+    (0, "ocean"),
+    (1, "plains"),
+    (2, "desert"),
+    (3, "windswept_hills"),
+    (4, "forest"),
+    (5, "taiga"),
+    (6, "swamp"),
+    (7, "river"),
+    (8, "nether_wastes"),
+    (9, "the_end"),
+    (10, "frozen_ocean"),
+    (11, "frozen_river"),
+    (12, "snowy_plains"),
+    (13, "snowy_plains"),
+    (14, "mushroom_fields"),
+    (15, "mushroom_fields"),
+    (16, "beach"),
+    (17, "desert"),
+    (18, "forest"),
+    (19, "taiga"),
+    (20, "windswept_hills"),
+    (21, "jungle"),
+    (22, "jungle"),
+    (23, "sparse_jungle"),
+    (24, "deep_ocean"),
+    (25, "stony_shore"),
+    (26, "snowy_beach"),
+    (27, "birch_forest"),
+    (28, "birch_forest"),
+    (29, "dark_forest"),
+    (30, "snowy_taiga"),
+    (31, "snowy_taiga"),
+    (32, "old_growth_pine_taiga"),
+    (33, "old_growth_pine_taiga"),
+    (34, "windswept_forest"),
+    (35, "savanna"),
+    (36, "savanna_plateau"),
+    (37, "badlands"),
+    (38, "wooded_badlands"),
+    (39, "badlands"),
+    (40, "small_end_islands"),
+    (41, "end_midlands"),
+    (42, "end_highlands"),
+    (43, "end_barrens"),
+    (44, "warm_ocean"),
+    (45, "lukewarm_ocean"),
+    (46, "cold_ocean"),
+    (47, "deep_lukewarm_ocean"),
+    (48, "deep_lukewarm_ocean"),
+    (49, "deep_cold_ocean"),
+    (50, "deep_frozen_ocean"),
+    (127, "the_void"),
+    (129, "sunflower_plains"),
+    (130, "desert"),
+    (131, "windswept_gravelly_hills"),
+    (132, "flower_forest"),
+    (133, "taiga"),
+    (134, "swamp"),
+    (140, "ice_spikes"),
+    (149, "jungle"),
+    (151, "sparse_jungle"),
+    (155, "old_growth_birch_forest"),
+    (156, "old_growth_birch_forest"),
+    (157, "dark_forest"),
+    (158, "snowy_taiga"),
+    (160, "old_growth_spruce_taiga"),
+    (161, "old_growth_spruce_taiga"),
+    (162, "windswept_gravelly_hills"),
+    (163, "windswept_savanna"),
+    (164, "windswept_savanna"),
+    (165, "eroded_badlands"),
+    (166, "wooded_badlands"),
+    (167, "badlands"),
+    (168, "bamboo_jungle"),
+    (169, "bamboo_jungle"),
+    (170, "soul_sand_valley"),
+    (171, "crimson_forest"),
+    (172, "warped_forest"),
+    (173, "basalt_deltas"),
+    (174, "dripstone_caves"),
+    (175, "lush_caves"),
+];
+
+/// Our stand-ins for vanilla block and biome names. Names may hold `*` wildcards and
+/// the first match wins. Properties our block shares with the vanilla one are carried over.
+#[derive(Debug, Clone)]
+pub struct VanillaMapping {
+    registry: BlockRegistry,
+    blocks: Vec<(String, BlockId)>,
+    biomes: Vec<(String, Biome)>,
+    /// Drawn for block states nothing stands in for.
+    placeholder: BlockId,
+    /// Vanilla name each of our blocks is written as, by its default state.
+    vanilla_names: HashMap<BlockId, String>,
+}
+
+#[derive(Deserialize)]
+struct MappingDef {
+    placeholder: String,
+    blocks: Vec<(String, String)>,
+    biomes: Vec<(String, String)>,
+}
+
+impl VanillaMapping {
+    pub fn from_file(path: &str, registry: &BlockRegistry) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_str(&contents, registry)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(contents: &str, registry: &BlockRegistry) -> std::io::Result<Self> {
+        let def: MappingDef = serde_json::from_str(contents)?;
+
+        let block = |name: &str| {
+            registry.get_id(name).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("vanilla blocks are mapped onto unknown block `{name}`"),
+                )
+            })
+        };
+        let biome = |name: &str| {
+            Biome::ALL
+                .into_iter()
+                .find(|biome| biome.settings().name == name)
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("vanilla biomes are mapped onto unknown biome `{name}`"),
+                    )
+                })
+        };
+
+        let blocks: Vec<(String, BlockId)> = def
+            .blocks
+            .into_iter()
+            .map(|(pattern, name)| Ok((pattern, block(&name)?)))
+            .collect::<std::io::Result<_>>()?;
+
+        // the first plain name standing in for a block, or the block's own name
+        let vanilla_names = registry
+            .blocks()
+            .iter()
+            .map(|block| {
+                let name = blocks
+                    .iter()
+                    .find(|(pattern, id)| *id == block.id && !pattern.contains('*'))
+                    .map_or_else(
+                        || format!("minecraft:{}", block.name),
+                        |(pattern, _)| pattern.clone(),
+                    );
+                (block.id, name)
+            })
+            .collect();
+
+        Ok(Self {
+            registry: registry.clone(),
+            blocks,
+            vanilla_names,
+            biomes: def
+                .biomes
+                .into_iter()
+                .map(|(pattern, name)| Ok((pattern, biome(&name)?)))
+                .collect::<std::io::Result<_>>()?,
+            placeholder: block(&def.placeholder)?,
+        })
+    }
+
+    /// Default state of the block standing in for the vanilla one.
+    pub fn get_block(&self, name: &str) -> Option<BlockId> {
+        self.blocks
+            .iter()
+            .find(|(pattern, _)| matches(pattern, name))
+            .map(|&(_, block)| block)
+    }
+
+    /// The state standing in for a vanilla `name[key=value,...]` state.
+    pub fn get_state(&self, state: &str) -> Option<BlockId> {
+        let (name, properties) = match state.split_once('[') {
+            Some((name, properties)) => (name, properties.trim_end_matches(']')),
+            None => (state, ""),
+        };
+
+        let mut id = self.get_block(name)?;
+        let Some(block) = self.registry.get(id) else {
+            return Some(id);
+        };
+
+        for (key, value) in properties
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+        {
+            let property = block
+                .get_properties()
+                .find(|&property| vanilla_property(name, property) == key);
+
+            if let Some(value) = property.and_then(|property| {
+                let value = property.parse_value(value)?;
+                Some((property, value))
+            }) {
+                id = block.with_value(id, value.0, value.1);
+            }
+        }
+
+        Some(id)
+    }
+
+    pub fn get_biome(&self, name: &str) -> Option<Biome> {
+        self.biomes
+            .iter()
+            .find(|(pattern, _)| matches(pattern, name))
+            .map(|&(_, biome)| biome)
+    }
+
+    pub fn get_placeholder(&self) -> BlockId {
+        self.placeholder
+    }
+
+    /// Name the block is written as in vanilla formats.
+    pub fn get_vanilla_name(&self, state: BlockId) -> &str {
+        self.registry
+            .get(state)
+            .and_then(|block| self.vanilla_names.get(&block.id))
+            .map_or("minecraft:air", String::as_str)
+    }
+
+    /// Properties the state is written with in vanilla formats, as `(key, value)`.
+    pub fn get_vanilla_properties(&self, state: BlockId) -> Vec<(&'static str, String)> {
+        let Some(block) = self.registry.get(state) else {
+            return Vec::new();
+        };
+        let name = self.get_vanilla_name(state);
+
+        block
+            .get_properties()
+            .filter_map(|property| {
+                let value = block.get_value(state, property)?;
+                Some((vanilla_property(name, property), property.value_name(value)))
+            })
+            .collect()
+    }
+
+    /// `name[key=value,...]` the state is written as in vanilla formats.
+    pub fn get_vanilla_state(&self, state: BlockId) -> String {
+        let name = self.get_vanilla_name(state);
+        let properties = self.get_vanilla_properties(state);
+        if properties.is_empty() {
+            return name.to_owned();
+        }
+
+        let properties: Vec<_> = properties
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        format!("{name}[{}]", properties.join(","))
+    }
+}
+
+/// Key of the property in states of the vanilla block, slabs call the half they take their `type`.
+fn vanilla_property(name: &str, property: Property) -> &'static str {
+    match property {
+        Property::Half if name.ends_with("_slab") => "type",
+        _ => property.name(),
+    }
+}
+
+/// Whether the name fits the pattern, where `*` stands for any run of characters.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // no wildcard at all
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+/// A chunk read from a vanilla region file.
+#[derive(Debug, Clone)]
+pub struct ImportedChunk {
+    pub chunk: Chunk,
+    /// Biome at the top block of each column, indexed by `z * 16 + x`.
+    pub biomes: Vec<Option<Biome>>,
+    /// Block states nothing stands in for, like `minecraft:water_cauldron[level=1]`,
+    /// drawn as the placeholder block.
+    pub unknown: Vec<String>,
+}
+
+/// Reads the blocks and biomes of a chunk as stored by the game since 1.13, both with
+/// the older `Level` compound and the flat layout of 1.18 and later.
+pub fn read_anvil_chunk(
+    root: &Compound,
+    mapping: &VanillaMapping,
+) -> std::io::Result<ImportedChunk> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_owned());
+
+    let data_version = root.get("DataVersion").and_then(Tag::as_i64).unwrap_or(0);
+    let level = root.get("Level").and_then(Tag::as_compound).unwrap_or(root);
+
+    let get_i32 = |name| {
+        level
+            .get(name)
+            .and_then(Tag::as_i64)
+            .map(|v| v as i32)
+            .ok_or_else(|| invalid(&format!("chunk without `{name}`")))
+    };
+    let pos = ChunkPos::new(get_i32("xPos")?, get_i32("zPos")?);
+
+    let mut chunk = Chunk::new(pos);
+    let mut unknown = Vec::new();
+    let mut biome_cells = HashMap::new();
+
+    let sections = level
+        .get("sections")
+        .or_else(|| level.get("Sections"))
+        .and_then(Tag::as_list)
+        .unwrap_or_default();
+
+    for section in sections.iter().filter_map(Tag::as_compound) {
+        let section_y = section
+            .get("Y")
+            .and_then(Tag::as_i64)
+            .ok_or_else(|| invalid("section without `Y`"))? as i32;
+
+        let (palette, data) = match section.get("block_states").and_then(Tag::as_compound) {
+            Some(states) => (states.get("palette"), states.get("data")),
+            None => (section.get("Palette"), section.get("BlockStates")),
+        };
+
+        // sections only holding light have no blocks
+        let Some(palette) = palette.and_then(Tag::as_list) else {
+            continue;
+        };
+
+        let mut blocks = Vec::with_capacity(palette.len());
+        for state in palette {
+            let state = state
+                .as_compound()
+                .ok_or_else(|| invalid("block state that isn't a compound"))?;
+
+            let name = state
+                .get("Name")
+                .and_then(Tag::as_str)
+                .ok_or_else(|| invalid("block state without a name"))?;
+
+            let state = describe_state(name, state);
+            blocks.push(mapping.get_state(&state).unwrap_or_else(|| {
+                if !unknown.contains(&state) {
+                    unknown.push(state);
+                }
+                mapping.get_placeholder()
+            }));
+        }
+
+        let bits = index_bits(blocks.len()).max(4);
+        let straddle = data_version < UNSTRADDLED_DATA_VERSION;
+        let indices = unpack(data, bits, SECTION_VOLUME, straddle)?;
+
+        let bottom = section_y * CHUNK_SIZE;
+        for (i, &index) in indices.iter().enumerate() {
+            let block = *blocks
+                .get(index as usize)
+                .ok_or_else(|| invalid("block index outside the palette"))?;
+
+            let (x, y, z) = (i % 16, i / 256, i / 16 % 16);
+            chunk.set_block(x as i32, bottom + y as i32, z as i32, block);
+        }
+
+        if let Some(biomes) = section.get("biomes").and_then(Tag::as_compound) {
+            let palette: Vec<_> = biomes
+                .get("palette")
+                .and_then(Tag::as_list)
+                .unwrap_or_default()
+                .iter()
+                .map(|name| name.as_str().and_then(|name| mapping.get_biome(name)))
+                .collect();
+
+            let indices = unpack(
+                biomes.get("data"),
+                index_bits(palette.len()),
+                BIOME_CELLS,
+                false,
+            )?;
+            let cells: Vec<_> = indices
+                .iter()
+                .map(|&i| palette.get(i as usize).copied().flatten())
+                .collect();
+            biome_cells.insert(section_y, cells);
+        }
+    }
+
+    // before 1.18 biomes were numeric ids next to the sections
+    let legacy_biomes = level.get("Biomes").and_then(Tag::as_int_array);
+    let legacy_biome = |id: i32| {
+        let &(_, name) = LEGACY_BIOMES.iter().find(|&&(legacy, _)| legacy == id)?;
+        mapping.get_biome(&format!("minecraft:{name}"))
+    };
+
+    let mut biomes = vec![None; (CHUNK_SIZE * CHUNK_SIZE) as usize];
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let Some(top) = (MIN_Y..MAX_Y)
+                .rev()
+                .find(|&y| !chunk.get_block(x, y, z).is_air())
+            else {
+                continue;
+            };
+
+            let column = (z * CHUNK_SIZE + x) as usize;
+            biomes[column] = match legacy_biomes {
+                // 1.15 to 1.17, 4×4×4 cells from y 0 to 256
+                Some(ids) if ids.len() == 1024 => {
+                    let cell = (top.clamp(0, 255) / 4 * 4 + z / 4) * 4 + x / 4;
+                    legacy_biome(ids[cell as usize])
+                }
+                // before 1.15, one per column
+                Some(ids) if ids.len() == 256 => legacy_biome(ids[column]),
+                _ => {
+                    let cell = ((top.rem_euclid(16) / 4 * 4 + z / 4) * 4 + x / 4) as usize;
+                    biome_cells
+                        .get(&top.div_euclid(16))
+                        .and_then(|cells| cells[cell])
+                }
+            };
+        }
+    }
+
+    Ok(ImportedChunk {
+        chunk,
+        biomes,
+        unknown,
+    })
+}
+
+/// `name[key=value,...]`, the way the game writes block states.
+pub(crate) fn describe_state(name: &str, state: &Compound) -> String {
+    let Some(properties) = state.get("Properties").and_then(Tag::as_compound) else {
+        return name.to_owned();
+    };
+
+    let properties: Vec<_> = properties
+        .iter()
+        .map(|(key, value)| format!("{key}={}", value.as_str().unwrap_or("?")))
+        .collect();
+    format!("{name}[{}]", properties.join(","))
+}
+
+/// Bits taken by each index into a palette of the given length.
+pub(crate) fn index_bits(length: usize) -> u32 {
+    usize::BITS - length.saturating_sub(1).leading_zeros()
+}
+
+/// Unpacks `count` indices of `bits` bits each from the longs of `data`, which is left out
+/// when all indices are 0. Before 1.16, indices could straddle two longs.
+pub(crate) fn unpack(
+    data: Option<&Tag>,
+    bits: u32,
+    count: usize,
+    straddle: bool,
+) -> std::io::Result<Vec<u16>> {
+    let data = match data {
+        Some(data) => data
+            .as_long_array()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "packed indices aren't longs"))?,
+        None => return Ok(vec![0; count]),
+    };
+
+    if bits == 0 {
+        return Ok(vec![0; count]);
+    }
+    if bits > 16 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("indices of {bits} bits are too large"),
+        ));
+    }
+
+    let bits = bits as usize;
+    let mask = (1u64 << bits) - 1;
+    let per_long = 64 / bits;
+
+    let needed = if straddle {
+        (count * bits).div_ceil(64)
+    } else {
+        count.div_ceil(per_long)
+    };
+    if data.len() < needed {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} longs can't hold {count} indices of {bits} bits",
+                data.len()
+            ),
+        ));
+    }
+
+    Ok((0..count)
+        .map(|i| {
+            let value = if straddle {
+                let bit = i * bits;
+                let (long, shift) = (bit / 64, bit % 64);
+
+                let low = data[long] as u64 >> shift;
+                if shift + bits > 64 {
+                    low | (data[long + 1] as u64) << (64 - shift)
+                } else {
+                    low
+                }
+            } else {
+                data[i / per_long] as u64 >> (i % per_long * bits)
+            };
+
+            (value & mask) as u16
+        })
+        .collect())
+}
+
+/// Packs indices of `bits` bits each into longs, the way `unpack` reads them.
+pub(crate) fn pack(indices: &[u16], bits: u32, straddle: bool) -> Vec<i64> {
+    let bits = bits.max(1) as usize;
+
+    if straddle {
+        let mut data = vec![0u64; (indices.len() * bits).div_ceil(64)];
+        for (i, &index) in indices.iter().enumerate() {
+            let bit = i * bits;
+            let (long, shift) = (bit / 64, bit % 64);
+
+            data[long] |= (index as u64) << shift;
+            if shift + bits > 64 {
+                data[long + 1] |= (index as u64) >> (64 - shift);
+            }
+        }
+        return data.into_iter().map(|long| long as i64).collect();
+    }
+
+    indices
+        .chunks(64 / bits)
+        .map(|chunk| {
+            let long = chunk.iter().enumerate().fold(0u64, |long, (i, &index)| {
+                long | (index as u64) << (i * bits)
+            });
+            long as i64
+        })
+        .collect()
+}
+
+/// The overworld of a vanilla save folder, read chunk by chunk as if it was generated.
+/// Chunks missing from the region files come back empty.
+#[derive(Debug)]
+pub struct AnvilWorld {
+    dir: PathBuf,
+    mapping: VanillaMapping,
+    spawn: IVec3,
+    /// `None` for regions without a file.
+    regions: Mutex<HashMap<(i32, i32), Option<RegionFile>>>,
+    biomes: Mutex<HashMap<ChunkPos, Vec<Option<Biome>>>>,
+    /// Block states without a stand-in, with the number of chunks they showed up in.
+    unknown: Mutex<BTreeMap<String, usize>>,
+}
+
+impl AnvilWorld {
+    pub fn open(dir: &Path, registry: &BlockRegistry) -> std::io::Result<Self> {
+        if !dir.join("region").is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} has no `region` folder", dir.display()),
+            ));
+        }
+
+        let mut world = Self {
+            dir: dir.to_path_buf(),
+            mapping: VanillaMapping::from_file(VANILLA_MAPPING_PATH, registry)?,
+            spawn: IVec3::ZERO,
+            regions: Mutex::new(HashMap::new()),
+            biomes: Mutex::new(HashMap::new()),
+            unknown: Mutex::new(BTreeMap::new()),
+        };
+
+        // the spawn column from `level.dat`, raised to the top of the ground there
+        let (x, z) = match nbt::read_file(&dir.join("level.dat")) {
+            Ok((_, level)) => {
+                let data = level.as_compound().and_then(|level| level.get("Data"));
+                let get = |name| {
+                    data.and_then(Tag::as_compound)
+                        .and_then(|data| data.get(name))
+                        .and_then(Tag::as_i64)
+                        .unwrap_or(0) as i32
+                };
+                (get("SpawnX"), get("SpawnZ"))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => (0, 0),
+            Err(error) => return Err(error),
+        };
+
+        let pos = ChunkPos::from_block(ivec3(x, 0, z));
+        let (lx, lz) = (x.rem_euclid(CHUNK_SIZE), z.rem_euclid(CHUNK_SIZE));
+        let top = world.load_chunk(pos)?.and_then(|imported| {
+            (MIN_Y..MAX_Y)
+                .rev()
+                .find(|&y| !imported.chunk.get_block(lx, y, lz).is_air())
+        });
+        world.spawn = ivec3(x, top.map_or(64, |y| y + 1), z);
+
+        Ok(world)
+    }
+
+    /// `None` if the chunk was never generated by the game.
+    pub fn load_chunk(&self, pos: ChunkPos) -> std::io::Result<Option<ImportedChunk>> {
+        let (rx, rz) = super::region_of(pos);
+
+        let data = {
+            let mut regions = self.regions.lock().unwrap();
+            let region = match regions.entry((rx, rz)) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    let path = self.dir.join("region").join(format!("r.{rx}.{rz}.mca"));
+                    entry.insert(RegionFile::open_read_only(&path)?)
+                }
+            };
+
+            match region {
+                Some(region) => region.read_chunk(
+                    pos.x.rem_euclid(super::REGION_SIZE),
+                    pos.z.rem_euclid(super::REGION_SIZE),
+                )?,
+                None => None,
+            }
+        };
+
+        let Some(data) = data else {
+            return Ok(None);
+        };
+
+        let (_, root) = nbt::read_uncompressed(&data)?;
+        let root = root
+            .as_compound()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "chunk root isn't a compound"))?;
+
+        let imported = read_anvil_chunk(root, &self.mapping)?;
+        if imported.chunk.get_position() != pos {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "chunk {}, {} is stored in the slot of another",
+                    pos.x, pos.z
+                ),
+            ));
+        }
+
+        Ok(Some(imported))
+    }
+
+    /// Block states met so far that are drawn as the placeholder, with the number of
+    /// chunks each showed up in.
+    pub fn get_unknown_states(&self) -> Vec<(String, usize)> {
+        let unknown = self.unknown.lock().unwrap();
+        unknown
+            .iter()
+            .map(|(state, &count)| (state.clone(), count))
+            .collect()
+    }
+}
+
+impl Generator for AnvilWorld {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let imported = match self.load_chunk(pos) {
+            Ok(Some(imported)) => imported,
+            Ok(None) => return Chunk::new(pos),
+            Err(error) => {
+                eprintln!("failed to import chunk {}, {}: {error}", pos.x, pos.z);
+                return Chunk::new(pos);
+            }
+        };
+
+        let mut unknown = self.unknown.lock().unwrap();
+        for state in imported.unknown {
+            if !unknown.contains_key(&state) {
+                eprintln!("no block stands in for `{state}`, it's drawn as the placeholder");
+            }
+            *unknown.entry(state).or_default() += 1;
+        }
+        drop(unknown);
+
+        self.biomes.lock().unwrap().insert(pos, imported.biomes);
+        imported.chunk
+    }
+
+    fn get_spawn(&self) -> IVec3 {
+        self.spawn
+    }
+
+    /// Only known for chunks that were already loaded.
+    fn get_biome(&self, x: i32, z: i32) -> Option<Biome> {
+        let pos = ChunkPos::from_block(ivec3(x, 0, z));
+        let index = z.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE);
+
+        self.biomes.lock().unwrap().get(&pos)?[index as usize]
+    }
+
+    fn unload(&self, pos: ChunkPos) {
+        self.biomes.lock().unwrap().remove(&pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::{Compression, REGION_SIZE};
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    fn state(name: &str, properties: &[(&str, &str)]) -> Tag {
+        let mut state = Compound::new();
+        state.insert("Name", name);
+        if !properties.is_empty() {
+            state.insert(
+                "Properties",
+                properties.iter().copied().collect::<Compound>(),
+            );
+        }
+        Tag::Compound(state)
+    }
+
+    /// A 1.18 style chunk: stone below y 0, a layer of grass on top, a cauldron in one corner,
+    /// plains biome except for a desert cell.
+    fn vanilla_chunk(x: i32, z: i32) -> Compound {
+        let palette = vec![
+            state("minecraft:air", &[]),
+            state("minecraft:stone", &[]),
+            state("minecraft:grass_block", &[("snowy", "false")]),
+            state("minecraft:water_cauldron", &[("level", "1")]),
+        ];
+
+        let mut lower = vec![1u16; SECTION_VOLUME];
+        lower[0] = 3;
+        let mut upper = vec![0u16; SECTION_VOLUME];
+        upper[..256].fill(2);
+
+        let mut cells = vec![0u16; BIOME_CELLS];
+        cells[0] = 1;
+
+        let section = |y: i8, indices: &[u16]| {
+            let mut states = Compound::new();
+            states.insert("palette", palette.clone());
+            states.insert("data", pack(indices, 4, false));
+
+            let mut biomes = Compound::new();
+            biomes.insert(
+                "palette",
+                vec![Tag::from("minecraft:plains"), "minecraft:desert".into()],
+            );
+            biomes.insert("data", pack(&cells, 1, false));
+
+            let mut section = Compound::new();
+            section.insert("Y", y);
+            section.insert("block_states", states);
+            section.insert("biomes", biomes);
+            Tag::Compound(section)
+        };
+
+        let mut root = Compound::new();
+        root.insert("DataVersion", 3465);
+        root.insert("xPos", x);
+        root.insert("zPos", z);
+        root.insert("Status", "minecraft:full");
+        root.insert("sections", vec![section(-1, &lower), section(0, &upper)]);
+        root
+    }
+
+    #[test]
+    fn vanilla_chunks_are_mapped_onto_our_blocks() {
+        let registry = registry();
+        let id = |name| registry.get_id(name).unwrap();
+        let mapping = VanillaMapping::from_file(VANILLA_MAPPING_PATH, &registry).unwrap();
+
+        let imported = read_anvil_chunk(&vanilla_chunk(-2, 5), &mapping).unwrap();
+        let chunk = &imported.chunk;
+
+        assert_eq!(chunk.get_position(), ChunkPos::new(-2, 5));
+        assert_eq!(chunk.get_block(0, -16, 0), id("unknown"));
+        assert_eq!(chunk.get_block(1, -16, 0), id("stone"));
+        assert_eq!(chunk.get_block(15, -1, 15), id("stone"));
+        assert_eq!(chunk.get_block(7, 0, 9), id("grass"));
+        assert!(chunk.get_block(7, 1, 9).is_air());
+        assert!(chunk.get_block(7, -17, 9).is_air());
+
+        assert_eq!(imported.unknown, ["minecraft:water_cauldron[level=1]"]);
+        assert_eq!(imported.biomes[0], Some(Biome::Desert));
+        assert_eq!(imported.biomes[4], Some(Biome::Plains));
+
+        assert_eq!(
+            mapping.get_vanilla_name(id("grass")),
+            "minecraft:grass_block"
+        );
+        assert_eq!(
+            mapping.get_vanilla_name(id("leaves")),
+            "minecraft:oak_leaves"
+        );
+
+        // shared properties carry over both ways, slabs call their half `type`
+        let slab = registry.parse_state("stone_slab[half=top]").unwrap();
+        assert_eq!(
+            mapping.get_state("minecraft:andesite_slab[type=top,waterlogged=false]"),
+            Some(slab)
+        );
+        assert_eq!(
+            mapping.get_vanilla_state(slab),
+            "minecraft:stone_slab[type=top,waterlogged=false]"
+        );
+        assert_eq!(
+            mapping.get_state("minecraft:spruce_log[axis=z]"),
+            registry.parse_state("log[axis=z]")
+        );
+    }
+
+    #[test]
+    fn older_chunks_keep_their_biome_ids() {
+        let registry = registry();
+        let mapping = VanillaMapping::from_file(VANILLA_MAPPING_PATH, &registry).unwrap();
+
+        // a 1.16 chunk with a layer of stone at y 0
+        let mut indices = vec![0u16; SECTION_VOLUME];
+        indices[..256].fill(1);
+        let mut section = Compound::new();
+        section.insert("Y", 0i8);
+        section.insert(
+            "Palette",
+            vec![state("minecraft:air", &[]), state("minecraft:stone", &[])],
+        );
+        section.insert("BlockStates", pack(&indices, 4, false));
+
+        let mut level = Compound::new();
+        level.insert("xPos", 0);
+        level.insert("zPos", 0);
+        level.insert("Sections", vec![Tag::Compound(section)]);
+
+        // plains, with a desert cell at the bottom corner and forest right above it
+        let mut cells = vec![1; 1024];
+        cells[0] = 2;
+        cells[16] = 4;
+        level.insert("Biomes", cells);
+
+        let mut root = Compound::new();
+        root.insert("DataVersion", 2586);
+        root.insert("Level", level.clone());
+
+        let imported = read_anvil_chunk(&root, &mapping).unwrap();
+        assert_eq!(imported.biomes[0], Some(Biome::Desert));
+        assert_eq!(imported.biomes[4], Some(Biome::Plains));
+
+        // before 1.15 there's one per column, variants keep their biome
+        let mut columns = vec![4; 256];
+        columns[1] = 2;
+        columns[2] = 140;
+        level.insert("Biomes", columns);
+        root.insert("Level", level);
+
+        let imported = read_anvil_chunk(&root, &mapping).unwrap();
+        assert_eq!(imported.biomes[0], Some(Biome::Forest));
+        assert_eq!(imported.biomes[1], Some(Biome::Desert));
+        assert_eq!(imported.biomes[2], Some(Biome::Snow));
+    }
+
+    #[test]
+    fn indices_unpack_with_and_without_straddling() {
+        let data = Tag::LongArray(vec![0x321]);
+        assert_eq!(unpack(Some(&data), 4, 3, false).unwrap(), [1, 2, 3]);
+
+        // the 13th 5 bit index starts at bit 60 and ends in the second long
+        let data = Tag::LongArray(vec![3 << 60, 1]);
+        let indices = unpack(Some(&data), 5, 13, true).unwrap();
+        assert_eq!(indices[12], 19);
+        assert!(indices[..12].iter().all(|&i| i == 0));
+
+        let indices: Vec<u16> = (0..4096).map(|i| (i * 7 % 37) as u16).collect();
+        for straddle in [false, true] {
+            let data = Tag::LongArray(pack(&indices, 6, straddle));
+            assert_eq!(unpack(Some(&data), 6, 4096, straddle).unwrap(), indices);
+        }
+        assert!(unpack(Some(&Tag::LongArray(vec![0; 3])), 6, 4096, false).is_err());
+        assert_eq!(unpack(None, 4, 5, false).unwrap(), [0; 5]);
+    }
+
+    #[test]
+    fn wildcards_match_runs_of_characters() {
+        assert!(matches("minecraft:*_log", "minecraft:dark_oak_log"));
+        assert!(matches(
+            "minecraft:*savanna*",
+            "minecraft:windswept_savanna_plateau"
+        ));
+        assert!(matches("minecraft:*", "minecraft:anything"));
+        assert!(matches("minecraft:stone", "minecraft:stone"));
+        assert!(!matches("minecraft:stone", "minecraft:stone_bricks"));
+        assert!(!matches("minecraft:*_log", "minecraft:log_cabin"));
+    }
+
+    #[test]
+    fn save_folders_open_in_the_viewer() {
+        let registry = registry();
+        let dir = std::env::temp_dir().join(format!("anvil-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("region")).unwrap();
+
+        let mut region = RegionFile::open(&dir.join("region/r.-1.0.mca")).unwrap();
+        let data =
+            nbt::to_bytes("", &Tag::Compound(vanilla_chunk(-1, 3)), Compression::None).unwrap();
+        region
+            .write_chunk(REGION_SIZE - 1, 3, &data, Compression::Zlib)
+            .unwrap();
+        region
+            .write_chunk(REGION_SIZE - 2, 3, b"not nbt", Compression::Zlib)
+            .unwrap();
+        drop(region);
+
+        let level: Compound = [(
+            "Data",
+            [("SpawnX", -5), ("SpawnY", 80), ("SpawnZ", 60)]
+                .into_iter()
+                .collect::<Compound>(),
+        )]
+        .into_iter()
+        .collect();
+        nbt::write_file(
+            &dir.join("level.dat"),
+            "",
+            &Tag::Compound(level),
+            Compression::Gzip,
+        )
+        .unwrap();
+
+        let world = AnvilWorld::open(&dir, &registry).unwrap();
+        assert_eq!(world.get_spawn(), ivec3(-5, 1, 60));
+
+        assert_eq!(
+            world.generate(ChunkPos::new(-1, 3)).blocks().count(),
+            4096 + 256
+        );
+        assert_eq!(
+            world.get_unknown_states(),
+            [("minecraft:water_cauldron[level=1]".into(), 1)]
+        );
+        assert_eq!(world.get_biome(-5, 60), Some(Biome::Plains));
+        world.unload(ChunkPos::new(-1, 3));
+        assert_eq!(world.get_biome(-5, 60), None);
+
+        // broken or missing chunks come back empty
+        assert_eq!(world.generate(ChunkPos::new(-2, 3)).blocks().count(), 0);
+        assert_eq!(world.generate(ChunkPos::new(40, 3)).blocks().count(), 0);
+        assert!(world.load_chunk(ChunkPos::new(-2, 3)).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
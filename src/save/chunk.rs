@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use crate::block::BlockRegistry;
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MIN_Y, SECTION_COUNT};
+
+/// Bumped whenever the layout below changes.
+const VERSION: u8 = 1;
+
+/// Writes the chunk as:
+///
+/// - the format version
+/// - the chunk x and z
+/// - the names of the block states the chunk holds, like `log[axis=x]`, so ids can change
+///   between runs
+/// - a mask of the sections that aren't empty
+/// - for each of those sections, the palette index of every block in YZX order
+///
+/// Numbers are big-endian. Names take a byte for their length, longer ones than fit
+/// are an error.
+pub fn encode_chunk(chunk: &Chunk, registry: &BlockRegistry) -> std::io::Result<Vec<u8>> {
+    let mut out = vec![VERSION];
+    let pos = chunk.get_position();
+    out.extend_from_slice(&pos.x.to_be_bytes());
+    out.extend_from_slice(&pos.z.to_be_bytes());
+
+    let mut palette: Vec<BlockId> = vec![BlockId::AIR];
+    let mut indices: HashMap<BlockId, u16> = HashMap::from([(BlockId::AIR, 0)]);
+    let mut mask = 0u32;
+    let mut body = Vec::new();
+
+    for i in 0..SECTION_COUNT {
+        let Some(section) = chunk.get_section(i) else {
+            continue;
+        };
+        mask |= 1 << i;
+
+        for (y, z, x) in section_order() {
+            let block = section.get(x, y, z);
+            let index = *indices.entry(block).or_insert_with(|| {
+                palette.push(block);
+                (palette.len() - 1) as u16
+            });
+            body.extend_from_slice(&index.to_be_bytes());
+        }
+    }
+
+    out.extend_from_slice(&(palette.len() as u16).to_be_bytes());
+    for block in palette {
+        let name = registry
+            .describe_state(block)
+            .unwrap_or_else(|| "air".into());
+        let len = u8::try_from(name.len()).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("block name `{name}` is too long to save"),
+            )
+        })?;
+        out.push(len);
+        out.extend_from_slice(name.as_bytes());
+    }
+
+    out.extend_from_slice(&mask.to_be_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Reads a chunk written by [`encode_chunk`]. Blocks missing from the registry are an error,
+/// rather than being silently dropped.
+pub fn decode_chunk(data: &[u8], registry: &BlockRegistry) -> std::io::Result<Chunk> {
+    let mut reader = Reader { data };
+
+    let version = reader.read::<1>()?[0];
+    if version != VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported chunk format version {version}"),
+        ));
+    }
+
+    let x = i32::from_be_bytes(reader.read()?);
+    let z = i32::from_be_bytes(reader.read()?);
+    let mut chunk = Chunk::new(ChunkPos::new(x, z));
+
+    let palette_len = u16::from_be_bytes(reader.read()?);
+    let mut palette = Vec::with_capacity(palette_len as usize);
+    for _ in 0..palette_len {
+        let len = reader.read::<1>()?[0] as usize;
+        let name = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "block name isn't UTF-8"))?;
+
+        let block = registry.parse_state(name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("chunk {x}, {z} holds unknown block `{name}`"),
+            )
+        })?;
+        palette.push(block);
+    }
+
+    let mask = u32::from_be_bytes(reader.read()?);
+    for i in (0..SECTION_COUNT).filter(|i| mask & (1 << i) != 0) {
+        let bottom = MIN_Y + i as i32 * CHUNK_SIZE;
+
+        for (y, z, x) in section_order() {
+            let index = u16::from_be_bytes(reader.read()?);
+            let block = *palette.get(index as usize).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, "block index outside the palette")
+            })?;
+            chunk.set_block(x as i32, bottom + y as i32, z as i32, block);
+        }
+    }
+
+    if !reader.data.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "trailing data after the chunk",
+        ));
+    }
+
+    Ok(chunk)
+}
+
+/// Local `(y, z, x)` positions of a section, in storage order.
+fn section_order() -> impl Iterator<Item = (usize, usize, usize)> {
+    const S: usize = CHUNK_SIZE as usize;
+    (0..S * S * S).map(|i| (i / (S * S), i / S % S, i % S))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "chunk data ends early",
+            ));
+        }
+
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::{TerrainGenerator, TerrainSettings};
+
+    #[test]
+    fn generated_chunks_round_trip() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let generator = TerrainGenerator::new(7, &registry, TerrainSettings::default());
+
+        let mut chunk = generator.generate(ChunkPos::new(-3, 5));
+        chunk.set_block(4, 300, 9, registry.get_id("cobblestone").unwrap());
+        chunk.set_block(5, 300, 9, registry.parse_state("log[axis=z]").unwrap());
+
+        let decoded = decode_chunk(&encode_chunk(&chunk, &registry).unwrap(), &registry).unwrap();
+        assert_eq!(decoded.get_position(), chunk.get_position());
+        assert!(decoded.blocks().eq(chunk.blocks()));
+    }
+
+    #[test]
+    fn unknown_blocks_are_an_error() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        chunk.set_block(0, 0, 0, registry.get_id("stone").unwrap());
+
+        let smaller = BlockRegistry::from_str(r#"[{ "id": 0, "name": "air" }]"#).unwrap();
+        let error = decode_chunk(&encode_chunk(&chunk, &registry).unwrap(), &smaller).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let data = encode_chunk(&chunk, &registry).unwrap();
+        assert!(decode_chunk(&data[..data.len() - 1], &registry).is_err());
+    }
+
+    #[test]
+    fn names_too_long_to_save_are_an_error() {
+        let name = "a".repeat(256);
+        let registry = BlockRegistry::from_str(&format!(
+            r#"[{{ "id": 0, "name": "air" }}, {{ "id": 1, "name": "{name}", "textures": {{ "all": "stone" }} }}]"#
+        ))
+        .unwrap();
+
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        chunk.set_block(0, 0, 0, registry.get_id(&name).unwrap());
+
+        let error = encode_chunk(&chunk, &registry).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
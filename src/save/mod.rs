@@ -0,0 +1,194 @@
+//! Worlds on disk: a `level.json` with the world settings and player, and the chunks
+//! grouped into region files under `region/`. Vanilla save folders can be read too.
+
+mod anvil;
+mod chunk;
+mod region;
+
+pub use anvil::*;
+pub use chunk::*;
+pub use region::*;
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockRegistry;
+use crate::world::{Chunk, ChunkPos};
+
+/// Everything about a world that isn't blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelData {
+    pub seed: u64,
+    /// World generator preset, see `worldgen::from_preset`.
+    pub generator: String,
+    pub spawn: [i32; 3],
+    /// Game ticks since the world was created.
+    pub time: u64,
+    /// Whether time stands still, see `WorldClock::set_frozen`.
+    #[serde(default)]
+    pub time_frozen: bool,
+    pub player: PlayerData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub position: [f32; 3],
+    pub forward: [f32; 3],
+}
+
+/// A world directory. Chunks can be loaded and saved from any thread.
+#[derive(Debug)]
+pub struct WorldSave {
+    dir: PathBuf,
+    registry: Arc<BlockRegistry>,
+    regions: Mutex<HashMap<(i32, i32), RegionFile>>,
+}
+
+impl WorldSave {
+    const LEVEL_FILE: &str = "level.json";
+
+    /// Creates the directory if it doesn't exist yet.
+    pub fn open(dir: &Path, registry: Arc<BlockRegistry>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.join("region"))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            registry,
+            regions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// `None` for a new world.
+    pub fn load_level(&self) -> std::io::Result<Option<LevelData>> {
+        match std::fs::read_to_string(self.dir.join(Self::LEVEL_FILE)) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save_level(&self, level: &LevelData) -> std::io::Result<()> {
+        // written next to the old file first, so a crash never leaves half a file
+        let path = self.dir.join(Self::LEVEL_FILE);
+        let temp = path.with_extension("json.tmp");
+
+        std::fs::write(&temp, serde_json::to_string_pretty(level)?)?;
+        std::fs::rename(temp, path)
+    }
+
+    /// `None` if the chunk was never saved.
+    pub fn load_chunk(&self, pos: ChunkPos) -> std::io::Result<Option<Chunk>> {
+        let data = self.with_region(pos, |region, x, z| region.read_chunk(x, z))?;
+
+        let Some(data) = data else {
+            return Ok(None);
+        };
+
+        let chunk = decode_chunk(&data, &self.registry)?;
+        if chunk.get_position() != pos {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "chunk {}, {} is stored in the slot of another",
+                    pos.x, pos.z
+                ),
+            ));
+        }
+
+        Ok(Some(chunk))
+    }
+
+    pub fn save_chunk(&self, chunk: &Chunk) -> std::io::Result<()> {
+        let data = encode_chunk(chunk, &self.registry)?;
+
+        self.with_region(chunk.get_position(), |region, x, z| {
+            region.write_chunk(x, z, &data, Compression::Zlib)
+        })
+    }
+
+    /// Runs `f` on the open region file of the chunk, with the chunk position local to it.
+    fn with_region<T>(
+        &self,
+        pos: ChunkPos,
+        f: impl FnOnce(&mut RegionFile, i32, i32) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let (rx, rz) = region_of(pos);
+        let mut regions = self.regions.lock().unwrap();
+
+        let region = match regions.entry((rx, rz)) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let path = self.dir.join("region").join(format!("r.{rx}.{rz}.region"));
+                entry.insert(RegionFile::open(&path)?)
+            }
+        };
+
+        f(
+            region,
+            pos.x.rem_euclid(REGION_SIZE),
+            pos.z.rem_euclid(REGION_SIZE),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen;
+
+    #[test]
+    fn worlds_are_rebuilt_exactly() {
+        let dir = std::env::temp_dir().join(format!("save-{}", std::process::id()));
+        let registry = Arc::new(BlockRegistry::from_file("res/blocks.json").unwrap());
+        let generator = worldgen::from_preset("noise", 3, &registry).unwrap();
+
+        let level = LevelData {
+            seed: 3,
+            generator: "noise".into(),
+            spawn: generator.get_spawn().to_array(),
+            time: 12_345,
+            time_frozen: true,
+            player: PlayerData {
+                position: [1.5, 80.0, -20.25],
+                forward: [0.0, 0.0, -1.0],
+            },
+        };
+
+        let positions = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(-1, -1),
+            ChunkPos::new(31, -32),
+            ChunkPos::new(40, 7),
+        ];
+        let mut chunks: Vec<Chunk> = positions
+            .iter()
+            .map(|&pos| generator.generate(pos))
+            .collect();
+        chunks[1].set_block(3, 70, 3, registry.get_id("gold_ore").unwrap());
+        chunks[2].set_block(0, -64, 15, registry.get_id("air").unwrap());
+
+        {
+            let save = WorldSave::open(&dir, Arc::clone(&registry)).unwrap();
+            assert_eq!(save.load_level().unwrap(), None);
+
+            save.save_level(&level).unwrap();
+            for chunk in &chunks {
+                save.save_chunk(chunk).unwrap();
+            }
+        }
+
+        let save = WorldSave::open(&dir, Arc::clone(&registry)).unwrap();
+        assert_eq!(save.load_level().unwrap(), Some(level));
+        for chunk in &chunks {
+            let loaded = save.load_chunk(chunk.get_position()).unwrap().unwrap();
+            assert!(loaded.blocks().eq(chunk.blocks()));
+        }
+        assert!(save.load_chunk(ChunkPos::new(1, 0)).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
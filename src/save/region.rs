@@ -0,0 +1,408 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+use crate::world::ChunkPos;
+
+/// Chunks along each side of a region.
+pub const REGION_SIZE: i32 = 32;
+
+const CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE) as usize;
+const SECTOR_SIZE: usize = 4096;
+/// The offset table and the timestamp table take one sector each.
+const HEADER_SECTORS: usize = 2;
+/// Sector counts are stored in a single byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+
+/// How the data of a chunk is compressed, numbered like in the region header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip = 1,
+    Zlib = 2,
+    None = 3,
+}
+
+impl Compression {
+    fn from_id(id: u8) -> std::io::Result<Self> {
+        match id {
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Zlib),
+            3 => Ok(Compression::None),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown chunk compression {id}"),
+            )),
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let level = flate2::Compression::default();
+
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::None => Ok(data.to_vec()),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        match self {
+            Compression::Gzip => GzDecoder::new(data).read_to_end(&mut out)?,
+            Compression::Zlib => ZlibDecoder::new(data).read_to_end(&mut out)?,
+            Compression::None => return Ok(data.to_vec()),
+        };
+
+        Ok(out)
+    }
+}
+
+/// Region the chunk belongs to.
+pub fn region_of(pos: ChunkPos) -> (i32, i32) {
+    (pos.x.div_euclid(REGION_SIZE), pos.z.div_euclid(REGION_SIZE))
+}
+
+/// 32×32 chunks in one file, laid out like the Anvil format: a table of where every
+/// chunk starts and how many 4 KiB sectors it takes, a table of when each one was
+/// last written, and the separately compressed chunks after them.
+#[derive(Debug)]
+pub struct RegionFile {
+    file: File,
+    /// Sector index shifted up a byte, with the sector count in the low byte.
+    offsets: [u32; CHUNK_COUNT],
+    timestamps: [u32; CHUNK_COUNT],
+    /// Which sectors of the file hold data.
+    used: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens the region file, creating an empty one if there is none.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        Self::from_file(file, true)
+    }
+
+    /// Opens a region file written by someone else, like the game, without ever changing it.
+    /// `None` if there is no such file, and empty files, which the game leaves around, hold
+    /// no chunks.
+    pub fn open_read_only(path: &Path) -> std::io::Result<Option<Self>> {
+        match File::open(path) {
+            Ok(file) => Self::from_file(file, false).map(Some),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Empty files get a header written if `writable`, and are read as an empty region
+    /// either way.
+    fn from_file(mut file: File, writable: bool) -> std::io::Result<Self> {
+        let mut header = vec![0; HEADER_SECTORS * SECTOR_SIZE];
+        let length = file.metadata()?.len() as usize;
+
+        if length == 0 {
+            if writable {
+                file.write_all(&header)?;
+            }
+        } else if length < header.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "region file is shorter than its header",
+            ));
+        } else {
+            file.read_exact(&mut header)?;
+        }
+
+        let read = |i: usize| u32::from_be_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+        let offsets = std::array::from_fn(read);
+        let timestamps = std::array::from_fn(|i| read(CHUNK_COUNT + i));
+
+        let mut region = Self {
+            file,
+            offsets,
+            timestamps,
+            used: vec![true; HEADER_SECTORS],
+        };
+
+        let sectors = length.max(header.len()).div_ceil(SECTOR_SIZE);
+        region.used.resize(sectors, false);
+
+        for i in 0..CHUNK_COUNT {
+            let (start, count) = region.get_sectors(i);
+            if count == 0 {
+                continue;
+            }
+
+            if start < HEADER_SECTORS || start + count > sectors {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("chunk {i} of the region lies outside the file"),
+                ));
+            }
+
+            region.used[start..start + count].fill(true);
+        }
+
+        Ok(region)
+    }
+
+    /// `x` and `z` are local to the region, in `0..REGION_SIZE`.
+    pub fn has_chunk(&self, x: i32, z: i32) -> bool {
+        self.offsets[Self::index(x, z)] != 0
+    }
+
+    /// Positions of every stored chunk, local to the region.
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..CHUNK_COUNT)
+            .filter(|&i| self.offsets[i] != 0)
+            .map(|i| (i as i32 % REGION_SIZE, i as i32 / REGION_SIZE))
+    }
+
+    /// Seconds since the Unix epoch the chunk was last written at.
+    pub fn get_timestamp(&self, x: i32, z: i32) -> u32 {
+        self.timestamps[Self::index(x, z)]
+    }
+
+    /// Reads and decompresses the data of the chunk, if there is any.
+    pub fn read_chunk(&mut self, x: i32, z: i32) -> std::io::Result<Option<Vec<u8>>> {
+        let (start, count) = self.get_sectors(Self::index(x, z));
+        if count == 0 {
+            return Ok(None);
+        }
+
+        // the last chunk of files written by other tools may not fill its last sector
+        let mut data = Vec::with_capacity(count * SECTOR_SIZE);
+        self.file
+            .seek(SeekFrom::Start((start * SECTOR_SIZE) as u64))?;
+        (&mut self.file)
+            .take((count * SECTOR_SIZE) as u64)
+            .read_to_end(&mut data)?;
+
+        // the length counts the compression byte but not itself
+        let length = match data.get(..4) {
+            Some(bytes) => u32::from_be_bytes(bytes.try_into().unwrap()) as usize,
+            None => 0,
+        };
+        if length == 0 || length + 4 > data.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("chunk {x}, {z} of the region has a bad length"),
+            ));
+        }
+
+        let compression = data[4];
+        if compression & 0x80 != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("chunk {x}, {z} is stored in a separate file"),
+            ));
+        }
+
+        Compression::from_id(compression)?
+            .decompress(&data[5..4 + length])
+            .map(Some)
+    }
+
+    /// Compresses and stores the data of the chunk, reusing its sectors when it still fits.
+    pub fn write_chunk(
+        &mut self,
+        x: i32,
+        z: i32,
+        data: &[u8],
+        compression: Compression,
+    ) -> std::io::Result<()> {
+        let index = Self::index(x, z);
+
+        let compressed = compression.compress(data)?;
+        let mut payload = Vec::with_capacity(compressed.len() + 5);
+        payload.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        payload.push(compression as u8);
+        payload.extend_from_slice(&compressed);
+
+        let count = payload.len().div_ceil(SECTOR_SIZE);
+        if count > MAX_CHUNK_SECTORS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("chunk {x}, {z} is too large for a region file"),
+            ));
+        }
+        payload.resize(count * SECTOR_SIZE, 0);
+
+        let (old_start, old_count) = self.get_sectors(index);
+        self.used[old_start..old_start + old_count].fill(false);
+
+        let start = self.allocate(count);
+        self.file
+            .seek(SeekFrom::Start((start * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&payload)?;
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+
+        self.set_header(index, ((start as u32) << 8) | count as u32, timestamp)
+    }
+
+    /// Forgets the chunk, its sectors are reused by later writes.
+    pub fn remove_chunk(&mut self, x: i32, z: i32) -> std::io::Result<()> {
+        let index = Self::index(x, z);
+
+        let (start, count) = self.get_sectors(index);
+        self.used[start..start + count].fill(false);
+
+        self.set_header(index, 0, 0)
+    }
+
+    fn set_header(&mut self, index: usize, offset: u32, timestamp: u32) -> std::io::Result<()> {
+        self.offsets[index] = offset;
+        self.timestamps[index] = timestamp;
+
+        self.file.seek(SeekFrom::Start(index as u64 * 4))?;
+        self.file.write_all(&offset.to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())
+    }
+
+    /// First run of `count` free sectors, growing the file if there is none.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+        for (i, &used) in self.used.iter().enumerate() {
+            run = if used { 0 } else { run + 1 };
+
+            if run == count {
+                let start = i + 1 - count;
+                self.used[start..=i].fill(true);
+                return start;
+            }
+        }
+
+        // extend the free sectors at the end of the file, if there are any
+        let start = self.used.len() - run;
+        self.used.resize(start + count, true);
+        self.used[start..].fill(true);
+        start
+    }
+
+    /// First sector and sector count of the chunk.
+    fn get_sectors(&self, index: usize) -> (usize, usize) {
+        let offset = self.offsets[index];
+        ((offset >> 8) as usize, (offset & 0xff) as usize)
+    }
+
+    fn index(x: i32, z: i32) -> usize {
+        debug_assert!((0..REGION_SIZE).contains(&x) && (0..REGION_SIZE).contains(&z));
+        (x + z * REGION_SIZE) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("region-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn chunks_survive_reopening() {
+        let path = temp_path("reopen");
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        {
+            let mut region = RegionFile::open(&path).unwrap();
+            region
+                .write_chunk(0, 0, b"first", Compression::Zlib)
+                .unwrap();
+            region.write_chunk(31, 31, &big, Compression::Gzip).unwrap();
+            region
+                .write_chunk(5, 7, b"plain", Compression::None)
+                .unwrap();
+        }
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(0, 0).unwrap().unwrap(), b"first");
+        assert_eq!(region.read_chunk(31, 31).unwrap().unwrap(), big);
+        assert_eq!(region.read_chunk(5, 7).unwrap().unwrap(), b"plain");
+        assert_eq!(region.read_chunk(1, 0).unwrap(), None);
+
+        let mut chunks: Vec<_> = region.chunks().collect();
+        chunks.sort();
+        assert_eq!(chunks, [(0, 0), (5, 7), (31, 31)]);
+        assert!(region.get_timestamp(5, 7) > 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_files_open_read_only_as_empty_regions() {
+        let path = temp_path("empty");
+        File::create(&path).unwrap();
+
+        let mut region = RegionFile::open_read_only(&path).unwrap().unwrap();
+        assert_eq!(region.chunks().count(), 0);
+        assert_eq!(region.read_chunk(3, 4).unwrap(), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn growing_chunks_move_and_free_their_sectors() {
+        let path = temp_path("grow");
+        let mut region = RegionFile::open(&path).unwrap();
+
+        let small = vec![1; 100];
+        let large = vec![2; 3 * SECTOR_SIZE];
+
+        region.write_chunk(0, 0, &small, Compression::None).unwrap();
+        region.write_chunk(1, 0, &small, Compression::None).unwrap();
+        // doesn't fit in its old sector any more, moves to the end
+        region.write_chunk(0, 0, &large, Compression::None).unwrap();
+        // takes the sector chunk 0, 0 left behind
+        region.write_chunk(2, 0, &small, Compression::None).unwrap();
+
+        assert_eq!(
+            region.get_sectors(RegionFile::index(2, 0)).0,
+            HEADER_SECTORS
+        );
+        assert_eq!(region.read_chunk(0, 0).unwrap().unwrap(), large);
+        assert_eq!(region.read_chunk(1, 0).unwrap().unwrap(), small);
+
+        let sectors = region.used.len();
+        region.remove_chunk(0, 0).unwrap();
+        region.write_chunk(0, 0, &large, Compression::None).unwrap();
+        assert_eq!(region.used.len(), sectors);
+
+        drop(region);
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(0, 0).unwrap().unwrap(), large);
+        assert_eq!(region.read_chunk(2, 0).unwrap().unwrap(), small);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn regions_cover_negative_chunks() {
+        assert_eq!(region_of(ChunkPos::new(0, 31)), (0, 0));
+        assert_eq!(region_of(ChunkPos::new(-1, 32)), (-1, 1));
+        assert_eq!(region_of(ChunkPos::new(-32, -33)), (-1, -2));
+    }
+}
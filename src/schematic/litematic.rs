@@ -0,0 +1,240 @@
+//! Litematica schematics. A file holds named regions, each a box of packed palette
+//! indices placed relative to the others; they're all read into one clipboard.
+
+use glam::{ivec3, IVec3};
+
+use super::{
+    build_palette, check_size, compound_state, invalid, map_palette, read_vec, state_compound,
+    Clipboard, DATA_VERSION,
+};
+use crate::nbt::{Compound, Tag};
+use crate::save::{self, VanillaMapping};
+
+const VERSION: i32 = 6;
+
+/// Name of the one region clipboards are written as.
+const REGION_NAME: &str = "Main";
+
+struct Region<'a> {
+    /// Lowest corner, relative to the file's origin.
+    min: IVec3,
+    size: IVec3,
+    tag: &'a Compound,
+}
+
+pub(super) fn read(root: &Compound, mapping: &VanillaMapping) -> std::io::Result<Clipboard> {
+    let regions = root
+        .get("Regions")
+        .and_then(Tag::as_compound)
+        .ok_or_else(|| invalid("litematic without regions"))?;
+
+    let mut boxes = Vec::with_capacity(regions.len());
+    for (name, region) in regions.iter() {
+        let tag = region
+            .as_compound()
+            .ok_or_else(|| invalid(&format!("region {name} isn't a compound")))?;
+        let position = read_vec(tag.get("Position"))?;
+        let size = read_vec(tag.get("Size"))?;
+
+        // negative sizes grow the box from the position towards lower coordinates
+        let min = position
+            + size.min(IVec3::ZERO)
+            + IVec3::select(size.cmplt(IVec3::ZERO), IVec3::ONE, IVec3::ZERO);
+        let size = check_size(size.abs())?;
+        boxes.push(Region { min, size, tag });
+    }
+
+    let Some(min) = boxes.iter().map(|region| region.min).reduce(IVec3::min) else {
+        return Ok(Clipboard::new(IVec3::ZERO));
+    };
+    let max = boxes
+        .iter()
+        .map(|region| region.min + region.size)
+        .reduce(IVec3::max)
+        .unwrap_or(min);
+
+    let mut clipboard = Clipboard::new(check_size(max - min)?);
+    for region in boxes {
+        read_region(&region, min, mapping, &mut clipboard)?;
+    }
+
+    Ok(clipboard)
+}
+
+fn read_region(
+    region: &Region,
+    origin: IVec3,
+    mapping: &VanillaMapping,
+    clipboard: &mut Clipboard,
+) -> std::io::Result<()> {
+    let states = region
+        .tag
+        .get("BlockStatePalette")
+        .and_then(Tag::as_list)
+        .ok_or_else(|| invalid("region without a palette"))?
+        .iter()
+        .map(compound_state)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let palette = map_palette(states, mapping, &mut clipboard.unknown);
+
+    let size = region.size;
+    let count = (size.x * size.y * size.z) as usize;
+    let bits = save::index_bits(palette.len()).max(2);
+    let indices = save::unpack(region.tag.get("BlockStates"), bits, count, true)?;
+
+    let mut indices = indices.into_iter();
+    for y in 0..size.y {
+        for z in 0..size.z {
+            for x in 0..size.x {
+                let index = indices.next().unwrap_or_default() as usize;
+                let block = *palette
+                    .get(index)
+                    .ok_or_else(|| invalid(&format!("missing palette entry {index}")))?;
+
+                // later regions only overwrite where they have something to paste
+                if block.is_some() {
+                    clipboard.set(region.min - origin + ivec3(x, y, z), block);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn write(clipboard: &Clipboard, mapping: &VanillaMapping) -> Compound {
+    let (palette, indices) = build_palette(clipboard);
+    let size = clipboard.get_size();
+    let bits = save::index_bits(palette.len()).max(2);
+
+    let vec = |v: IVec3| -> Compound { [("x", v.x), ("y", v.y), ("z", v.z)].into_iter().collect() };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as i64);
+    let volume = size.x * size.y * size.z;
+
+    let region: Compound = [
+        ("Position", Tag::Compound(vec(IVec3::ZERO))),
+        ("Size", Tag::Compound(vec(size))),
+        (
+            "BlockStatePalette",
+            Tag::List(
+                palette
+                    .iter()
+                    .map(|&block| state_compound(block, mapping))
+                    .collect(),
+            ),
+        ),
+        (
+            "BlockStates",
+            Tag::LongArray(save::pack(&indices, bits, true)),
+        ),
+        ("Entities", Tag::List(Vec::new())),
+        ("TileEntities", Tag::List(Vec::new())),
+        ("PendingBlockTicks", Tag::List(Vec::new())),
+        ("PendingFluidTicks", Tag::List(Vec::new())),
+    ]
+    .into_iter()
+    .collect();
+
+    let metadata: Compound = [
+        ("Name", Tag::from("selection")),
+        ("Author", Tag::from("")),
+        ("Description", Tag::from("")),
+        ("RegionCount", Tag::Int(1)),
+        (
+            "TotalBlocks",
+            Tag::Int(
+                clipboard
+                    .blocks()
+                    .filter(|(_, block)| !block.is_air())
+                    .count() as i32,
+            ),
+        ),
+        ("TotalVolume", Tag::Int(volume)),
+        ("EnclosingSize", Tag::Compound(vec(size))),
+        ("TimeCreated", Tag::Long(now)),
+        ("TimeModified", Tag::Long(now)),
+    ]
+    .into_iter()
+    .collect();
+
+    [
+        ("MinecraftDataVersion", Tag::Int(DATA_VERSION)),
+        ("Version", Tag::Int(VERSION)),
+        ("Metadata", Tag::Compound(metadata)),
+        (
+            "Regions",
+            Tag::Compound([(REGION_NAME, region)].into_iter().collect()),
+        ),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockRegistry;
+    use crate::save::VANILLA_MAPPING_PATH;
+    use crate::world::BlockId;
+
+    #[test]
+    fn regions_with_negative_sizes_share_one_box() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let mapping = VanillaMapping::from_file(VANILLA_MAPPING_PATH, &registry).unwrap();
+        let id = |name| registry.get_id(name).unwrap();
+
+        let vec = |x, y, z| Tag::Compound([("x", x), ("y", y), ("z", z)].into_iter().collect());
+        let state = |name: &str| Tag::Compound([("Name", name)].into_iter().collect());
+        let region = |position, size, palette: Vec<Tag>, indices: &[u16]| {
+            let bits = save::index_bits(palette.len()).max(2);
+            Tag::Compound(
+                [
+                    ("Position", position),
+                    ("Size", size),
+                    ("BlockStatePalette", Tag::List(palette)),
+                    (
+                        "BlockStates",
+                        Tag::LongArray(save::pack(indices, bits, true)),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            )
+        };
+
+        // a 2x1x1 stone region at the origin and a 1x2x1 one reaching down to it from x = 2
+        let regions: Compound = [
+            (
+                "a",
+                region(
+                    vec(0, 0, 0),
+                    vec(2, 1, 1),
+                    vec![state("minecraft:air"), state("minecraft:stone")],
+                    &[1, 0],
+                ),
+            ),
+            (
+                "b",
+                region(
+                    vec(2, 1, 0),
+                    vec(1, -2, 1),
+                    vec![state("minecraft:air"), state("minecraft:sand")],
+                    &[1, 1],
+                ),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let root: Compound = [("Regions", regions)].into_iter().collect();
+
+        let clipboard = read(&root, &mapping).unwrap();
+        assert_eq!(clipboard.get_size(), ivec3(3, 2, 1));
+        assert_eq!(clipboard.get(ivec3(0, 0, 0)), Some(id("stone")));
+        assert_eq!(clipboard.get(ivec3(1, 0, 0)), Some(BlockId::AIR));
+        assert_eq!(clipboard.get(ivec3(2, 0, 0)), Some(id("sand")));
+        assert_eq!(clipboard.get(ivec3(2, 1, 0)), Some(id("sand")));
+        assert_eq!(clipboard.get(ivec3(0, 1, 0)), None);
+    }
+}
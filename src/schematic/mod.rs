@@ -0,0 +1,527 @@
+//! Structures shared as files: Sponge `.schem`, Litematica `.litematic` and the
+//! game's own structure `.nbt`. They are read into a [`Clipboard`] that can be
+//! pasted into the world, and a clipboard copied from the world can be written back out.
+
+mod litematic;
+mod sponge;
+mod structure;
+
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use glam::{ivec3, IVec3};
+
+use crate::block::{Axis, BlockRegistry, Facing};
+use crate::nbt::{self, Compound, Tag};
+use crate::save::{self, Compression, VanillaMapping};
+use crate::world::{BlockId, World};
+
+/// Quarter turns around the vertical axis, clockwise when looking down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    /// A further quarter turn clockwise.
+    pub fn next(self) -> Self {
+        match self {
+            Rotation::None => Rotation::Clockwise90,
+            Rotation::Clockwise90 => Rotation::Clockwise180,
+            Rotation::Clockwise180 => Rotation::Counterclockwise90,
+            Rotation::Counterclockwise90 => Rotation::None,
+        }
+    }
+
+    /// Where a position inside a box of the given size ends up once the box is turned,
+    /// the turned box still starting at the origin.
+    pub fn apply(self, pos: IVec3, size: IVec3) -> IVec3 {
+        match self {
+            Rotation::None => pos,
+            Rotation::Clockwise90 => ivec3(size.z - 1 - pos.z, pos.y, pos.x),
+            Rotation::Clockwise180 => ivec3(size.x - 1 - pos.x, pos.y, size.z - 1 - pos.z),
+            Rotation::Counterclockwise90 => ivec3(pos.z, pos.y, size.x - 1 - pos.x),
+        }
+    }
+
+    /// Which way a horizontal direction points once turned.
+    pub fn apply_to_facing(self, facing: Facing) -> Facing {
+        // clockwise from south, the way `Facing::quarter_turns` counts
+        const TURNS: [Facing; 4] = [Facing::South, Facing::West, Facing::North, Facing::East];
+        TURNS[(facing.quarter_turns() + self.quarter_turns()) as usize % 4]
+    }
+
+    /// Which axis one lying along `axis` lies along once turned.
+    pub fn apply_to_axis(self, axis: Axis) -> Axis {
+        match (self, axis) {
+            (Rotation::Clockwise90 | Rotation::Counterclockwise90, Axis::X) => Axis::Z,
+            (Rotation::Clockwise90 | Rotation::Counterclockwise90, Axis::Z) => Axis::X,
+            _ => axis,
+        }
+    }
+
+    /// The state turned along with the block, by its `facing` and `axis`.
+    pub fn apply_to_state(self, state: BlockId, registry: &BlockRegistry) -> BlockId {
+        let Some(block) = registry.get(state) else {
+            return state;
+        };
+
+        let mut state = state;
+        if let Some(facing) = block.get::<Facing>(state) {
+            state = block.with(state, self.apply_to_facing(facing));
+        }
+        if let Some(axis) = block.get::<Axis>(state) {
+            state = block.with(state, self.apply_to_axis(axis));
+        }
+        state
+    }
+
+    /// Size of the box once turned.
+    pub fn apply_to_size(self, size: IVec3) -> IVec3 {
+        match self {
+            Rotation::None | Rotation::Clockwise180 => size,
+            Rotation::Clockwise90 | Rotation::Counterclockwise90 => ivec3(size.z, size.y, size.x),
+        }
+    }
+
+    fn quarter_turns(self) -> u8 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::Counterclockwise90 => 3,
+        }
+    }
+}
+
+/// A box of blocks to paste. Cells left empty keep whatever the world has there,
+/// unlike air, which clears it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clipboard {
+    size: IVec3,
+    /// YZX ordered, like sections.
+    blocks: Vec<Option<BlockId>>,
+    /// Block states nothing stands in for, pasted as the placeholder block.
+    unknown: Vec<String>,
+}
+
+impl Clipboard {
+    /// An empty box, pasting it changes nothing.
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+
+        Self {
+            size,
+            blocks: vec![None; (size.x * size.y * size.z) as usize],
+            unknown: Vec::new(),
+        }
+    }
+
+    /// The blocks of the world between the two corners, both included.
+    pub fn copy(world: &World, a: IVec3, b: IVec3) -> Self {
+        let min = a.min(b);
+        let mut clipboard = Self::new(a.max(b) - min + IVec3::ONE);
+
+        for pos in clipboard.positions() {
+            clipboard.set(pos, Some(world.get_block(min + pos)));
+        }
+
+        clipboard
+    }
+
+    /// Reads a schematic, telling the format apart by the file extension:
+    /// `.schem`, `.litematic` or `.nbt`.
+    pub fn load(path: &Path, mapping: &VanillaMapping) -> std::io::Result<Self> {
+        let (_, root) = nbt::read_file(path)?;
+        let root = root
+            .as_compound()
+            .ok_or_else(|| invalid("root tag isn't a compound"))?;
+
+        match extension(path)?.as_str() {
+            "schem" => sponge::read(root, mapping),
+            "litematic" => litematic::read(root, mapping),
+            _ => structure::read(root, mapping),
+        }
+    }
+
+    /// Writes the clipboard in the format of the file extension, gzip compressed like the game does.
+    pub fn save(&self, path: &Path, mapping: &VanillaMapping) -> std::io::Result<()> {
+        let root = match extension(path)?.as_str() {
+            "schem" => sponge::write(self, mapping),
+            "litematic" => litematic::write(self, mapping),
+            _ => structure::write(self, mapping),
+        };
+
+        nbt::write_file(path, "", &Tag::Compound(root), Compression::Gzip)
+    }
+
+    pub fn get_size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// `None` outside the box and for cells that keep the world's block.
+    pub fn get(&self, pos: IVec3) -> Option<BlockId> {
+        self.index(pos).and_then(|i| self.blocks[i])
+    }
+
+    /// Writes outside of the box are ignored.
+    pub fn set(&mut self, pos: IVec3, block: Option<BlockId>) {
+        if let Some(i) = self.index(pos) {
+            self.blocks[i] = block;
+        }
+    }
+
+    /// Block states read from the file that are pasted as the placeholder block.
+    pub fn get_unknown_states(&self) -> &[String] {
+        &self.unknown
+    }
+
+    /// The clipboard turned around its vertical axis, blocks turning along with it.
+    pub fn rotated(&self, rotation: Rotation, registry: &BlockRegistry) -> Self {
+        let mut rotated = Self::new(rotation.apply_to_size(self.size));
+        rotated.unknown = self.unknown.clone();
+
+        for pos in self.positions() {
+            let block = self
+                .get(pos)
+                .map(|block| rotation.apply_to_state(block, registry));
+            rotated.set(rotation.apply(pos, self.size), block);
+        }
+
+        rotated
+    }
+
+    /// Every block pasting changes, relative to the lowest corner of the box.
+    pub fn blocks(&self) -> impl Iterator<Item = (IVec3, BlockId)> + '_ {
+        self.positions()
+            .filter_map(|pos| Some((pos, self.get(pos)?)))
+    }
+
+    /// Pastes the clipboard with its lowest corner at the position.
+    pub fn paste(&self, world: &mut World, position: IVec3) {
+        for (pos, block) in self.blocks() {
+            world.set_block(position + pos, block);
+        }
+    }
+
+    /// Every position in the box, in storage order.
+    fn positions(&self) -> impl Iterator<Item = IVec3> {
+        let size = self.size;
+        (0..size.y).flat_map(move |y| {
+            (0..size.z).flat_map(move |z| (0..size.x).map(move |x| ivec3(x, y, z)))
+        })
+    }
+
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+
+        Some(((pos.y * self.size.z + pos.z) * self.size.x + pos.x) as usize)
+    }
+}
+
+/// Data version files are written with: 1.20.1. Blocks are written by name, so the version
+/// only tells tools what to expect.
+const DATA_VERSION: i32 = 3465;
+
+/// The game's marker for cells a structure leaves alone.
+const STRUCTURE_VOID: &str = "minecraft:structure_void";
+
+/// Maps a palette of vanilla block states onto our blocks, noting the states nothing
+/// stands in for. States are written `name[key=value,...]`.
+fn map_palette(
+    states: impl IntoIterator<Item = String>,
+    mapping: &VanillaMapping,
+    unknown: &mut Vec<String>,
+) -> Vec<Option<BlockId>> {
+    states
+        .into_iter()
+        .map(|state| {
+            let name = state.split('[').next().unwrap_or_default();
+            if name == STRUCTURE_VOID {
+                return None;
+            }
+
+            Some(mapping.get_state(&state).unwrap_or_else(|| {
+                if !unknown.contains(&state) {
+                    unknown.push(state);
+                }
+                mapping.get_placeholder()
+            }))
+        })
+        .collect()
+}
+
+/// The palette of the clipboard, in order of first use, and each cell's index into it.
+fn build_palette(clipboard: &Clipboard) -> (Vec<Option<BlockId>>, Vec<u16>) {
+    let mut palette = Vec::new();
+    let indices = clipboard
+        .blocks
+        .iter()
+        .map(
+            |block| match palette.iter().position(|entry| entry == block) {
+                Some(index) => index as u16,
+                None => {
+                    palette.push(*block);
+                    (palette.len() - 1) as u16
+                }
+            },
+        )
+        .collect();
+
+    (palette, indices)
+}
+
+/// State a palette entry is written as, cells that keep the world's block being structure void.
+fn vanilla_state(block: Option<BlockId>, mapping: &VanillaMapping) -> String {
+    block.map_or(STRUCTURE_VOID.into(), |block| {
+        mapping.get_vanilla_state(block)
+    })
+}
+
+/// A palette entry of the structure and Litematica formats as a state string.
+fn compound_state(state: &Tag) -> std::io::Result<String> {
+    let state = state
+        .as_compound()
+        .ok_or_else(|| invalid("block state that isn't a compound"))?;
+    let name = state
+        .get("Name")
+        .and_then(Tag::as_str)
+        .ok_or_else(|| invalid("block state without a name"))?;
+
+    Ok(save::describe_state(name, state))
+}
+
+/// A palette entry of the structure and Litematica formats.
+fn state_compound(block: Option<BlockId>, mapping: &VanillaMapping) -> Tag {
+    let Some(block) = block else {
+        return Tag::Compound([("Name", STRUCTURE_VOID)].into_iter().collect());
+    };
+
+    let mut state: Compound = [("Name", mapping.get_vanilla_name(block))]
+        .into_iter()
+        .collect();
+    let properties = mapping.get_vanilla_properties(block);
+    if !properties.is_empty() {
+        state.insert("Properties", properties.into_iter().collect::<Compound>());
+    }
+
+    Tag::Compound(state)
+}
+
+/// Reads an `{x, y, z}` compound or a list of three ints.
+fn read_vec(tag: Option<&Tag>) -> std::io::Result<IVec3> {
+    let bad = || invalid("bad position or size");
+
+    match tag.ok_or_else(bad)? {
+        Tag::Compound(compound) => {
+            let get = |name| compound.get(name).and_then(Tag::as_i64).ok_or_else(bad);
+            Ok(ivec3(get("x")? as i32, get("y")? as i32, get("z")? as i32))
+        }
+        Tag::List(list) => match list.as_slice() {
+            [x, y, z] => {
+                let get = |tag: &Tag| tag.as_i64().ok_or_else(bad);
+                Ok(ivec3(get(x)? as i32, get(y)? as i32, get(z)? as i32))
+            }
+            _ => Err(bad()),
+        },
+        Tag::IntArray(v) if v.len() == 3 => Ok(ivec3(v[0], v[1], v[2])),
+        _ => Err(bad()),
+    }
+}
+
+/// Schematics bigger than this along any axis are refused rather than allocated.
+const MAX_SIZE: i32 = 4096;
+
+/// Checks a size read from a file before a clipboard is made that big.
+fn check_size(size: IVec3) -> std::io::Result<IVec3> {
+    if size.cmplt(IVec3::ZERO).any() || size.cmpgt(IVec3::splat(MAX_SIZE)).any() {
+        return Err(invalid(&format!("bad schematic size {size}")));
+    }
+
+    if size.x as u64 * size.y as u64 * size.z as u64 > 1 << 28 {
+        return Err(invalid(&format!("schematic of size {size} is too large")));
+    }
+
+    Ok(size)
+}
+
+fn extension(path: &Path) -> std::io::Result<String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "schem" | "litematic" | "nbt" => Ok(extension),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} isn't a `.schem`, `.litematic` or `.nbt` file",
+                path.display()
+            ),
+        )),
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockRegistry;
+    use crate::world::ChunkPos;
+    use crate::worldgen::{Generator, TerrainGenerator, TerrainSettings};
+
+    fn setup() -> (BlockRegistry, VanillaMapping) {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let mapping = VanillaMapping::from_file(save::VANILLA_MAPPING_PATH, &registry).unwrap();
+        (registry, mapping)
+    }
+
+    #[test]
+    fn rotations_turn_clockwise() {
+        let size = ivec3(3, 1, 2);
+        // the +x end of the box turns to face +z
+        let pos = ivec3(2, 0, 0);
+
+        assert_eq!(Rotation::Clockwise90.apply(pos, size), ivec3(1, 0, 2));
+        assert_eq!(Rotation::Clockwise180.apply(pos, size), ivec3(0, 0, 1));
+        assert_eq!(
+            Rotation::Counterclockwise90.apply(pos, size),
+            ivec3(0, 0, 0)
+        );
+
+        let mut rotation = Rotation::None;
+        for _ in 0..4 {
+            rotation = rotation.next();
+        }
+        assert_eq!(rotation, Rotation::None);
+    }
+
+    #[test]
+    fn directions_turn_with_positions() {
+        let size = ivec3(3, 1, 3);
+        let center = ivec3(1, 0, 1);
+
+        let mut rotation = Rotation::None;
+        for _ in 0..4 {
+            for facing in Facing::ALL {
+                let turned = rotation.apply(center + facing.normal(), size) - center;
+                assert_eq!(
+                    rotation.apply_to_facing(facing).normal(),
+                    turned,
+                    "{rotation:?}"
+                );
+
+                let axis = Axis::from_normal(facing.normal());
+                assert_eq!(
+                    rotation.apply_to_axis(axis),
+                    Axis::from_normal(turned),
+                    "{rotation:?}"
+                );
+            }
+            rotation = rotation.next();
+        }
+
+        assert_eq!(
+            Rotation::Clockwise90.apply_to_facing(Facing::East),
+            Facing::South
+        );
+        assert_eq!(Rotation::Clockwise90.apply_to_axis(Axis::Y), Axis::Y);
+    }
+
+    #[test]
+    fn four_quarter_turns_change_nothing() {
+        let (registry, _) = setup();
+        let mut clipboard = Clipboard::new(ivec3(4, 2, 3));
+        clipboard.set(ivec3(3, 1, 0), registry.get_id("log"));
+        clipboard.set(ivec3(0, 0, 2), Some(BlockId::AIR));
+        clipboard.set(ivec3(1, 0, 1), registry.parse_state("log[axis=x]"));
+
+        let mut turned = clipboard.clone();
+        for _ in 0..4 {
+            turned = turned.rotated(Rotation::Clockwise90, &registry);
+        }
+        assert_eq!(turned, clipboard);
+
+        let turned = clipboard.rotated(Rotation::Clockwise90, &registry);
+        assert_eq!(turned.get_size(), ivec3(3, 2, 4));
+        assert_eq!(turned.get(ivec3(2, 1, 3)), registry.get_id("log"));
+    }
+
+    #[test]
+    fn turned_blocks_keep_facing_the_new_way_once_saved() {
+        let (registry, mapping) = setup();
+        let stairs = registry
+            .parse_state("cobblestone_stairs[facing=east]")
+            .unwrap();
+        let log = registry.parse_state("log[axis=x]").unwrap();
+
+        let mut clipboard = Clipboard::new(ivec3(2, 1, 1));
+        clipboard.set(ivec3(0, 0, 0), Some(stairs));
+        clipboard.set(ivec3(1, 0, 0), Some(log));
+
+        let turned = clipboard.rotated(Rotation::Clockwise90, &registry);
+        let block = |pos| turned.get(pos).and_then(|id| registry.describe_state(id));
+        assert_eq!(
+            block(ivec3(0, 0, 0)).as_deref(),
+            Some("cobblestone_stairs[facing=south,half=bottom,waterlogged=false]")
+        );
+        assert_eq!(block(ivec3(0, 0, 1)).as_deref(), Some("log[axis=z]"));
+
+        let dir = std::env::temp_dir().join(format!("rotated-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["turned.schem", "turned.litematic", "turned.nbt"] {
+            let path = dir.join(name);
+            turned.save(&path, &mapping).unwrap();
+            assert_eq!(Clipboard::load(&path, &mapping).unwrap(), turned, "{name}");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn formats_round_trip_through_files() {
+        let (registry, mapping) = setup();
+        let generator = TerrainGenerator::new(9, &registry, TerrainSettings::default());
+
+        let mut world = World::new();
+        world.insert_chunk(generator.generate(ChunkPos::new(0, 0)));
+        let top = generator.get_spawn().y;
+        let copied = Clipboard::copy(&world, ivec3(2, top - 6, 3), ivec3(13, top + 3, 11));
+        assert_eq!(copied.get_size(), ivec3(12, 10, 9));
+
+        let dir = std::env::temp_dir().join(format!("schematic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["copy.schem", "copy.litematic", "copy.nbt"] {
+            let path = dir.join(name);
+            copied.save(&path, &mapping).unwrap();
+            let loaded = Clipboard::load(&path, &mapping).unwrap();
+
+            assert_eq!(loaded, copied, "{name}");
+        }
+
+        let mut pasted = World::new();
+        copied
+            .rotated(Rotation::Clockwise180, &registry)
+            .paste(&mut pasted, ivec3(-20, 0, 5));
+        assert_eq!(
+            pasted.get_block(ivec3(-20 + 11 - 2, 4, 5 + 8 - 1)),
+            copied.get(ivec3(2, 4, 1)).unwrap()
+        );
+
+        assert!(copied.save(&dir.join("copy.txt"), &mapping).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
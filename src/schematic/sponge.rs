@@ -0,0 +1,228 @@
+//! Sponge schematics, as written by WorldEdit. Versions 1 and 2 keep everything at the
+//! root, version 3 moves it into a `Schematic` compound with blocks in their own.
+
+use glam::ivec3;
+
+use super::{
+    build_palette, check_size, invalid, map_palette, vanilla_state, Clipboard, DATA_VERSION,
+};
+use crate::nbt::{Compound, Tag};
+use crate::save::VanillaMapping;
+
+const VERSION: i32 = 3;
+
+pub(super) fn read(root: &Compound, mapping: &VanillaMapping) -> std::io::Result<Clipboard> {
+    let root = match root.get("Schematic").and_then(Tag::as_compound) {
+        Some(schematic) => schematic,
+        None => root,
+    };
+
+    let length = |name| {
+        root.get(name)
+            .and_then(Tag::as_i64)
+            // sizes are unsigned shorts
+            .map(|v| v as u16 as i32)
+            .ok_or_else(|| invalid(&format!("schematic without a {name}")))
+    };
+    let size = check_size(ivec3(
+        length("Width")?,
+        length("Height")?,
+        length("Length")?,
+    ))?;
+
+    let blocks = match root.get("Blocks").and_then(Tag::as_compound) {
+        Some(blocks) => blocks,
+        None => root,
+    };
+    let palette = blocks
+        .get("Palette")
+        .and_then(Tag::as_compound)
+        .ok_or_else(|| invalid("schematic without a palette"))?;
+    let data = blocks
+        .get("Data")
+        .or_else(|| blocks.get("BlockData"))
+        .and_then(Tag::as_byte_array)
+        .ok_or_else(|| invalid("schematic without block data"))?;
+
+    // the palette maps states to indices, which needn't be in order or dense
+    let mut entries = Vec::with_capacity(palette.len());
+    for (state, index) in palette.iter() {
+        let index = index
+            .as_i64()
+            .filter(|&index| (0..=u16::MAX as i64).contains(&index))
+            .ok_or_else(|| invalid(&format!("bad palette index for {state}")))?;
+        entries.push((index as usize, state.to_owned()));
+    }
+    let length = entries
+        .iter()
+        .map(|&(index, _)| index + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut clipboard = Clipboard::new(size);
+    let mapped = map_palette(
+        entries.iter().map(|(_, state)| state.clone()),
+        mapping,
+        &mut clipboard.unknown,
+    );
+    let mut lookup = vec![None; length];
+    for ((index, _), block) in entries.iter().zip(mapped) {
+        lookup[*index] = Some(block);
+    }
+
+    let indices = read_varints(data, clipboard.blocks.len())?;
+    for (cell, index) in clipboard.blocks.iter_mut().zip(indices) {
+        *cell = lookup
+            .get(index as usize)
+            .copied()
+            .flatten()
+            .ok_or_else(|| {
+                invalid(&format!(
+                    "block data refers to missing palette entry {index}"
+                ))
+            })?;
+    }
+
+    Ok(clipboard)
+}
+
+pub(super) fn write(clipboard: &Clipboard, mapping: &VanillaMapping) -> Compound {
+    let (palette, indices) = build_palette(clipboard);
+    let size = clipboard.get_size();
+
+    let mut data = Vec::new();
+    for index in indices {
+        write_varint(&mut data, index as u32);
+    }
+
+    let blocks: Compound = [
+        (
+            "Palette",
+            Tag::Compound(
+                palette
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &block)| (vanilla_state(block, mapping), i as i32))
+                    .collect(),
+            ),
+        ),
+        ("Data", Tag::ByteArray(data)),
+    ]
+    .into_iter()
+    .collect();
+
+    let schematic: Compound = [
+        ("Version", Tag::Int(VERSION)),
+        ("DataVersion", Tag::Int(DATA_VERSION)),
+        ("Width", Tag::Short(size.x as u16 as i16)),
+        ("Height", Tag::Short(size.y as u16 as i16)),
+        ("Length", Tag::Short(size.z as u16 as i16)),
+        ("Offset", Tag::IntArray(vec![0; 3])),
+        ("Blocks", Tag::Compound(blocks)),
+    ]
+    .into_iter()
+    .collect();
+
+    [("Schematic", schematic)].into_iter().collect()
+}
+
+/// Palette indices are stored as unsigned LEB128 varints, seven bits to a byte.
+fn read_varints(data: &[i8], count: usize) -> std::io::Result<Vec<u32>> {
+    let mut values = Vec::with_capacity(count);
+    let mut bytes = data.iter().map(|&b| b as u8);
+
+    while values.len() < count {
+        let mut value = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = bytes
+                .next()
+                .ok_or_else(|| invalid("block data ends early"))?;
+            if shift > 28 {
+                return Err(invalid("block data holds an overlong varint"));
+            }
+
+            value |= ((byte & 0x7f) as u32) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+fn write_varint(out: &mut Vec<i8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte as i8);
+            return;
+        }
+        out.push((byte | 0x80) as i8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockRegistry;
+    use crate::save::VANILLA_MAPPING_PATH;
+    use crate::world::BlockId;
+
+    #[test]
+    fn varints_take_seven_bits_a_byte() {
+        let values = [0, 1, 127, 128, 300, 16384, u16::MAX as u32];
+        let mut data = Vec::new();
+        values.iter().for_each(|&v| write_varint(&mut data, v));
+
+        assert_eq!(data[2..6], [127, -128, 1, -84]);
+        assert_eq!(read_varints(&data, values.len()).unwrap(), values);
+        assert!(read_varints(&data[..data.len() - 1], values.len()).is_err());
+    }
+
+    #[test]
+    fn version_two_schematics_load() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let mapping = VanillaMapping::from_file(VANILLA_MAPPING_PATH, &registry).unwrap();
+        let id = |name| registry.get_id(name).unwrap();
+
+        // a 2x1x2 schematic the way WorldEdit 7.2 writes it
+        let palette: Compound = [
+            ("minecraft:air", 0),
+            ("minecraft:spruce_log[axis=x]", 2),
+            ("minecraft:lantern[hanging=false]", 1),
+        ]
+        .into_iter()
+        .collect();
+        let root: Compound = [
+            ("Version", Tag::Int(2)),
+            ("DataVersion", Tag::Int(2586)),
+            ("Width", Tag::Short(2)),
+            ("Height", Tag::Short(1)),
+            ("Length", Tag::Short(2)),
+            ("PaletteMax", Tag::Int(3)),
+            ("Palette", Tag::Compound(palette)),
+            ("BlockData", Tag::ByteArray(vec![2, 0, 1, 2])),
+        ]
+        .into_iter()
+        .collect();
+
+        let clipboard = read(&root, &mapping).unwrap();
+        assert_eq!(clipboard.get_size(), ivec3(2, 1, 2));
+        assert_eq!(
+            clipboard.get(ivec3(0, 0, 0)),
+            registry.parse_state("log[axis=x]")
+        );
+        assert_eq!(clipboard.get(ivec3(1, 0, 0)), Some(BlockId::AIR));
+        assert_eq!(clipboard.get(ivec3(0, 0, 1)), Some(id("unknown")));
+        assert_eq!(
+            clipboard.get_unknown_states(),
+            ["minecraft:lantern[hanging=false]"]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! The game's structure files, as saved by structure blocks. Blocks are listed one by one
+//! with their position, and cells missing from the list are left alone when placed.
+
+use super::{
+    check_size, compound_state, invalid, map_palette, read_vec, state_compound, Clipboard,
+    DATA_VERSION,
+};
+use crate::nbt::{Compound, Tag};
+use crate::save::VanillaMapping;
+use crate::world::BlockId;
+
+pub(super) fn read(root: &Compound, mapping: &VanillaMapping) -> std::io::Result<Clipboard> {
+    let size = check_size(read_vec(root.get("size"))?)?;
+
+    // files with several palettes pick one at random when placed, we take the first
+    let palette = match root.get("palettes").and_then(Tag::as_list) {
+        Some(palettes) => palettes.first(),
+        None => root.get("palette"),
+    }
+    .and_then(Tag::as_list)
+    .ok_or_else(|| invalid("structure without a palette"))?;
+    let states = palette
+        .iter()
+        .map(compound_state)
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut clipboard = Clipboard::new(size);
+    let palette = map_palette(states, mapping, &mut clipboard.unknown);
+
+    let blocks = root
+        .get("blocks")
+        .and_then(Tag::as_list)
+        .ok_or_else(|| invalid("structure without blocks"))?;
+    for block in blocks {
+        let block = block
+            .as_compound()
+            .ok_or_else(|| invalid("structure block that isn't a compound"))?;
+        let pos = read_vec(block.get("pos"))?;
+        let state = block
+            .get("state")
+            .and_then(Tag::as_i64)
+            .and_then(|state| palette.get(usize::try_from(state).ok()?))
+            .ok_or_else(|| invalid("structure block without a state from the palette"))?;
+
+        clipboard.set(pos, *state);
+    }
+
+    Ok(clipboard)
+}
+
+pub(super) fn write(clipboard: &Clipboard, mapping: &VanillaMapping) -> Compound {
+    let mut palette: Vec<BlockId> = Vec::new();
+    let mut blocks = Vec::new();
+
+    for (pos, block) in clipboard.blocks() {
+        let state = palette
+            .iter()
+            .position(|&entry| entry == block)
+            .unwrap_or_else(|| {
+                palette.push(block);
+                palette.len() - 1
+            });
+
+        let block: Compound = [
+            ("pos", Tag::List(pos.to_array().map(Tag::Int).to_vec())),
+            ("state", Tag::Int(state as i32)),
+        ]
+        .into_iter()
+        .collect();
+        blocks.push(Tag::Compound(block));
+    }
+
+    let size = clipboard.get_size();
+    [
+        ("DataVersion", Tag::Int(DATA_VERSION)),
+        ("size", Tag::List(size.to_array().map(Tag::Int).to_vec())),
+        (
+            "palette",
+            Tag::List(
+                palette
+                    .into_iter()
+                    .map(|block| state_compound(Some(block), mapping))
+                    .collect(),
+            ),
+        ),
+        ("blocks", Tag::List(blocks)),
+        ("entities", Tag::List(Vec::new())),
+    ]
+    .into_iter()
+    .collect()
+}
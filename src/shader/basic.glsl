@@ -4,26 +4,32 @@
 layout(location = 0) in vec3 a_pos;
 layout(location = 1) in vec2 a_tex_coord;
 layout(location = 2) in vec3 a_normal;
-layout(location = 3) in vec3 a_instance_pos;
-layout(location = 4) in int a_texture_idx;
+layout(location = 3) in uint a_texture_idx;
+layout(location = 4) in vec2 a_light;
+layout(location = 5) in float a_ao;
 
 out vec3 normal;
 out vec3 frag_position;
 out vec2 tex_coord;
 flat out int texture_idx;
+out vec2 light;
+out float ao;
+out float view_depth;
 
 uniform mat4 view;
 uniform mat4 projection;
 
-uniform vec3 light_position;
-
 void main() {
-    gl_Position = projection * view * vec4(a_pos + a_instance_pos, 1.0);
+    vec4 view_position = view * vec4(a_pos, 1.0);
+    gl_Position = projection * view_position;
+    view_depth = -view_position.z;
 
     tex_coord = a_tex_coord;
     normal = a_normal;
-    frag_position = a_pos + a_instance_pos;
-    texture_idx = a_texture_idx;
+    frag_position = a_pos;
+    texture_idx = int(a_texture_idx);
+    light = a_light;
+    ao = a_ao;
 }
 
 -- fragment
@@ -33,25 +39,120 @@ in vec2 tex_coord;
 in vec3 normal;
 in vec3 frag_position;
 flat in int texture_idx;
+in vec2 light;
+in float ao;
+in float view_depth;
 
 out vec4 frag_color;
 
 // uniform sampler2D tex;
 uniform sampler2DArray tex_array;
 
+// the sun or the moon, pointing towards it
+uniform vec3 light_direction;
 uniform vec3 light_color;
-uniform vec3 light_position;
 uniform vec3 eye_position;
 
+// both follow the time of day
+uniform float ambient_strength;
+// how bright sky light is compared to noon
+uniform float sky_light;
+
+// match the constants in `renderer/shadow.rs`
+const int CASCADES = 3;
+
+uniform sampler2DArrayShadow shadow_map;
+uniform mat4 light_space[CASCADES];
+// view depth each cascade reaches to
+uniform float cascade_ends[CASCADES];
+// size of a shadow map texel in blocks, for each cascade
+uniform float shadow_texel_sizes[CASCADES];
+
+// match the constants in `renderer/light.rs`
+const int MAX_POINT_LIGHTS = 256;
+const int LIGHTS_PER_DRAW = 8;
+
+struct PointLight {
+    vec3 position;
+    float radius;
+    vec3 color;
+};
+
+layout(std140) uniform PointLights {
+    PointLight point_lights[MAX_POINT_LIGHTS];
+};
+
+// the lights reaching this draw, as indices into `point_lights`
+uniform int point_light_count;
+uniform int point_light_indices[LIGHTS_PER_DRAW];
+
+// torches and the like burn a little warmer than daylight
+const vec3 block_light_color = vec3(1.0, 0.9, 0.75);
+
+// each light level is a fifth darker than the one above, level 0 is nearly black
+float brightness(float level) {
+    return pow(0.8, 15.0 * (1.0 - level));
+}
+
+// inverse square falloff, eased down to nothing at the radius so lights can be left out
+// of draws they don't reach without a visible edge
+float attenuation(float distance, float radius) {
+    float fade = clamp(1.0 - pow(distance / radius, 4.0), 0.0, 1.0);
+    return fade * fade / (1.0 + 0.2 * distance * distance);
+}
+
+vec3 point_lighting(vec3 norm) {
+    vec3 total = vec3(0.0);
+    for (int i = 0; i < point_light_count; i++) {
+        PointLight point = point_lights[point_light_indices[i]];
+
+        vec3 to_light = point.position - frag_position;
+        float distance = length(to_light);
+        float diff = max(dot(norm, to_light / max(distance, 0.001)), 0.0);
+        total += point.color * diff * attenuation(distance, point.radius);
+    }
+    return total;
+}
+
+// how much of the sun or moon reaches the fragment, averaged over 3x3 texels of the nearest
+// cascade covering it
+float light_visibility(vec3 norm) {
+    int cascade = -1;
+    for (int i = CASCADES - 1; i >= 0; i--) {
+        if (view_depth < cascade_ends[i]) {
+            cascade = i;
+        }
+    }
+    if (cascade < 0) {
+        return 1.0;
+    }
+
+    // looking up a little off the surface keeps faces from shadowing themselves
+    vec3 offset = norm * shadow_texel_sizes[cascade] * 1.5;
+    vec4 position = light_space[cascade] * vec4(frag_position + offset, 1.0);
+    vec3 coords = position.xyz / position.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0).xy);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 uv = coords.xy + vec2(x, y) * texel;
+            lit += texture(shadow_map, vec4(uv, cascade, coords.z));
+        }
+    }
+    return lit / 9.0;
+}
+
 void main() {
     vec3 norm = normalize(normal);
 
     // ambient
-    float ambient_strength = 0.1;
-    vec3 ambient = ambient_strength * light_color;
+    vec3 ambient = vec3(ambient_strength);
 
     // diffuse
-    vec3 light_direction = normalize(light_position - frag_position);
     float diff = max(dot(norm, light_direction), 0.0);
     vec3 diffuse = diff * light_color;
 
@@ -63,7 +164,23 @@ void main() {
     float spec = pow(max(dot(eye_direction, reflect_dir), 0.0), 32);
     vec3 specular = specular_strength * spec * light_color;
 
-    vec4 intensity = vec4(ambient + diffuse + specular, 1.0);
+    // faces turned away from the light are dark already
+    float shadow = diff > 0.0 ? light_visibility(norm) : 1.0;
+
+    vec4 color = texture(tex_array, vec3(tex_coord, texture_idx));
+    // cut out the holes in leaves and plants
+    if (color.a < 0.5) {
+        discard;
+    }
+
+    // the sun and moon only reach as far as sky light does
+    vec3 sky = (ambient + (diffuse + specular) * shadow) * brightness(light.x) * sky_light;
+    vec3 block = block_light_color * brightness(light.y) + point_lighting(norm);
+
+    // corners boxed in by blocks get less of both
+    float occlusion = mix(0.4, 1.0, ao);
+
+    vec4 intensity = vec4((sky + block) * occlusion, 1.0);
     // frag_color = texture(tex, tex_coord) * intensity;
-    frag_color = texture(tex_array, vec3(tex_coord, texture_idx)) * intensity;
+    frag_color = color * intensity;
 }
@@ -0,0 +1,33 @@
+-- vertex
+#version 330 core
+
+layout(location = 0) in vec3 a_pos;
+layout(location = 1) in vec2 a_tex_coord;
+layout(location = 3) in uint a_texture_idx;
+
+out vec2 tex_coord;
+flat out int texture_idx;
+
+uniform mat4 light_space;
+
+void main() {
+    gl_Position = light_space * vec4(a_pos, 1.0);
+
+    tex_coord = a_tex_coord;
+    texture_idx = int(a_texture_idx);
+}
+
+-- fragment
+#version 330 core
+
+in vec2 tex_coord;
+flat in int texture_idx;
+
+uniform sampler2DArray tex_array;
+
+void main() {
+    // the holes in leaves and plants let the sun through
+    if (texture(tex_array, vec3(tex_coord, texture_idx)).a < 0.5) {
+        discard;
+    }
+}
@@ -32,6 +32,8 @@ pub fn create_window() -> CreateWindowOutput {
     window.set_cursor_pos_polling(true);
     window.set_mouse_button_polling(true);
     window.set_size_polling(true);
+    window.set_drag_and_drop_polling(true);
+    window.set_scroll_polling(true);
 
     window.make_current();
 
@@ -0,0 +1,437 @@
+use super::light::ChunkLight;
+use super::{BlockId, Light};
+
+/// Width and depth of a chunk column, and the edge length of a section.
+pub const CHUNK_SIZE: i32 = 16;
+/// Lowest block y coordinate a chunk can hold.
+pub const MIN_Y: i32 = -64;
+/// Number of vertically stacked sections in a chunk column.
+pub const SECTION_COUNT: usize = 24;
+/// One past the highest block y coordinate a chunk can hold.
+pub const MAX_Y: i32 = MIN_Y + SECTION_COUNT as i32 * CHUNK_SIZE;
+
+const SECTION_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Position of a chunk column, in chunk units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Chunk containing the given world block position.
+    pub fn from_block(pos: glam::IVec3) -> Self {
+        Self {
+            x: pos.x.div_euclid(CHUNK_SIZE),
+            z: pos.z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    /// World position of the chunk's lowest corner block.
+    pub fn origin(&self) -> glam::IVec3 {
+        glam::ivec3(self.x * CHUNK_SIZE, MIN_Y, self.z * CHUNK_SIZE)
+    }
+}
+
+/// A 16x16x16 cube of blocks, stored as a palette of the blocks it holds and YZX ordered
+/// indices into it, packed into as few bits as the palette needs. A section of a single
+/// block keeps no indices at all.
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// Blocks the indices refer to, with how many cells use each. Entries nothing uses
+    /// anymore are taken again before the palette grows.
+    palette: Vec<(BlockId, u16)>,
+    /// Bits per index, `0` when the palette holds a single block.
+    bits: u32,
+    /// Indices don't straddle two words, the top bits of each are left unused.
+    data: Box<[u64]>,
+    non_air: u16,
+}
+
+impl Section {
+    pub fn new() -> Self {
+        Self::filled(BlockId::AIR)
+    }
+
+    /// A section holding nothing but the given block.
+    pub fn filled(block: BlockId) -> Self {
+        Self {
+            palette: vec![(block, SECTION_VOLUME as u16)],
+            bits: 0,
+            data: Box::default(),
+            non_air: if block.is_air() {
+                0
+            } else {
+                SECTION_VOLUME as u16
+            },
+        }
+    }
+
+    /// `x`, `y` and `z` are local to the section, in `0..CHUNK_SIZE`.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.get_at(Self::index(x, y, z))
+    }
+
+    /// Returns the block that was previously stored at the position.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) -> BlockId {
+        let i = Self::index(x, y, z);
+        let old_entry = self.read(i);
+        let old = self.palette[old_entry].0;
+        if old == block {
+            return old;
+        }
+
+        let entry = self.palette_entry(block);
+        self.write(i, entry);
+        self.palette[entry].1 += 1;
+        self.palette[old_entry].1 -= 1;
+
+        match (old.is_air(), block.is_air()) {
+            (true, false) => self.non_air += 1,
+            (false, true) => self.non_air -= 1,
+            _ => {}
+        }
+
+        if self.palette[old_entry].1 == 0 {
+            self.shrink();
+        }
+
+        old
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.non_air == 0
+    }
+
+    /// The different blocks in the section.
+    pub fn palette(&self) -> impl Iterator<Item = BlockId> + '_ {
+        self.palette
+            .iter()
+            .filter(|&&(_, count)| count > 0)
+            .map(|&(block, _)| block)
+    }
+
+    /// Bits each block takes, besides the palette.
+    pub fn get_bits_per_block(&self) -> u32 {
+        self.bits
+    }
+
+    fn get_at(&self, i: usize) -> BlockId {
+        self.palette[self.read(i)].0
+    }
+
+    /// Palette entry of the block, taking an unused entry or growing the palette if it
+    /// isn't in there yet.
+    fn palette_entry(&mut self, block: BlockId) -> usize {
+        if let Some(entry) = self.palette.iter().position(|&(b, _)| b == block) {
+            return entry;
+        }
+
+        if let Some(entry) = self.palette.iter().position(|&(_, count)| count == 0) {
+            self.palette[entry].0 = block;
+            return entry;
+        }
+
+        self.palette.push((block, 0));
+        let bits = index_bits(self.palette.len());
+        if bits > self.bits {
+            self.repack(bits, |entry| entry);
+        }
+
+        self.palette.len() - 1
+    }
+
+    /// Drops unused palette entries once the indices would fit in at least two bits less,
+    /// so edits back and forth across a power of two don't repack every time.
+    fn shrink(&mut self) {
+        let used = self.palette.iter().filter(|&&(_, count)| count > 0).count();
+        if used > 1 && index_bits(used) + 1 >= self.bits {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(used);
+        for (entry, &(block, count)) in self.palette.iter().enumerate() {
+            if count > 0 {
+                remap[entry] = palette.len();
+                palette.push((block, count));
+            }
+        }
+
+        self.repack(index_bits(used), |entry| remap[entry]);
+        self.palette = palette;
+    }
+
+    /// Rewrites every index with the new width, mapping it through `remap` on the way.
+    fn repack(&mut self, bits: u32, remap: impl Fn(usize) -> usize) {
+        let data = match 64u32.checked_div(bits) {
+            Some(per_word) => {
+                vec![0; SECTION_VOLUME.div_ceil(per_word as usize)].into_boxed_slice()
+            }
+            None => Box::default(),
+        };
+
+        let old = Self {
+            palette: Vec::new(),
+            bits: std::mem::replace(&mut self.bits, bits),
+            data: std::mem::replace(&mut self.data, data),
+            non_air: 0,
+        };
+
+        if bits > 0 {
+            for i in 0..SECTION_VOLUME {
+                self.write(i, remap(old.read(i)));
+            }
+        }
+    }
+
+    fn read(&self, i: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        ((self.data[i / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn write(&mut self, i: usize, entry: usize) {
+        if self.bits == 0 {
+            return;
+        }
+
+        let per_word = (64 / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+
+        let word = &mut self.data[i / per_word];
+        *word = (*word & !mask) | ((entry as u64) << shift);
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        const S: usize = CHUNK_SIZE as usize;
+        debug_assert!(x < S && y < S && z < S, "section index out of bounds");
+        (y * S + z) * S + x
+    }
+}
+
+/// Bits needed to tell the entries of a palette of the given length apart.
+fn index_bits(length: usize) -> u32 {
+    usize::BITS - length.saturating_sub(1).leading_zeros()
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A column of sections spanning `MIN_Y..MAX_Y`.
+/// Sections that only contain air are not allocated.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    position: ChunkPos,
+    sections: [Option<Section>; SECTION_COUNT],
+    /// Open sky everywhere until the chunk is lit, see `light::light_chunk`.
+    light: ChunkLight,
+}
+
+impl Chunk {
+    pub fn new(position: ChunkPos) -> Self {
+        Self {
+            position,
+            sections: std::array::from_fn(|_| None),
+            light: ChunkLight::new(Light::SKY),
+        }
+    }
+
+    pub fn get_position(&self) -> ChunkPos {
+        self.position
+    }
+
+    /// `x` and `z` are local to the chunk, `y` is the world y coordinate.
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        let Some((index, local_y)) = Self::section_index(y) else {
+            return BlockId::AIR;
+        };
+
+        match &self.sections[index] {
+            Some(section) => section.get(x as _, local_y, z as _),
+            None => BlockId::AIR,
+        }
+    }
+
+    /// `x` and `z` are local to the chunk, `y` is the world y coordinate.
+    /// Returns the block that was previously stored at the position,
+    /// writes outside of `MIN_Y..MAX_Y` are ignored.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: BlockId) -> BlockId {
+        let Some((index, local_y)) = Self::section_index(y) else {
+            return BlockId::AIR;
+        };
+
+        let slot = &mut self.sections[index];
+        if slot.is_none() && block.is_air() {
+            return BlockId::AIR;
+        }
+
+        let section = slot.get_or_insert_with(Section::new);
+        let old = section.set(x as _, local_y, z as _, block);
+
+        if section.is_empty() {
+            *slot = None;
+        }
+
+        old
+    }
+
+    /// `x` and `z` are local to the chunk, `y` is the world y coordinate.
+    /// Above the chunk is open sky, below it is dark.
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> Light {
+        if y >= MAX_Y {
+            return Light::SKY;
+        }
+
+        match Self::section_index(y) {
+            Some((index, local_y)) => self
+                .light
+                .get(index, Section::index(x as _, local_y, z as _)),
+            None => Light::DARK,
+        }
+    }
+
+    /// `x` and `z` are local to the chunk, `y` is the world y coordinate.
+    /// Writes outside of `MIN_Y..MAX_Y` are ignored.
+    pub fn set_light(&mut self, x: i32, y: i32, z: i32, light: Light) {
+        if let Some((index, local_y)) = Self::section_index(y) {
+            self.light
+                .set(index, Section::index(x as _, local_y, z as _), light);
+        }
+    }
+
+    /// Lights every cell of the chunk the same.
+    pub fn fill_light(&mut self, light: Light) {
+        self.light = ChunkLight::new(light);
+    }
+
+    /// Frees the light of single cells in sections that are lit the same throughout.
+    pub fn compact_light(&mut self) {
+        self.light.compact();
+    }
+
+    /// Section at the given index, counted upwards from `MIN_Y`.
+    pub fn get_section(&self, index: usize) -> Option<&Section> {
+        self.sections.get(index)?.as_ref()
+    }
+
+    /// Iterates over every non-air block as `(world position, block)`.
+    pub fn blocks(&self) -> impl Iterator<Item = (glam::IVec3, BlockId)> + '_ {
+        let origin = self.position.origin();
+
+        self.sections
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((i, s.as_ref()?)))
+            .flat_map(move |(i, section)| {
+                let section_origin = origin + glam::IVec3::Y * (i as i32 * CHUNK_SIZE);
+
+                (0..SECTION_VOLUME).filter_map(move |idx| {
+                    let block = section.get_at(idx);
+                    if block.is_air() {
+                        return None;
+                    }
+
+                    let s = CHUNK_SIZE as usize;
+                    let local =
+                        glam::ivec3((idx % s) as _, (idx / (s * s)) as _, (idx / s % s) as _);
+                    Some((section_origin + local, block))
+                })
+            })
+    }
+
+    fn section_index(y: i32) -> Option<(usize, usize)> {
+        if !(MIN_Y..MAX_Y).contains(&y) {
+            return None;
+        }
+
+        let y = (y - MIN_Y) as usize;
+        Some((y / CHUNK_SIZE as usize, y % CHUNK_SIZE as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::noise::Rng;
+
+    #[test]
+    fn sections_match_a_plain_array_through_random_edits() {
+        let mut rng = Rng::new(18);
+        let mut section = Section::new();
+        let mut expected = vec![BlockId::AIR; SECTION_VOLUME];
+
+        for round in 0..40 {
+            // rounds alternate between many and few kinds of block
+            let kinds = if round % 2 == 0 { 40 } else { 3 };
+
+            for _ in 0..3000 {
+                let (x, y, z) = (
+                    rng.range_i32(0, 16) as usize,
+                    rng.range_i32(0, 16) as usize,
+                    rng.range_i32(0, 16) as usize,
+                );
+                let block = BlockId(rng.range_i32(0, kinds) as u16);
+
+                let old = section.set(x, y, z, block);
+                assert_eq!(
+                    old,
+                    std::mem::replace(&mut expected[Section::index(x, y, z)], block)
+                );
+            }
+
+            for (i, &block) in expected.iter().enumerate() {
+                assert_eq!(section.get_at(i), block);
+            }
+            let non_air = expected.iter().filter(|block| !block.is_air()).count();
+            assert_eq!(section.non_air as usize, non_air);
+        }
+    }
+
+    #[test]
+    fn palettes_grow_and_collapse() {
+        let mut section = Section::new();
+        assert_eq!(section.get_bits_per_block(), 0);
+
+        for i in 0..5 {
+            section.set(i, 0, 0, BlockId(i as u16 + 1));
+        }
+        assert_eq!(section.get_bits_per_block(), 3);
+        assert_eq!(section.palette().count(), 6);
+
+        // back down to two blocks, one bit is enough again
+        for i in 1..5 {
+            section.set(i, 0, 0, BlockId::AIR);
+        }
+        assert_eq!(section.get_bits_per_block(), 1);
+
+        section.set(0, 0, 0, BlockId::AIR);
+        assert_eq!(section.get_bits_per_block(), 0);
+        assert!(section.is_empty());
+
+        let stone = BlockId(3);
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        for (x, y, z) in
+            (0..16).flat_map(|y| (0..16).flat_map(move |z| (0..16).map(move |x| (x, y, z))))
+        {
+            chunk.set_block(x, y, z, stone);
+        }
+        let section = chunk.get_section(4).unwrap();
+        assert_eq!(section.get_bits_per_block(), 0);
+        assert_eq!(section.palette().collect::<Vec<_>>(), [stone]);
+        assert_eq!(chunk.blocks().count(), SECTION_VOLUME);
+    }
+}
@@ -0,0 +1,93 @@
+/// Game ticks in a day. The sun rises at tick 0, is highest at a quarter of the day and
+/// sets halfway through.
+pub const TICKS_PER_DAY: u64 = 24_000;
+/// Game ticks per second of real time.
+pub const TICKS_PER_SECOND: f64 = 20.0;
+
+/// World time in game ticks, counting on with real time unless frozen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldClock {
+    /// Fractional, so frames shorter than a tick still move the sun.
+    ticks: f64,
+    frozen: bool,
+}
+
+impl WorldClock {
+    pub fn new(ticks: u64) -> Self {
+        Self {
+            ticks: ticks as f64,
+            frozen: false,
+        }
+    }
+
+    /// Moves time on by `dt` seconds, unless it's frozen.
+    pub fn advance(&mut self, dt: f32) {
+        if !self.frozen {
+            self.ticks += dt as f64 * TICKS_PER_SECOND;
+        }
+    }
+
+    /// Ticks since the world was created.
+    pub fn get_time(&self) -> u64 {
+        self.ticks as u64
+    }
+
+    /// Ticks since the last sunrise.
+    pub fn get_time_of_day(&self) -> u64 {
+        self.get_time() % TICKS_PER_DAY
+    }
+
+    /// How far through the day it is, `0..1` from sunrise to sunrise.
+    pub fn get_day_fraction(&self) -> f32 {
+        (self.ticks.rem_euclid(TICKS_PER_DAY as f64) / TICKS_PER_DAY as f64) as f32
+    }
+
+    pub fn set_time(&mut self, ticks: u64) {
+        self.ticks = ticks as f64;
+    }
+
+    /// Moves time on to the next time the day is `tick` ticks past sunrise, so days keep
+    /// counting up.
+    pub fn set_time_of_day(&mut self, tick: u64) {
+        let time = self.get_time();
+        let today = time - time % TICKS_PER_DAY + tick % TICKS_PER_DAY;
+        self.set_time(if today > time {
+            today
+        } else {
+            today + TICKS_PER_DAY
+        });
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Frozen time stays where it is until it's set or unfrozen.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_counts_on_unless_frozen() {
+        let mut clock = WorldClock::new(TICKS_PER_DAY - 10);
+        clock.advance(1.0);
+        assert_eq!(clock.get_time(), TICKS_PER_DAY + 10);
+        assert_eq!(clock.get_time_of_day(), 10);
+
+        clock.set_frozen(true);
+        clock.advance(60.0);
+        assert_eq!(clock.get_time(), TICKS_PER_DAY + 10);
+
+        // setting the time of day never goes back to an earlier day
+        clock.set_time_of_day(6000);
+        assert_eq!(clock.get_time(), TICKS_PER_DAY + 6000);
+        clock.set_time_of_day(0);
+        assert_eq!(clock.get_time(), 2 * TICKS_PER_DAY);
+        assert_eq!(clock.get_day_fraction(), 0.0);
+    }
+}
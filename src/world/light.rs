@@ -0,0 +1,111 @@
+use super::{CHUNK_SIZE, SECTION_COUNT};
+
+const SECTION_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Which light a level belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightKind {
+    /// Light coming down from the open sky.
+    Sky,
+    /// Light given off by blocks like torches.
+    Block,
+}
+
+/// Sky and block light levels of a cell, each `0..=15`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Light(u8);
+
+impl Light {
+    pub const MAX: u8 = 15;
+    /// No light at all.
+    pub const DARK: Self = Self(0);
+    /// Open sky, cells are lit like this until their light is worked out.
+    pub const SKY: Self = Self(Self::MAX << 4);
+
+    pub fn new(sky: u8, block: u8) -> Self {
+        Self(sky.min(Self::MAX) << 4 | block.min(Self::MAX))
+    }
+
+    pub fn get_sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub fn get_block(self) -> u8 {
+        self.0 & Self::MAX
+    }
+
+    pub fn get(self, kind: LightKind) -> u8 {
+        match kind {
+            LightKind::Sky => self.get_sky(),
+            LightKind::Block => self.get_block(),
+        }
+    }
+
+    pub fn with(self, kind: LightKind, level: u8) -> Self {
+        match kind {
+            LightKind::Sky => Self::new(level, self.get_block()),
+            LightKind::Block => Self::new(self.get_sky(), level),
+        }
+    }
+
+    /// The brighter of both levels, each on its own.
+    pub fn max(self, other: Self) -> Self {
+        Self::new(
+            self.get_sky().max(other.get_sky()),
+            self.get_block().max(other.get_block()),
+        )
+    }
+}
+
+/// Light of every cell of a chunk, section by section. Sections whose cells are all lit
+/// the same keep a single value.
+#[derive(Debug, Clone)]
+pub(super) struct ChunkLight {
+    sections: [SectionLight; SECTION_COUNT],
+}
+
+#[derive(Debug, Clone)]
+enum SectionLight {
+    Uniform(Light),
+    /// YZX ordered like blocks.
+    Cells(Box<[Light; SECTION_VOLUME]>),
+}
+
+impl ChunkLight {
+    pub fn new(light: Light) -> Self {
+        Self {
+            sections: std::array::from_fn(|_| SectionLight::Uniform(light)),
+        }
+    }
+
+    pub fn get(&self, section: usize, i: usize) -> Light {
+        match &self.sections[section] {
+            SectionLight::Uniform(light) => *light,
+            SectionLight::Cells(cells) => cells[i],
+        }
+    }
+
+    pub fn set(&mut self, section: usize, i: usize, light: Light) {
+        match &mut self.sections[section] {
+            SectionLight::Uniform(uniform) if *uniform == light => {}
+            SectionLight::Uniform(uniform) => {
+                let mut cells = Box::new([*uniform; SECTION_VOLUME]);
+                cells[i] = light;
+                self.sections[section] = SectionLight::Cells(cells);
+            }
+            SectionLight::Cells(cells) => cells[i] = light,
+        }
+    }
+
+    /// Goes back to a single value for sections whose cells are all lit the same.
+    pub fn compact(&mut self) {
+        for section in &mut self.sections {
+            if let SectionLight::Cells(cells) = section {
+                let first = cells[0];
+                if cells.iter().all(|&light| light == first) {
+                    *section = SectionLight::Uniform(first);
+                }
+            }
+        }
+    }
+}
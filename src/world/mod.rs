@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod chunk;
+mod clock;
+mod light;
+mod raycast;
+
+pub use chunk::*;
+pub use clock::*;
+pub use light::{Light, LightKind};
+pub use raycast::*;
+
+/// Numeric id of a block type, `0` is always air.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    pub const AIR: Self = Self(0);
+
+    pub fn is_air(&self) -> bool {
+        *self == Self::AIR
+    }
+}
+
+/// All loaded chunks, the single source of truth for block data.
+/// Chunks are shared copy-on-write, so snapshots for other threads are cheap.
+#[derive(Debug, Default, Clone)]
+pub struct World {
+    chunks: HashMap<ChunkPos, Arc<Chunk>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Air is returned for positions inside unloaded chunks.
+    pub fn get_block(&self, pos: glam::IVec3) -> BlockId {
+        let Some(chunk) = self.chunks.get(&ChunkPos::from_block(pos)) else {
+            return BlockId::AIR;
+        };
+
+        let (x, z) = Self::local_xz(pos);
+        chunk.get_block(x, pos.y, z)
+    }
+
+    /// Loads an empty chunk if the position isn't loaded yet.
+    /// Returns the block that was previously stored at the position.
+    pub fn set_block(&mut self, pos: glam::IVec3, block: BlockId) -> BlockId {
+        let chunk_pos = ChunkPos::from_block(pos);
+        let chunk = self
+            .chunks
+            .entry(chunk_pos)
+            .or_insert_with(|| Arc::new(Chunk::new(chunk_pos)));
+
+        let (x, z) = Self::local_xz(pos);
+        Arc::make_mut(chunk).set_block(x, pos.y, z, block)
+    }
+
+    /// Open sky is returned for positions inside unloaded chunks.
+    pub fn get_light(&self, pos: glam::IVec3) -> Light {
+        let Some(chunk) = self.chunks.get(&ChunkPos::from_block(pos)) else {
+            return Light::SKY;
+        };
+
+        let (x, z) = Self::local_xz(pos);
+        chunk.get_light(x, pos.y, z)
+    }
+
+    /// Writes to unloaded chunks are ignored.
+    pub fn set_light(&mut self, pos: glam::IVec3, light: Light) {
+        if let Some(chunk) = self.get_chunk_mut(ChunkPos::from_block(pos)) {
+            let (x, z) = Self::local_xz(pos);
+            chunk.set_light(x, pos.y, z, light);
+        }
+    }
+
+    pub fn get_chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos).map(Arc::as_ref)
+    }
+
+    pub fn get_chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos).map(Arc::make_mut)
+    }
+
+    /// Returns the chunk that was previously loaded at the same position.
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+        self.chunks
+            .insert(chunk.get_position(), Arc::new(chunk))
+            .map(Arc::unwrap_or_clone)
+    }
+
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        self.chunks.remove(&pos).map(Arc::unwrap_or_clone)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values().map(Arc::as_ref)
+    }
+
+    /// A world holding only the chunk and the loaded chunks surrounding it,
+    /// enough to mesh the chunk away from the main thread.
+    pub fn neighbourhood(&self, pos: ChunkPos) -> World {
+        let mut chunks = HashMap::new();
+
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                let neighbour = ChunkPos::new(pos.x + dx, pos.z + dz);
+                if let Some(chunk) = self.chunks.get(&neighbour) {
+                    chunks.insert(neighbour, Arc::clone(chunk));
+                }
+            }
+        }
+
+        World { chunks }
+    }
+
+    fn local_xz(pos: glam::IVec3) -> (i32, i32) {
+        (pos.x.rem_euclid(CHUNK_SIZE), pos.z.rem_euclid(CHUNK_SIZE))
+    }
+}
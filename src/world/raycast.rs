@@ -0,0 +1,144 @@
+use super::{BlockId, World};
+
+/// The first non-air block hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub position: glam::IVec3,
+    pub block: BlockId,
+    /// Normal of the face the ray entered through, zero if the ray started inside the block.
+    pub normal: glam::IVec3,
+    /// Distance along the ray to where it entered the block.
+    pub distance: f32,
+}
+
+impl World {
+    /// Walks the blocks along the ray one at a time, blocks occupy `pos..pos + 1`.
+    pub fn raycast(
+        &self,
+        origin: glam::Vec3,
+        direction: glam::Vec3,
+        max_distance: f32,
+    ) -> Option<RaycastHit> {
+        let direction = direction.try_normalize()?;
+
+        let mut position = origin.floor().as_ivec3();
+        let step = direction.signum().as_ivec3();
+
+        // distance along the ray between two block boundaries, per axis. Axes the ray
+        // doesn't move along are never stepped.
+        let delta = glam::Vec3::from_array(std::array::from_fn(|axis| {
+            if direction[axis] == 0.0 {
+                f32::INFINITY
+            } else {
+                direction[axis].recip().abs()
+            }
+        }));
+
+        // distance along the ray to the first block boundary, per axis
+        let mut next = glam::Vec3::from_array(std::array::from_fn(|axis| {
+            if direction[axis] == 0.0 {
+                return f32::INFINITY;
+            }
+
+            let fract = origin[axis] - origin[axis].floor();
+            let to_boundary = if direction[axis] > 0.0 {
+                1.0 - fract
+            } else {
+                fract
+            };
+            to_boundary * delta[axis]
+        }));
+
+        let mut normal = glam::IVec3::ZERO;
+        let mut distance = 0.0;
+
+        loop {
+            let block = self.get_block(position);
+            if !block.is_air() {
+                return Some(RaycastHit {
+                    position,
+                    block,
+                    normal,
+                    distance,
+                });
+            }
+
+            let axis = next.min_position();
+            if next[axis] > max_distance {
+                return None;
+            }
+
+            position[axis] += step[axis];
+            distance = next[axis];
+            next[axis] += delta[axis];
+
+            normal = glam::IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, vec3};
+
+    use super::*;
+
+    const STONE: BlockId = BlockId(1);
+
+    #[test]
+    fn axis_aligned_rays_starting_on_a_boundary_hit() {
+        let mut world = World::new();
+        world.set_block(ivec3(0, -3, 0), STONE);
+
+        // x and z sit on block boundaries and the ray doesn't move along them
+        let hit = world
+            .raycast(vec3(0.0, 0.0, 0.5), vec3(0.0, -1.0, 0.0), 8.0)
+            .unwrap();
+        assert_eq!(hit.position, ivec3(0, -3, 0));
+        assert_eq!(hit.normal, ivec3(0, 1, 0));
+        assert_eq!(hit.distance, 2.0);
+
+        assert_eq!(
+            world.raycast(vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 8.0),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_report_the_face_the_ray_entered_through() {
+        let mut world = World::new();
+        world.set_block(ivec3(3, 0, 0), STONE);
+        world.set_block(ivec3(0, 0, -3), STONE);
+
+        let hit = world
+            .raycast(vec3(0.5, 0.5, 0.5), vec3(1.0, 0.2, 0.1), 8.0)
+            .unwrap();
+        assert_eq!(hit.position, ivec3(3, 0, 0));
+        assert_eq!(hit.normal, ivec3(-1, 0, 0));
+
+        let hit = world
+            .raycast(vec3(0.5, 0.5, 0.5), vec3(0.0, 0.0, -1.0), 8.0)
+            .unwrap();
+        assert_eq!(hit.normal, ivec3(0, 0, 1));
+        assert_eq!(hit.distance, 2.5);
+
+        // starting inside a block hits it straight away
+        let hit = world
+            .raycast(vec3(3.5, 0.5, 0.5), vec3(0.0, 1.0, 0.0), 8.0)
+            .unwrap();
+        assert_eq!(hit.normal, glam::IVec3::ZERO);
+        assert_eq!(hit.distance, 0.0);
+    }
+
+    #[test]
+    fn blocks_out_of_reach_are_not_hit() {
+        let mut world = World::new();
+        world.set_block(ivec3(10, 0, 0), STONE);
+
+        let ray = (vec3(0.5, 0.5, 0.5), vec3(1.0, 0.0, 0.0));
+        assert_eq!(world.raycast(ray.0, ray.1, 9.0), None);
+        assert_eq!(world.raycast(ray.0, ray.1, 9.5).unwrap().distance, 9.5);
+        assert_eq!(world.raycast(ray.0, vec3(0.0, 0.0, 0.0), 9.5), None);
+    }
+}
@@ -0,0 +1,247 @@
+use super::noise::{mix, Fbm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Plains,
+    Desert,
+    Forest,
+    Mountains,
+    Snow,
+}
+
+/// How a biome shapes and decorates its columns.
+#[derive(Debug, Clone)]
+pub struct BiomeSettings {
+    pub name: &'static str,
+    /// Temperature and humidity the biome is found at, both in `-1.0..=1.0`.
+    pub climate: (f64, f64),
+
+    /// Height the terrain varies around.
+    pub base_height: f64,
+    /// How far the terrain reaches above and below `base_height`.
+    pub height_variation: f64,
+
+    /// Block names, resolved through the block registry.
+    pub surface: &'static str,
+    pub filler: &'static str,
+    /// Surface block of columns below sea level.
+    pub underwater: &'static str,
+
+    /// Chance of a tree growing on a surface block.
+    pub tree_density: f64,
+    /// Chance of a flower growing on a surface block.
+    pub flower_density: f64,
+    /// Chance of tall grass growing on a surface block.
+    pub grass_density: f64,
+
+    /// Colour of the biome on maps.
+    pub color: [u8; 3],
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [
+        Biome::Ocean,
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Forest,
+        Biome::Mountains,
+        Biome::Snow,
+    ];
+
+    pub fn settings(self) -> &'static BiomeSettings {
+        &BIOMES[self as usize]
+    }
+}
+
+/// Temperature and humidity at a column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+}
+
+/// Low frequency temperature and humidity noise, picking the biome of every column.
+#[derive(Debug, Clone)]
+pub struct ClimateMap {
+    temperature: Fbm,
+    humidity: Fbm,
+    frequency: f64,
+    blend: f64,
+}
+
+impl ClimateMap {
+    /// Biomes are roughly `1 / frequency` blocks across, biomes whose climate is
+    /// within `blend` of the closest one get mixed into its terrain.
+    pub fn new(seed: u64, frequency: f64, blend: f64) -> Self {
+        Self {
+            temperature: Fbm::new(mix(seed, 1), 3, 0.5, 2.0),
+            humidity: Fbm::new(mix(seed, 2), 3, 0.5, 2.0),
+            frequency,
+            blend,
+        }
+    }
+
+    pub fn get_climate(&self, x: i32, z: i32) -> Climate {
+        let (x, z) = (x as f64 * self.frequency, z as f64 * self.frequency);
+
+        // fbm rarely leaves -0.5..0.5, stretch it so every biome shows up
+        Climate {
+            temperature: (self.temperature.get2(x, z) * 3.0).clamp(-1.0, 1.0),
+            humidity: (self.humidity.get2(x, z) * 3.0).clamp(-1.0, 1.0),
+        }
+    }
+
+    /// The biome whose climate is closest to the column's.
+    pub fn get_biome(&self, x: i32, z: i32) -> Biome {
+        let distances = self.distances(self.get_climate(x, z));
+
+        Biome::ALL
+            .into_iter()
+            .zip(distances)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0
+    }
+
+    /// Share of each biome in the column, indexed like `Biome::ALL` and summing up to 1.
+    /// Changes smoothly across biome borders.
+    pub fn get_weights(&self, x: i32, z: i32) -> [f64; 6] {
+        let distances = self.distances(self.get_climate(x, z));
+        let closest = distances.iter().copied().fold(f64::INFINITY, f64::min);
+
+        let mut weights = distances.map(|d| {
+            let w = (1.0 - (d - closest) / self.blend).max(0.0);
+            w * w
+        });
+
+        let total: f64 = weights.iter().sum();
+        weights.iter_mut().for_each(|w| *w /= total);
+        weights
+    }
+
+    fn distances(&self, climate: Climate) -> [f64; 6] {
+        Biome::ALL.map(|biome| {
+            let (t, h) = biome.settings().climate;
+            let (dt, dh) = (climate.temperature - t, climate.humidity - h);
+            (dt * dt + dh * dh).sqrt()
+        })
+    }
+}
+
+const BIOMES: [BiomeSettings; 6] = [
+    BiomeSettings {
+        name: "ocean",
+        climate: (0.2, 0.9),
+        base_height: 42.0,
+        height_variation: 12.0,
+        surface: "sand",
+        filler: "sand",
+        underwater: "sand",
+        tree_density: 0.0,
+        flower_density: 0.0,
+        grass_density: 0.0,
+        color: [40, 70, 170],
+    },
+    BiomeSettings {
+        name: "plains",
+        climate: (0.2, 0.0),
+        base_height: 66.0,
+        height_variation: 8.0,
+        surface: "grass",
+        filler: "dirt",
+        underwater: "sand",
+        tree_density: 0.002,
+        flower_density: 0.02,
+        grass_density: 0.15,
+        color: [120, 180, 80],
+    },
+    BiomeSettings {
+        name: "desert",
+        climate: (0.8, -0.7),
+        base_height: 67.0,
+        height_variation: 10.0,
+        surface: "sand",
+        filler: "sand",
+        underwater: "sand",
+        tree_density: 0.0,
+        flower_density: 0.0,
+        grass_density: 0.0,
+        color: [220, 200, 120],
+    },
+    BiomeSettings {
+        name: "forest",
+        climate: (0.3, 0.45),
+        base_height: 68.0,
+        height_variation: 14.0,
+        surface: "grass",
+        filler: "dirt",
+        underwater: "sand",
+        tree_density: 0.04,
+        flower_density: 0.01,
+        grass_density: 0.1,
+        color: [40, 110, 40],
+    },
+    BiomeSettings {
+        name: "mountains",
+        climate: (-0.3, -0.5),
+        base_height: 92.0,
+        height_variation: 56.0,
+        surface: "stone",
+        filler: "stone",
+        underwater: "gravel",
+        tree_density: 0.0,
+        flower_density: 0.0,
+        grass_density: 0.0,
+        color: [130, 130, 130],
+    },
+    BiomeSettings {
+        name: "snow",
+        climate: (-0.8, 0.2),
+        base_height: 70.0,
+        height_variation: 18.0,
+        surface: "snow",
+        filler: "dirt",
+        underwater: "gravel",
+        tree_density: 0.005,
+        flower_density: 0.0,
+        grass_density: 0.0,
+        color: [235, 240, 250],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_sum_up_to_one() {
+        let climate = ClimateMap::new(11, 1.0 / 512.0, 0.25);
+
+        for x in (-2000..2000).step_by(37) {
+            for z in (-2000..2000).step_by(41) {
+                let weights = climate.get_weights(x, z);
+                let total: f64 = weights.iter().sum();
+                assert!((total - 1.0).abs() < 1e-9);
+
+                // the biome of the column always has the largest share
+                let biome = climate.get_biome(x, z);
+                assert!(weights.iter().all(|&w| w <= weights[biome as usize]));
+            }
+        }
+    }
+
+    #[test]
+    fn every_biome_shows_up() {
+        let climate = ClimateMap::new(11, 1.0 / 512.0, 0.25);
+        let mut found = [false; 6];
+
+        for x in (-20000..20000).step_by(64) {
+            for z in (-20000..20000).step_by(64) {
+                found[climate.get_biome(x, z) as usize] = true;
+            }
+        }
+
+        assert_eq!(found, [true; 6]);
+    }
+}
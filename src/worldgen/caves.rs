@@ -0,0 +1,289 @@
+use super::noise::{self, mix, Perlin, Rng};
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MIN_Y};
+
+/// Where and how often underground spaces are carved out.
+#[derive(Debug, Clone)]
+pub struct CaveSettings {
+    /// Lowest y coordinate caves are carved at.
+    pub min_y: i32,
+    /// Highest y coordinate caves start at, worms may wander a bit higher.
+    pub max_y: i32,
+
+    /// Frequency of the noise tunnels, `0.0` disables them.
+    pub tunnel_frequency: f64,
+    /// Width of the noise tunnels, in noise units.
+    pub tunnel_width: f64,
+    /// Noise tunnels keep at least this many blocks below the surface.
+    pub tunnel_cover: i32,
+
+    /// Average number of worm caves starting in each chunk.
+    pub worms_per_chunk: f64,
+    /// Steps of one block each.
+    pub worm_length: u32,
+    pub worm_radius: f64,
+
+    /// Chance of a chunk starting a ravine.
+    pub ravine_chance: f64,
+    pub ravine_length: u32,
+    pub ravine_radius: f64,
+    /// Ravines are this many times taller than they are wide.
+    pub ravine_height_scale: f64,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            min_y: MIN_Y + 5,
+            max_y: 60,
+
+            tunnel_frequency: 1.0 / 48.0,
+            tunnel_width: 0.07,
+            tunnel_cover: 6,
+
+            worms_per_chunk: 0.6,
+            worm_length: 96,
+            worm_radius: 2.2,
+
+            ravine_chance: 0.02,
+            ravine_length: 80,
+            ravine_radius: 2.5,
+            ravine_height_scale: 3.5,
+        }
+    }
+}
+
+impl CaveSettings {
+    /// No caves at all.
+    pub fn disabled() -> Self {
+        Self {
+            tunnel_frequency: 0.0,
+            worms_per_chunk: 0.0,
+            ravine_chance: 0.0,
+            ..Self::default()
+        }
+    }
+}
+
+/// Carves noise tunnels and worm shaped caves and ravines out of generated terrain.
+/// Worms are simulated from the chunk they start in, so one crossing chunk borders
+/// is carved the same way whichever chunk is generated first.
+#[derive(Debug, Clone)]
+pub struct CaveCarver {
+    settings: CaveSettings,
+    seed: u64,
+    tunnel_noise: [Perlin; 2],
+}
+
+struct Worm {
+    length: u32,
+    radius: f64,
+    height_scale: f64,
+    /// Largest change of the pitch per step.
+    pitch_wobble: f64,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u64, settings: CaveSettings) -> Self {
+        Self {
+            settings,
+            seed,
+            tunnel_noise: [Perlin::new(mix(seed, 1)), Perlin::new(mix(seed, 2))],
+        }
+    }
+
+    /// `heights` holds the surface height of every column of the chunk, indexed `[x][z]`.
+    /// Water, and blocks right under water, are left alone so oceans don't drain.
+    pub fn carve(&self, chunk: &mut Chunk, heights: &[[i32; 16]; 16], water: BlockId) {
+        self.carve_tunnels(chunk, heights, water);
+        self.carve_worms(chunk, water);
+    }
+
+    fn carve_tunnels(&self, chunk: &mut Chunk, heights: &[[i32; 16]; 16], water: BlockId) {
+        let f = self.settings.tunnel_frequency;
+        if f <= 0.0 {
+            return;
+        }
+
+        let origin = chunk.get_position().origin();
+        let width = self.settings.tunnel_width;
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let top = (heights[x as usize][z as usize] - self.settings.tunnel_cover)
+                    .min(self.settings.max_y);
+
+                for y in self.settings.min_y..=top {
+                    let (wx, wz) = ((origin.x + x) as f64 * f, (origin.z + z) as f64 * f);
+
+                    // tunnels follow the lines where two noise fields both cross zero
+                    let a = self.tunnel_noise[0].get3(wx, y as f64 * f * 2.0, wz);
+                    if a.abs() > width {
+                        continue;
+                    }
+
+                    let b = self.tunnel_noise[1].get3(wx, y as f64 * f * 2.0, wz);
+                    if a * a + b * b < width * width {
+                        carve_block(chunk, x, y, z, water);
+                    }
+                }
+            }
+        }
+    }
+
+    fn carve_worms(&self, chunk: &mut Chunk, water: BlockId) {
+        let pos = chunk.get_position();
+        let settings = &self.settings;
+
+        let worm = Worm {
+            length: settings.worm_length,
+            radius: settings.worm_radius,
+            height_scale: 1.0,
+            pitch_wobble: 0.25,
+        };
+
+        let ravine = Worm {
+            length: settings.ravine_length,
+            radius: settings.ravine_radius,
+            height_scale: settings.ravine_height_scale,
+            pitch_wobble: 0.05,
+        };
+
+        // chunks around this one whose worms may reach into it
+        let longest = settings.worm_length.max(settings.ravine_length) as f64 * 1.25;
+        let widest = settings.worm_radius.max(settings.ravine_radius) * 1.4 * 1.5;
+        let range = ((longest + widest) / CHUNK_SIZE as f64).ceil() as i32;
+
+        for dx in -range..=range {
+            for dz in -range..=range {
+                let source = ChunkPos::new(pos.x + dx, pos.z + dz);
+                let source_seed = mix(self.seed, noise::hash(self.seed, source.x, 0, source.z));
+                let mut rng = Rng::new(source_seed);
+
+                // whole worms per chunk, plus one more with the chance of the fraction
+                let average = settings.worms_per_chunk.max(0.0);
+                let count = average.floor() as u64 + (rng.next_f64() < average.fract()) as u64;
+
+                for i in 0..count {
+                    let mut rng = Rng::new(mix(source_seed, i + 1));
+                    self.run_worm(chunk, source, &worm, &mut rng, water);
+                }
+
+                if rng.next_f64() < settings.ravine_chance {
+                    let mut rng = Rng::new(mix(source_seed, 0));
+                    self.run_worm(chunk, source, &ravine, &mut rng, water);
+                }
+            }
+        }
+    }
+
+    /// Walks the worm from its start in `source`, carving the parts inside `chunk`.
+    fn run_worm(
+        &self,
+        chunk: &mut Chunk,
+        source: ChunkPos,
+        worm: &Worm,
+        rng: &mut Rng,
+        water: BlockId,
+    ) {
+        use std::f64::consts::{PI, TAU};
+
+        let settings = &self.settings;
+        if settings.max_y <= settings.min_y {
+            return;
+        }
+
+        let origin = source.origin();
+        let mut position = glam::dvec3(
+            origin.x as f64 + rng.range(0.0, 16.0),
+            rng.range_i32(settings.min_y, settings.max_y) as f64,
+            origin.z as f64 + rng.range(0.0, 16.0),
+        );
+
+        let length = (worm.length as f64 * rng.range(0.75, 1.25)) as u32;
+        let radius = worm.radius * rng.range(0.6, 1.4);
+
+        // skip the walk entirely when the worm can't reach the chunk
+        let target = chunk.get_position().origin();
+        let center = glam::dvec2(target.x as f64 + 8.0, target.z as f64 + 8.0);
+        let reach = length as f64 + radius * 2.0 + CHUNK_SIZE as f64;
+        if glam::dvec2(position.x, position.z).distance(center) > reach {
+            return;
+        }
+
+        let mut yaw = rng.range(0.0, TAU);
+        let mut pitch = rng.range(-0.25, 0.25);
+        let (mut yaw_change, mut pitch_change) = (0.0, 0.0);
+
+        for step in 0..length {
+            // thin at both ends, widest in the middle
+            let t = step as f64 / length as f64;
+            let r = radius * (0.5 + noise::sin(PI * t));
+
+            self.carve_ellipsoid(chunk, position, r, r * worm.height_scale, water);
+
+            let horizontal = noise::cos(pitch);
+            position += glam::dvec3(
+                noise::cos(yaw) * horizontal,
+                noise::sin(pitch),
+                noise::sin(yaw) * horizontal,
+            );
+
+            yaw += yaw_change;
+            pitch = pitch * 0.8 + pitch_change;
+            yaw_change = yaw_change * 0.75 + rng.range(-0.25, 0.25);
+            pitch_change = pitch_change * 0.75 + rng.range(-worm.pitch_wobble, worm.pitch_wobble);
+        }
+    }
+
+    fn carve_ellipsoid(
+        &self,
+        chunk: &mut Chunk,
+        center: glam::DVec3,
+        radius: f64,
+        height: f64,
+        water: BlockId,
+    ) {
+        let origin = chunk.get_position().origin();
+
+        let min = (center - glam::dvec3(radius, height, radius))
+            .floor()
+            .as_ivec3();
+        let max = (center + glam::dvec3(radius, height, radius))
+            .ceil()
+            .as_ivec3();
+
+        let min_x = (min.x - origin.x).max(0);
+        let max_x = (max.x - origin.x).min(CHUNK_SIZE - 1);
+        let min_z = (min.z - origin.z).max(0);
+        let max_z = (max.z - origin.z).min(CHUNK_SIZE - 1);
+        if min_x > max_x || min_z > max_z {
+            return;
+        }
+
+        let min_y = min.y.max(self.settings.min_y);
+
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                for y in min_y..=max.y {
+                    let d = (glam::dvec3((origin.x + x) as f64, y as f64, (origin.z + z) as f64)
+                        + 0.5
+                        - center)
+                        / glam::dvec3(radius, height, radius);
+
+                    if d.length_squared() < 1.0 {
+                        carve_block(chunk, x, y, z, water);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn carve_block(chunk: &mut Chunk, x: i32, y: i32, z: i32, water: BlockId) {
+    let block = chunk.get_block(x, y, z);
+    if block.is_air() || block == water || chunk.get_block(x, y + 1, z) == water {
+        return;
+    }
+
+    chunk.set_block(x, y, z, BlockId::AIR);
+}
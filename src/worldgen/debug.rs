@@ -0,0 +1,84 @@
+use glam::{ivec3, IVec3};
+
+use super::generator::Generator;
+use crate::block::BlockRegistry;
+use crate::world::{BlockId, Chunk, ChunkPos};
+
+/// Every state of every registered block on its own, laid out on a grid to inspect
+/// the textures.
+#[derive(Debug, Clone)]
+pub struct DebugGenerator {
+    blocks: Vec<BlockId>,
+    /// Blocks along one side of the grid.
+    width: i32,
+}
+
+impl DebugGenerator {
+    /// Height the grid floats at.
+    pub const GRID_Y: i32 = 70;
+    /// Distance between neighbouring blocks, leaving a gap so every face shows.
+    pub const SPACING: i32 = 2;
+
+    pub fn new(registry: &BlockRegistry) -> Self {
+        let blocks: Vec<_> = registry.states().filter(|id| !id.is_air()).collect();
+
+        let width = (blocks.len() as f64).sqrt().ceil().max(1.0) as i32;
+        Self { blocks, width }
+    }
+
+    /// Where the `index`th block of the grid is placed.
+    pub fn get_position(&self, index: usize) -> IVec3 {
+        let index = index as i32;
+        ivec3(
+            index % self.width * Self::SPACING,
+            Self::GRID_Y,
+            index / self.width * Self::SPACING,
+        )
+    }
+}
+
+impl Generator for DebugGenerator {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos.origin();
+
+        for (i, &block) in self.blocks.iter().enumerate() {
+            let position = self.get_position(i);
+            if ChunkPos::from_block(position) == pos {
+                chunk.set_block(
+                    position.x - origin.x,
+                    position.y,
+                    position.z - origin.z,
+                    block,
+                );
+            }
+        }
+
+        chunk
+    }
+
+    fn get_spawn(&self) -> IVec3 {
+        ivec3(-Self::SPACING, Self::GRID_Y + 1, -Self::SPACING)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_state_shows_up_once() {
+        let registry = BlockRegistry::from_file("res/blocks.json").unwrap();
+        let generator = DebugGenerator::new(&registry);
+
+        let mut found: Vec<_> = (-1..4)
+            .flat_map(|x| (-1..4).map(move |z| ChunkPos::new(x, z)))
+            .flat_map(|pos| generator.generate(pos).blocks().collect::<Vec<_>>())
+            .map(|(_, block)| block)
+            .collect();
+        found.sort_by_key(|block| block.0);
+
+        let expected: Vec<_> = registry.states().skip(1).collect();
+        assert_eq!(found, expected);
+    }
+}
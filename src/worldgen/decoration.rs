@@ -0,0 +1,683 @@
+use glam::{ivec3, IVec3};
+
+use super::biome::Biome;
+use super::noise::{self, mix, Rng};
+use super::terrain::TerrainGenerator;
+use crate::block::BlockRegistry;
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MAX_Y, MIN_Y};
+
+/// A kind of ore and where its veins are found.
+#[derive(Debug, Clone)]
+pub struct OreSettings {
+    pub block: &'static str,
+    /// Veins start between these y coordinates.
+    pub min_y: i32,
+    pub max_y: i32,
+    /// Average number of veins starting in each chunk.
+    pub veins_per_chunk: f64,
+    /// Most blocks in one vein.
+    pub vein_size: u32,
+}
+
+/// What gets placed on and in the terrain after it is shaped and carved.
+#[derive(Debug, Clone)]
+pub struct DecorationSettings {
+    /// Multiplies the tree, flower and grass density of every biome, `0.0` disables them.
+    pub vegetation: f64,
+    pub ores: Vec<OreSettings>,
+    /// Chance of a chunk holding a small structure.
+    pub structure_chance: f64,
+}
+
+impl Default for DecorationSettings {
+    fn default() -> Self {
+        Self {
+            vegetation: 1.0,
+            ores: vec![
+                OreSettings {
+                    block: "coal_ore",
+                    min_y: 0,
+                    max_y: 128,
+                    veins_per_chunk: 12.0,
+                    vein_size: 12,
+                },
+                OreSettings {
+                    block: "iron_ore",
+                    min_y: -24,
+                    max_y: 64,
+                    veins_per_chunk: 8.0,
+                    vein_size: 8,
+                },
+                OreSettings {
+                    block: "gold_ore",
+                    min_y: MIN_Y,
+                    max_y: 32,
+                    veins_per_chunk: 3.0,
+                    vein_size: 7,
+                },
+                OreSettings {
+                    block: "diamond_ore",
+                    min_y: MIN_Y,
+                    max_y: 16,
+                    veins_per_chunk: 1.5,
+                    vein_size: 6,
+                },
+            ],
+            structure_chance: 1.0 / 48.0,
+        }
+    }
+}
+
+impl DecorationSettings {
+    /// Bare terrain.
+    pub fn disabled() -> Self {
+        Self {
+            vegetation: 0.0,
+            ores: Vec::new(),
+            structure_chance: 0.0,
+        }
+    }
+}
+
+/// Which blocks a placement may overwrite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replace {
+    Air,
+    /// Air, leaves and plants, so trunks grow through the canopy of other trees.
+    Plants,
+    Stone,
+    Any,
+}
+
+/// One block a decoration wants to place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub position: IVec3,
+    pub block: BlockId,
+    pub replace: Replace,
+}
+
+/// Places ores, trees, plants and structures.
+///
+/// Every decoration belongs to the chunk it starts in and is worked out from nothing but
+/// the seed and the pure terrain functions, never from generated blocks. A chunk applies
+/// the parts of its neighbours' decorations reaching into it, so a tree crossing a border
+/// ends up whole whichever side is generated first.
+#[derive(Debug, Clone)]
+pub struct Decorator {
+    settings: DecorationSettings,
+    seed: u64,
+    ores: Vec<BlockId>,
+
+    stone: BlockId,
+    water: BlockId,
+    log: BlockId,
+    leaves: BlockId,
+    tall_grass: BlockId,
+    flower: BlockId,
+    cobblestone: BlockId,
+}
+
+/// Blocks a tree or structure may reach away from the column it starts in.
+const FEATURE_REACH: i32 = 3;
+
+type Stage = fn(&Decorator, ChunkPos, &TerrainGenerator, &mut Vec<Placement>);
+
+impl Decorator {
+    pub fn new(seed: u64, registry: &BlockRegistry, settings: DecorationSettings) -> Self {
+        Self {
+            ores: settings
+                .ores
+                .iter()
+                .map(|ore| super::require(registry, ore.block))
+                .collect(),
+            settings,
+            seed,
+
+            stone: super::require(registry, "stone"),
+            water: super::require(registry, "water"),
+            log: super::require(registry, "log"),
+            leaves: super::require(registry, "leaves"),
+            tall_grass: super::require(registry, "tall_grass"),
+            flower: super::require(registry, "flower"),
+            cobblestone: super::require(registry, "cobblestone"),
+        }
+    }
+
+    pub fn decorate(&self, chunk: &mut Chunk, terrain: &TerrainGenerator) {
+        let pos = chunk.get_position();
+
+        let reach = self
+            .settings
+            .ores
+            .iter()
+            .map(|ore| ore.vein_size as i32)
+            .fold(FEATURE_REACH, i32::max);
+        let range = (reach + CHUNK_SIZE - 1) / CHUNK_SIZE;
+
+        let sources: Vec<_> = (-range..=range)
+            .flat_map(|dx| (-range..=range).map(move |dz| ChunkPos::new(pos.x + dx, pos.z + dz)))
+            .collect();
+
+        // stage by stage, so structures clear out the trees grown into them
+        let stages: [Stage; 3] = [
+            Self::place_ores,
+            Self::place_vegetation,
+            Self::place_structures,
+        ];
+
+        let mut placements = Vec::new();
+        for stage in stages {
+            for &source in &sources {
+                stage(self, source, terrain, &mut placements);
+            }
+        }
+
+        for placement in placements {
+            self.apply(chunk, placement);
+        }
+    }
+
+    /// Every block the decorations starting in `source` want to place, including the
+    /// ones reaching into neighbouring chunks.
+    pub fn get_placements(&self, source: ChunkPos, terrain: &TerrainGenerator) -> Vec<Placement> {
+        let mut placements = Vec::new();
+        self.place_ores(source, terrain, &mut placements);
+        self.place_vegetation(source, terrain, &mut placements);
+        self.place_structures(source, terrain, &mut placements);
+        placements
+    }
+
+    fn apply(&self, chunk: &mut Chunk, placement: Placement) {
+        let origin = chunk.get_position().origin();
+        let (x, y, z) = (
+            placement.position.x - origin.x,
+            placement.position.y,
+            placement.position.z - origin.z,
+        );
+
+        if !(0..CHUNK_SIZE).contains(&x)
+            || !(0..CHUNK_SIZE).contains(&z)
+            || !(MIN_Y..MAX_Y).contains(&y)
+        {
+            return;
+        }
+
+        let existing = chunk.get_block(x, y, z);
+        let replaceable = match placement.replace {
+            Replace::Air => existing.is_air(),
+            Replace::Plants => {
+                existing.is_air() || [self.leaves, self.tall_grass, self.flower].contains(&existing)
+            }
+            Replace::Stone => existing == self.stone,
+            Replace::Any => true,
+        };
+
+        if replaceable {
+            chunk.set_block(x, y, z, placement.block);
+        }
+    }
+
+    fn rng(&self, source: ChunkPos, stage: i32) -> Rng {
+        Rng::new(mix(
+            self.seed,
+            noise::hash(self.seed, source.x, stage, source.z),
+        ))
+    }
+
+    /// Random walks through stone, starting at depths depending on the ore.
+    fn place_ores(&self, source: ChunkPos, _: &TerrainGenerator, placements: &mut Vec<Placement>) {
+        let mut rng = self.rng(source, 0);
+        let origin = source.origin();
+
+        for (ore, &block) in self.settings.ores.iter().zip(&self.ores) {
+            if ore.max_y <= ore.min_y {
+                continue;
+            }
+
+            let average = ore.veins_per_chunk.max(0.0);
+            let count = average.floor() as u32 + (rng.next_f64() < average.fract()) as u32;
+
+            for _ in 0..count {
+                let mut position = ivec3(
+                    origin.x + rng.range_i32(0, CHUNK_SIZE),
+                    rng.range_i32(ore.min_y, ore.max_y),
+                    origin.z + rng.range_i32(0, CHUNK_SIZE),
+                );
+
+                let size = rng.range_i32(ore.vein_size as i32 / 2, ore.vein_size as i32 + 1);
+                for _ in 0..size {
+                    placements.push(Placement {
+                        position,
+                        block,
+                        replace: Replace::Stone,
+                    });
+
+                    position += ivec3(
+                        rng.range_i32(-1, 2),
+                        rng.range_i32(-1, 2),
+                        rng.range_i32(-1, 2),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Trees, flowers and tall grass on the dry surface, as dense as the biome asks for.
+    fn place_vegetation(
+        &self,
+        source: ChunkPos,
+        terrain: &TerrainGenerator,
+        placements: &mut Vec<Placement>,
+    ) {
+        let vegetation = self.settings.vegetation;
+        if vegetation <= 0.0 {
+            return;
+        }
+
+        let densest = Biome::ALL
+            .iter()
+            .map(|biome| {
+                let settings = biome.settings();
+                settings.tree_density + settings.flower_density + settings.grass_density
+            })
+            .fold(0.0, f64::max)
+            * vegetation;
+
+        let origin = source.origin();
+        let sea_level = terrain.get_settings().sea_level;
+
+        for x in origin.x..origin.x + CHUNK_SIZE {
+            for z in origin.z..origin.z + CHUNK_SIZE {
+                let mut rng = Rng::new(mix(self.seed, noise::hash(self.seed, x, 1, z)));
+
+                // most columns get nothing, skip the terrain lookups for them
+                let roll = rng.next_f64();
+                if roll >= densest {
+                    continue;
+                }
+
+                let biome = terrain.get_biome(x, z);
+                let settings = biome.settings();
+
+                let surface = terrain.get_surface(x, z);
+                if surface < sea_level {
+                    continue;
+                }
+
+                let ground = ivec3(x, surface + 1, z);
+                let trees = settings.tree_density * vegetation;
+                let flowers = trees + settings.flower_density * vegetation;
+                let grass = flowers + settings.grass_density * vegetation;
+
+                if roll < trees {
+                    match biome {
+                        Biome::Snow => self.place_spruce(ground, &mut rng, placements),
+                        _ => self.place_oak(ground, &mut rng, placements),
+                    }
+                } else if settings.surface == "grass" && roll < grass {
+                    placements.push(Placement {
+                        position: ground,
+                        block: if roll < flowers {
+                            self.flower
+                        } else {
+                            self.tall_grass
+                        },
+                        replace: Replace::Air,
+                    });
+                }
+            }
+        }
+    }
+
+    /// A short trunk under a round canopy two blocks wide.
+    fn place_oak(&self, ground: IVec3, rng: &mut Rng, placements: &mut Vec<Placement>) {
+        let height = rng.range_i32(4, 7);
+        let top = ground.y + height - 1;
+
+        for dy in -2..=1 {
+            let radius: i32 = if dy < 0 { 2 } else { 1 };
+
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    // round off the corners, some at random
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (dy == 1 || rng.next_f64() < 0.5) {
+                        continue;
+                    }
+
+                    placements.push(Placement {
+                        position: ivec3(ground.x + dx, top + dy, ground.z + dz),
+                        block: self.leaves,
+                        replace: Replace::Air,
+                    });
+                }
+            }
+        }
+
+        self.place_trunk(ground, height, placements);
+    }
+
+    /// A tall trunk under a cone of alternating wide and narrow layers.
+    fn place_spruce(&self, ground: IVec3, rng: &mut Rng, placements: &mut Vec<Placement>) {
+        let height = rng.range_i32(6, 10);
+        let top = ground.y + height;
+
+        for (i, y) in (ground.y + 2..=top).rev().enumerate() {
+            let radius = match i {
+                0 => 0,
+                _ if i % 2 == 1 => 1,
+                _ => 2.min(1 + i as i32 / 4),
+            };
+
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    if radius > 1 && dx.abs() == radius && dz.abs() == radius {
+                        continue;
+                    }
+
+                    placements.push(Placement {
+                        position: ivec3(ground.x + dx, y, ground.z + dz),
+                        block: self.leaves,
+                        replace: Replace::Air,
+                    });
+                }
+            }
+        }
+
+        self.place_trunk(ground, height, placements);
+    }
+
+    fn place_trunk(&self, ground: IVec3, height: i32, placements: &mut Vec<Placement>) {
+        for dy in 0..height {
+            placements.push(Placement {
+                position: ground + IVec3::Y * dy,
+                block: self.log,
+                replace: Replace::Plants,
+            });
+        }
+    }
+
+    /// Now and then a desert well or the ruin of a small cobblestone hut,
+    /// only where the ground is flat enough for it.
+    fn place_structures(
+        &self,
+        source: ChunkPos,
+        terrain: &TerrainGenerator,
+        placements: &mut Vec<Placement>,
+    ) {
+        let mut rng = self.rng(source, 2);
+        if rng.next_f64() >= self.settings.structure_chance {
+            return;
+        }
+
+        let origin = source.origin();
+        let (x, z) = (
+            origin.x + rng.range_i32(0, CHUNK_SIZE),
+            origin.z + rng.range_i32(0, CHUNK_SIZE),
+        );
+
+        let surface = terrain.get_surface(x, z);
+        if surface < terrain.get_settings().sea_level {
+            return;
+        }
+
+        let corners = [(-2, -2), (-2, 2), (2, -2), (2, 2)];
+        if corners
+            .iter()
+            .any(|&(dx, dz)| (terrain.get_surface(x + dx, z + dz) - surface).abs() > 1)
+        {
+            return;
+        }
+
+        let center = ivec3(x, surface, z);
+        match terrain.get_biome(x, z) {
+            Biome::Desert => self.place_well(center, placements),
+            _ => self.place_ruin(center, &mut rng, placements),
+        }
+    }
+
+    /// A 5×5 cobblestone well around a pool of water, with a roof on four pillars.
+    fn place_well(&self, center: IVec3, placements: &mut Vec<Placement>) {
+        for dx in -2..=2i32 {
+            for dz in -2..=2i32 {
+                let edge = dx.abs() == 2 || dz.abs() == 2;
+                let pool = dx.abs() <= 1 && dz.abs() <= 1;
+                let pillar = dx.abs() == 1 && dz.abs() == 1;
+
+                let mut place = |dy: i32, block: BlockId| {
+                    placements.push(Placement {
+                        position: center + ivec3(dx, dy, dz),
+                        block,
+                        replace: Replace::Any,
+                    });
+                };
+
+                place(-1, self.cobblestone);
+                place(0, if pool { self.water } else { self.cobblestone });
+                place(1, if edge { self.cobblestone } else { BlockId::AIR });
+                place(
+                    2,
+                    if pillar {
+                        self.cobblestone
+                    } else {
+                        BlockId::AIR
+                    },
+                );
+                place(
+                    3,
+                    if pillar {
+                        self.cobblestone
+                    } else {
+                        BlockId::AIR
+                    },
+                );
+                place(4, if pool { self.cobblestone } else { BlockId::AIR });
+            }
+        }
+    }
+
+    /// The floor and crumbling walls of a 5×5 hut.
+    fn place_ruin(&self, center: IVec3, rng: &mut Rng, placements: &mut Vec<Placement>) {
+        for dx in -2..=2i32 {
+            for dz in -2..=2i32 {
+                let wall = dx.abs() == 2 || dz.abs() == 2;
+                let door = dx == 0 && dz == -2;
+                let height = if wall && !door {
+                    rng.range_i32(0, 4)
+                } else {
+                    0
+                };
+
+                placements.push(Placement {
+                    position: center + ivec3(dx, 0, dz),
+                    block: self.cobblestone,
+                    replace: Replace::Any,
+                });
+
+                for dy in 1..=3 {
+                    placements.push(Placement {
+                        position: center + ivec3(dx, dy, dz),
+                        block: if dy <= height {
+                            self.cobblestone
+                        } else {
+                            BlockId::AIR
+                        },
+                        replace: Replace::Any,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+    use crate::worldgen::{CaveSettings, TerrainSettings};
+    use std::collections::HashMap;
+
+    const SEED: u64 = 8;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    fn generator(registry: &BlockRegistry, decorations: DecorationSettings) -> TerrainGenerator {
+        TerrainGenerator::new(
+            SEED,
+            registry,
+            TerrainSettings {
+                caves: CaveSettings::disabled(),
+                decorations,
+                ..TerrainSettings::default()
+            },
+        )
+    }
+
+    fn vegetation_only() -> DecorationSettings {
+        DecorationSettings {
+            ores: Vec::new(),
+            structure_chance: 0.0,
+            ..DecorationSettings::default()
+        }
+    }
+
+    #[test]
+    fn trees_crossing_chunk_borders_are_whole() {
+        let registry = registry();
+        let decorated = generator(&registry, vegetation_only());
+        let bare = generator(&registry, DecorationSettings::disabled());
+        let tree = [registry.get_id("log"), registry.get_id("leaves")].map(Option::unwrap);
+
+        let mut chunks = HashMap::new();
+        let mut crossing = 0;
+
+        for cx in 0..6 {
+            for cz in 0..6 {
+                let source = ChunkPos::new(cx, cz);
+
+                for placement in decorated.get_decorator().get_placements(source, &decorated) {
+                    let target = ChunkPos::from_block(placement.position);
+                    if target == source || !tree.contains(&placement.block) {
+                        continue;
+                    }
+
+                    let (before, after) = chunks
+                        .entry(target)
+                        .or_insert_with(|| (bare.generate(target), decorated.generate(target)));
+
+                    let local = placement.position - target.origin();
+                    let y = placement.position.y;
+                    if before.get_block(local.x, y, local.z).is_air() {
+                        assert!(tree.contains(&after.get_block(local.x, y, local.z)));
+                        crossing += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(crossing > 0);
+    }
+
+    #[test]
+    fn generation_order_does_not_matter() {
+        let registry = registry();
+        let generator = generator(&registry, DecorationSettings::default());
+
+        let positions: Vec<_> = (0..3)
+            .flat_map(|x| (0..3).map(move |z| ChunkPos::new(x, z)))
+            .collect();
+
+        let build = |order: &mut dyn Iterator<Item = &ChunkPos>| {
+            let mut world = World::new();
+            for &pos in order {
+                world.insert_chunk(generator.generate(pos));
+            }
+
+            positions
+                .iter()
+                .flat_map(|&pos| world.get_chunk(pos).unwrap().blocks())
+                .collect::<Vec<_>>()
+        };
+
+        let forward = build(&mut positions.iter());
+        let backward = build(&mut positions.iter().rev());
+        let shuffled = build(
+            &mut positions
+                .iter()
+                .step_by(2)
+                .chain(positions.iter().skip(1).step_by(2)),
+        );
+
+        assert_eq!(forward, backward);
+        assert_eq!(forward, shuffled);
+    }
+
+    #[test]
+    fn ores_replace_stone_at_their_depth() {
+        let registry = registry();
+        let settings = DecorationSettings {
+            vegetation: 0.0,
+            structure_chance: 0.0,
+            ..DecorationSettings::default()
+        };
+        let decorated = generator(&registry, settings.clone());
+        let bare = generator(&registry, DecorationSettings::disabled());
+
+        let pos = ChunkPos::new(-1, 3);
+        let (before, after) = (bare.generate(pos), decorated.generate(pos));
+        let mut ores = 0;
+
+        for (position, block) in after.blocks() {
+            let local = position - pos.origin();
+            let previous = before.get_block(local.x, position.y, local.z);
+            if previous == block {
+                continue;
+            }
+
+            let ore = settings
+                .ores
+                .iter()
+                .find(|ore| registry.get_id(ore.block) == Some(block))
+                .expect("only ores are placed");
+
+            let size = ore.vein_size as i32;
+            assert_eq!(previous, registry.get_id("stone").unwrap());
+            assert!((ore.min_y - size..ore.max_y + size).contains(&position.y));
+            ores += 1;
+        }
+
+        assert!(ores > 0);
+    }
+
+    #[test]
+    fn plants_grow_on_grass() {
+        let registry = registry();
+        let generator = generator(&registry, vegetation_only());
+        let id = |name| registry.get_id(name).unwrap();
+
+        let mut plants = 0;
+        for pos in [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(2, 5),
+            ChunkPos::new(-4, 1),
+        ] {
+            let chunk = generator.generate(pos);
+
+            for (position, block) in chunk.blocks() {
+                if block == id("flower") || block == id("tall_grass") {
+                    let local = position - pos.origin();
+                    assert_eq!(
+                        chunk.get_block(local.x, position.y - 1, local.z),
+                        id("grass")
+                    );
+                    plants += 1;
+                }
+            }
+        }
+
+        assert!(plants > 0);
+    }
+}
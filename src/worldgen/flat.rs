@@ -0,0 +1,186 @@
+use std::io::{Error, ErrorKind};
+
+use glam::{ivec2, ivec3, IVec2, IVec3};
+
+use super::generator::Generator;
+use crate::block::BlockRegistry;
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MAX_Y, MIN_Y};
+
+/// The same stack of layers in every column, or only the columns of an area.
+#[derive(Debug, Clone)]
+pub struct FlatGenerator {
+    /// Bottom to top.
+    layers: Vec<BlockId>,
+    /// Y coordinate of the bottom layer.
+    bottom: i32,
+    /// Columns from the first corner up to, but not including, the second one.
+    area: Option<(IVec2, IVec2)>,
+}
+
+impl FlatGenerator {
+    pub const DEFAULT_LAYERS: &str = "1*bedrock,3*dirt,grass";
+
+    /// Layers stacked from the bottom of the world up.
+    pub fn new(layers: Vec<BlockId>) -> Self {
+        Self {
+            layers,
+            bottom: MIN_Y,
+            area: None,
+        }
+    }
+
+    /// Reads comma separated layers from the bottom up, each a block name with
+    /// an optional count in front, like `1*bedrock,3*dirt,grass`.
+    pub fn parse(layers: &str, registry: &BlockRegistry) -> std::io::Result<Self> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidInput, msg);
+        let mut blocks = Vec::new();
+
+        for layer in layers.split(',').map(str::trim) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => {
+                    let count = count
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| invalid(format!("bad layer count in `{layer}`")))?;
+                    (count, name.trim())
+                }
+                None => (1, layer),
+            };
+
+            let block = registry
+                .get_id(name)
+                .ok_or_else(|| invalid(format!("unknown block `{name}` in layer `{layer}`")))?;
+
+            if count == 0 {
+                return Err(invalid(format!("empty layer `{layer}`")));
+            }
+
+            // checked before the layers are laid out, huge counts would run out of memory
+            let total = blocks
+                .len()
+                .checked_add(count)
+                .filter(|&total| total <= (MAX_Y - MIN_Y) as usize)
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "layers up to `{layer}` don't fit in the world height"
+                    ))
+                })?;
+            blocks.resize(total, block);
+        }
+
+        Ok(Self::new(blocks))
+    }
+
+    /// The 20×20×20 block of dirt under grass the first demo scene was made of.
+    pub fn cube(registry: &BlockRegistry) -> Self {
+        let mut layers = vec![super::require(registry, "dirt"); 19];
+        layers.push(super::require(registry, "grass"));
+
+        Self {
+            layers,
+            bottom: -19,
+            area: Some((ivec2(0, 0), ivec2(20, 20))),
+        }
+    }
+
+    fn contains(&self, x: i32, z: i32) -> bool {
+        self.area
+            .is_none_or(|(min, max)| (min.x..max.x).contains(&x) && (min.y..max.y).contains(&z))
+    }
+}
+
+impl Generator for FlatGenerator {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos.origin();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                if !self.contains(origin.x + x, origin.z + z) {
+                    continue;
+                }
+
+                for (y, &block) in (self.bottom..).zip(&self.layers) {
+                    chunk.set_block(x, y, z, block);
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn get_spawn(&self) -> IVec3 {
+        let top = self.bottom + self.layers.len() as i32;
+
+        match self.area {
+            Some((min, max)) => ivec3((min.x + max.x) / 2, top, (min.y + max.y) / 2),
+            None => ivec3(0, top, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    #[test]
+    fn layers_are_stacked_from_the_bottom() {
+        let registry = registry();
+        let id = |name| registry.get_id(name).unwrap();
+        let generator = FlatGenerator::parse("1*bedrock, 3*dirt,grass", &registry).unwrap();
+
+        let chunk = generator.generate(ChunkPos::new(-7, 4));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                assert_eq!(chunk.get_block(x, MIN_Y, z), id("bedrock"));
+                assert_eq!(chunk.get_block(x, MIN_Y + 1, z), id("dirt"));
+                assert_eq!(chunk.get_block(x, MIN_Y + 3, z), id("dirt"));
+                assert_eq!(chunk.get_block(x, MIN_Y + 4, z), id("grass"));
+                assert!(chunk.get_block(x, MIN_Y + 5, z).is_air());
+            }
+        }
+
+        assert_eq!(generator.get_spawn(), ivec3(0, MIN_Y + 5, 0));
+    }
+
+    #[test]
+    fn bad_layers_are_rejected() {
+        let registry = registry();
+
+        for layers in ["", "dirt,", "x*dirt", "0*dirt", "3*cheese", "400*stone"] {
+            assert!(FlatGenerator::parse(layers, &registry).is_err(), "{layers}");
+        }
+    }
+
+    #[test]
+    fn oversized_layers_are_rejected_before_they_are_laid_out() {
+        let registry = registry();
+
+        for layers in [
+            "99999999999*stone",
+            &format!("{}*stone", usize::MAX),
+            &format!("dirt,{}*stone", usize::MAX),
+        ] {
+            assert!(FlatGenerator::parse(layers, &registry).is_err(), "{layers}");
+        }
+    }
+
+    #[test]
+    fn cube_is_twenty_blocks_wide() {
+        let registry = registry();
+        let generator = FlatGenerator::cube(&registry);
+
+        let blocks = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(1, 0),
+            ChunkPos::new(0, 1),
+        ]
+        .map(|pos| generator.generate(pos).blocks().count());
+        assert_eq!(blocks, [16 * 16 * 20, 4 * 16 * 20, 16 * 4 * 20]);
+        assert_eq!(generator.generate(ChunkPos::new(-1, 0)).blocks().count(), 0);
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+use glam::{ivec3, IVec3};
+
+use super::{Biome, DebugGenerator, FlatGenerator, TerrainGenerator, TerrainSettings};
+use crate::block::BlockRegistry;
+use crate::save::AnvilWorld;
+use crate::world::{BlockId, Chunk, ChunkPos};
+
+/// Produces the blocks of every chunk of a world.
+pub trait Generator: Send + Sync {
+    /// Must always give the same chunk for the same position.
+    fn generate(&self, pos: ChunkPos) -> Chunk;
+
+    /// Block the player starts in, right above the ground.
+    fn get_spawn(&self) -> IVec3;
+
+    /// Biome of the column, for generators that have them.
+    fn get_biome(&self, _x: i32, _z: i32) -> Option<Biome> {
+        None
+    }
+
+    /// Forgets whatever was kept about a chunk that was unloaded.
+    fn unload(&self, _pos: ChunkPos) {}
+}
+
+/// Builds the generator a preset names:
+///
+/// - `noise`: the seeded terrain generator
+/// - `superflat`, or `superflat:<layers>` with layers like `1*bedrock,3*dirt,grass`
+/// - `void`: nothing but a single block to build from
+/// - `debug`: every registered block on a grid
+/// - `cube`: a 20×20×20 cube of dirt and grass
+/// - `anvil:<folder>`: the overworld of a vanilla save folder
+pub fn from_preset(
+    preset: &str,
+    seed: u64,
+    registry: &BlockRegistry,
+) -> std::io::Result<Arc<dyn Generator>> {
+    let (name, options) = match preset.split_once(':') {
+        Some((name, options)) => (name, Some(options)),
+        None => (preset, None),
+    };
+
+    let generator: Arc<dyn Generator> = match (name, options) {
+        ("noise", None) => Arc::new(TerrainGenerator::new(
+            seed,
+            registry,
+            TerrainSettings::default(),
+        )),
+        ("superflat", layers) => Arc::new(FlatGenerator::parse(
+            layers.unwrap_or(FlatGenerator::DEFAULT_LAYERS),
+            registry,
+        )?),
+        ("void", None) => Arc::new(VoidGenerator::new(registry)),
+        ("debug", None) => Arc::new(DebugGenerator::new(registry)),
+        ("cube", None) => Arc::new(FlatGenerator::cube(registry)),
+        ("anvil", Some(dir)) => Arc::new(AnvilWorld::open(dir.as_ref(), registry)?),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown world generator preset `{preset}`"),
+            ))
+        }
+    };
+
+    Ok(generator)
+}
+
+/// An empty world, apart from the block under the spawn.
+#[derive(Debug, Clone)]
+pub struct VoidGenerator {
+    platform: BlockId,
+}
+
+impl VoidGenerator {
+    const SPAWN: IVec3 = ivec3(0, 64, 0);
+
+    pub fn new(registry: &BlockRegistry) -> Self {
+        Self {
+            platform: super::require(registry, "stone"),
+        }
+    }
+}
+
+impl Generator for VoidGenerator {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+
+        let below = Self::SPAWN - IVec3::Y;
+        if ChunkPos::from_block(below) == pos {
+            let local = below - pos.origin();
+            chunk.set_block(local.x, below.y, local.z, self.platform);
+        }
+
+        chunk
+    }
+
+    fn get_spawn(&self) -> IVec3 {
+        Self::SPAWN
+    }
+}
+
+impl Generator for TerrainGenerator {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        TerrainGenerator::generate(self, pos)
+    }
+
+    fn get_spawn(&self) -> IVec3 {
+        let surface = self.get_surface(0, 0).max(self.get_settings().sea_level);
+        ivec3(0, surface + 1, 0)
+    }
+
+    fn get_biome(&self, x: i32, z: i32) -> Option<Biome> {
+        Some(TerrainGenerator::get_biome(self, x, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    #[test]
+    fn presets_are_recognised() {
+        let registry = registry();
+
+        for preset in [
+            "noise",
+            "superflat",
+            "superflat:2*stone,grass",
+            "void",
+            "debug",
+            "cube",
+        ] {
+            assert!(from_preset(preset, 1, &registry).is_ok(), "{preset}");
+        }
+
+        for preset in ["", "flat", "void:stone", "superflat:3*nothing"] {
+            assert!(from_preset(preset, 1, &registry).is_err(), "{preset}");
+        }
+    }
+
+    #[test]
+    fn spawn_is_above_the_ground() {
+        let registry = registry();
+
+        for preset in ["noise", "superflat", "void", "debug", "cube"] {
+            let generator = from_preset(preset, 1, &registry).unwrap();
+            let spawn = generator.get_spawn();
+            let chunk = generator.generate(ChunkPos::from_block(spawn));
+            let local = spawn - ChunkPos::from_block(spawn).origin();
+
+            assert!(
+                chunk.get_block(local.x, spawn.y, local.z).is_air(),
+                "{preset}"
+            );
+        }
+    }
+
+    #[test]
+    fn void_is_empty_away_from_spawn() {
+        let generator = VoidGenerator::new(&registry());
+
+        assert_eq!(generator.generate(ChunkPos::new(3, -2)).blocks().count(), 0);
+        assert_eq!(generator.generate(ChunkPos::new(0, 0)).blocks().count(), 1);
+    }
+}
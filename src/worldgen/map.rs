@@ -0,0 +1,423 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use glam::{ivec2, IVec2};
+
+use super::{Biome, Generator};
+use crate::block::{BlockRegistry, Face};
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MIN_Y};
+
+/// What a top-down map shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapKind {
+    /// Grey by height, lit from the north west, water tinted blue.
+    Height,
+    Biome,
+    /// Colour of the topmost block, water darker the deeper it is.
+    Blocks,
+}
+
+impl MapKind {
+    pub const ALL: [MapKind; 3] = [MapKind::Height, MapKind::Biome, MapKind::Blocks];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapKind::Height => "height",
+            MapKind::Biome => "biome",
+            MapKind::Blocks => "blocks",
+        }
+    }
+}
+
+/// The topmost block of a column.
+#[derive(Debug, Clone, Copy)]
+struct Column {
+    block: BlockId,
+    /// Height of the topmost block that isn't water.
+    ground: i32,
+    /// Water blocks above the ground.
+    water_depth: i32,
+    biome: Option<Biome>,
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Self {
+            block: BlockId::AIR,
+            ground: MIN_Y - 1,
+            water_depth: 0,
+            biome: None,
+        }
+    }
+}
+
+/// Top view of a generated area, from which every kind of map is drawn.
+#[derive(Debug, Clone)]
+pub struct Survey {
+    /// Block coordinates of the north west corner.
+    origin: IVec2,
+    width: usize,
+    height: usize,
+    /// Row by row, north to south.
+    columns: Vec<Column>,
+}
+
+impl Survey {
+    /// Generates the chunks from `min` to `max`, both included, spread over `threads` threads.
+    pub fn new(
+        generator: &dyn Generator,
+        registry: &BlockRegistry,
+        min: ChunkPos,
+        max: ChunkPos,
+        threads: usize,
+    ) -> Self {
+        let water = registry.get_id("water");
+        let chunks: Vec<_> = (min.z..=max.z)
+            .flat_map(|z| (min.x..=max.x).map(move |x| ChunkPos::new(x, z)))
+            .collect();
+
+        let mut survey = Self {
+            origin: ivec2(min.x * CHUNK_SIZE, min.z * CHUNK_SIZE),
+            width: ((max.x - min.x + 1).max(0) * CHUNK_SIZE) as usize,
+            height: ((max.z - min.z + 1).max(0) * CHUNK_SIZE) as usize,
+            columns: Vec::new(),
+        };
+        survey.columns = vec![Column::default(); survey.width * survey.height];
+
+        let next = AtomicUsize::new(0);
+        let surveyed: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.max(1))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut surveyed = Vec::new();
+
+                        while let Some(&pos) = chunks.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            let chunk = generator.generate(pos);
+                            surveyed.push((pos, survey_chunk(&chunk, generator, water)));
+                        }
+
+                        surveyed
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        for (pos, columns) in surveyed {
+            let origin = pos.origin();
+
+            for (i, column) in columns.into_iter().enumerate() {
+                let x = origin.x + i as i32 % CHUNK_SIZE - survey.origin.x;
+                let z = origin.z + i as i32 / CHUNK_SIZE - survey.origin.y;
+                survey.columns[z as usize * survey.width + x as usize] = column;
+            }
+        }
+
+        survey
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn render(&self, kind: MapKind, colors: &BlockColors) -> Image {
+        let mut image = Image::new(self.width, self.height);
+
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let column = self.columns[z * self.width + x];
+
+                let color = match kind {
+                    MapKind::Height => {
+                        // y 0 to 192 covers everything from the sea floor to the peaks
+                        let grey = (column.ground as f32 / 192.0).clamp(0.0, 1.0) * 255.0;
+                        let color = [grey; 3];
+                        let lit = shade(color, self.get_slope(x, z));
+
+                        if column.water_depth > 0 {
+                            mix(lit, [40.0, 70.0, 170.0], 0.5)
+                        } else {
+                            lit
+                        }
+                    }
+                    MapKind::Biome => column
+                        .biome
+                        .map_or([96.0; 3], |biome| biome.settings().color.map(f32::from)),
+                    MapKind::Blocks => {
+                        let color = colors.get(column.block).map(f32::from);
+                        if column.water_depth > 0 {
+                            let deep = (column.water_depth as f32 / 24.0).min(0.6);
+                            mix(color, [0.0; 3], deep)
+                        } else {
+                            shade(color, self.get_slope(x, z))
+                        }
+                    }
+                };
+
+                image.set_pixel(x, z, color.map(|c| c.round().clamp(0.0, 255.0) as u8));
+            }
+        }
+
+        image
+    }
+
+    /// How much higher the column is than the one to its north west.
+    fn get_slope(&self, x: usize, z: usize) -> i32 {
+        if x == 0 || z == 0 {
+            return 0;
+        }
+
+        let ground = |x: usize, z: usize| {
+            let column = self.columns[z * self.width + x];
+            column.ground + column.water_depth
+        };
+        ground(x, z) - ground(x - 1, z - 1)
+    }
+}
+
+/// Topmost block of every column of the chunk, indexed `x + z * 16`.
+fn survey_chunk(chunk: &Chunk, generator: &dyn Generator, water: Option<BlockId>) -> Vec<Column> {
+    let origin = chunk.get_position().origin();
+    let mut columns = vec![Column::default(); (CHUNK_SIZE * CHUNK_SIZE) as usize];
+
+    // only sections with blocks in them are looked through
+    let top = (0..crate::world::SECTION_COUNT)
+        .rev()
+        .find(|&i| chunk.get_section(i).is_some())
+        .map_or(MIN_Y - 1, |i| MIN_Y + (i as i32 + 1) * CHUNK_SIZE - 1);
+
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let column = &mut columns[(x + z * CHUNK_SIZE) as usize];
+            column.biome = generator.get_biome(origin.x + x, origin.z + z);
+
+            let mut ys = (MIN_Y..=top).rev();
+            let Some(y) = ys.find(|&y| !chunk.get_block(x, y, z).is_air()) else {
+                continue;
+            };
+
+            column.block = chunk.get_block(x, y, z);
+            column.ground = y;
+
+            if Some(column.block) == water {
+                let ground = (MIN_Y..y)
+                    .rev()
+                    .find(|&y| Some(chunk.get_block(x, y, z)) != water)
+                    .unwrap_or(MIN_Y - 1);
+
+                column.water_depth = y - ground;
+                column.ground = ground;
+            }
+        }
+    }
+
+    columns
+}
+
+/// Brighter on slopes facing north west.
+fn shade(color: [f32; 3], slope: i32) -> [f32; 3] {
+    let light = (1.0 + slope as f32 * 0.12).clamp(0.6, 1.4);
+    color.map(|c| c * light)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Average colour of the top face of every block, read from its texture.
+#[derive(Debug, Clone)]
+pub struct BlockColors {
+    colors: Vec<[u8; 3]>,
+}
+
+impl BlockColors {
+    /// Loads `<dir>/<texture>.png` for every block, transparent pixels left out.
+    pub fn load(registry: &BlockRegistry, dir: &Path) -> std::io::Result<Self> {
+        let textures = registry
+            .get_textures()
+            .iter()
+            .map(|name| average_color(&dir.join(format!("{name}.png"))))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let colors = registry
+            .states()
+            .map(|id| match id.is_air() {
+                true => [0; 3],
+                false => textures[registry.get_face(id, Face::Up).layer as usize],
+            })
+            .collect();
+
+        Ok(Self { colors })
+    }
+
+    pub fn get(&self, block: BlockId) -> [u8; 3] {
+        self.colors
+            .get(block.0 as usize)
+            .copied()
+            .unwrap_or([255, 0, 255])
+    }
+}
+
+fn average_color(path: &Path) -> std::io::Result<[u8; 3]> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut pixels)?;
+
+    let channels = info.color_type.samples();
+    let mut sum = [0.0; 3];
+    let mut weight = 0.0;
+
+    for pixel in pixels[..info.buffer_size()].chunks_exact(channels) {
+        let (rgb, alpha) = match pixel {
+            [grey] => ([*grey; 3], 255),
+            [grey, alpha] => ([*grey; 3], *alpha),
+            [r, g, b] => ([*r, *g, *b], 255),
+            [r, g, b, alpha] => ([*r, *g, *b], *alpha),
+            _ => unreachable!(),
+        };
+
+        let a = alpha as f64 / 255.0;
+        for (sum, c) in sum.iter_mut().zip(rgb) {
+            *sum += c as f64 * a;
+        }
+        weight += a;
+    }
+
+    Ok(sum.map(|c| (c / weight.max(f64::EPSILON)).round() as u8))
+}
+
+/// An RGB picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 3]; width * height],
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::{from_preset, FlatGenerator};
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    #[test]
+    fn flat_world_gives_flat_maps() {
+        let registry = registry();
+        let colors = BlockColors::load(&registry, Path::new("res/block")).unwrap();
+        let generator = FlatGenerator::parse("3*stone,grass", &registry).unwrap();
+
+        let survey = Survey::new(
+            &generator,
+            &registry,
+            ChunkPos::new(-1, -1),
+            ChunkPos::new(1, 0),
+            3,
+        );
+        assert_eq!((survey.get_width(), survey.get_height()), (48, 32));
+
+        let height = survey.render(MapKind::Height, &colors);
+        let blocks = survey.render(MapKind::Blocks, &colors);
+        let grass = colors.get(registry.get_id("grass").unwrap());
+
+        for y in 0..32 {
+            for x in 0..48 {
+                assert_eq!(height.get_pixel(x, y), height.get_pixel(0, 0));
+                assert_eq!(blocks.get_pixel(x, y), grass);
+            }
+        }
+    }
+
+    #[test]
+    fn biomes_show_up_on_noise_maps() {
+        let registry = registry();
+        let colors = BlockColors::load(&registry, Path::new("res/block")).unwrap();
+        let generator = from_preset("noise", 3, &registry).unwrap();
+
+        let survey = Survey::new(
+            generator.as_ref(),
+            &registry,
+            ChunkPos::new(0, 0),
+            ChunkPos::new(1, 1),
+            2,
+        );
+        let biomes = survey.render(MapKind::Biome, &colors);
+
+        let expected = generator.get_biome(5, 20).unwrap().settings().color;
+        assert_eq!(biomes.get_pixel(5, 20), expected);
+    }
+
+    #[test]
+    fn images_round_trip_through_png() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(0, 0, [255, 0, 0]);
+        image.set_pixel(2, 1, [1, 2, 3]);
+
+        let path = std::env::temp_dir().join(format!("map-{}.png", std::process::id()));
+        image.save(&path).unwrap();
+
+        let mut reader = png::Decoder::new(BufReader::new(File::open(&path).unwrap()))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(pixels, image.pixels.as_flattened());
+    }
+}
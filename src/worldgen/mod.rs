@@ -0,0 +1,32 @@
+mod biome;
+mod caves;
+mod debug;
+mod decoration;
+mod flat;
+mod generator;
+pub mod map;
+pub mod noise;
+mod terrain;
+
+pub use biome::*;
+pub use caves::*;
+pub use debug::*;
+pub use decoration::*;
+pub use flat::*;
+pub use generator::*;
+pub use terrain::*;
+
+use crate::block::BlockRegistry;
+use crate::world::BlockId;
+
+/// World seed used when none is given.
+pub const DEFAULT_SEED: u64 = 0x6d69_6e65;
+/// Generator preset used when none is given, see `from_preset`.
+pub const DEFAULT_PRESET: &str = "noise";
+
+/// Looks up a block the generator can't work without.
+fn require(registry: &BlockRegistry, name: &str) -> BlockId {
+    registry
+        .get_id(name)
+        .unwrap_or_else(|| panic!("world generation needs the `{name}` block"))
+}
@@ -0,0 +1,276 @@
+//! Gradient noise built only from integer hashing and basic float arithmetic,
+//! so the same seed gives bit identical results on every machine.
+
+/// Seeded Perlin noise, values roughly in `-1.0..=1.0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Perlin {
+    seed: u64,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn get2(&self, x: f64, z: f64) -> f64 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (x - x0, z - z0);
+        let (ix, iz) = (x0 as i32, z0 as i32);
+
+        let grad = |dx: i32, dz: i32| {
+            let h = hash(self.seed, ix + dx, 0, iz + dz);
+            let (gx, gz) = GRADIENTS_2D[(h & 7) as usize];
+            gx * (fx - dx as f64) + gz * (fz - dz as f64)
+        };
+
+        let (u, v) = (fade(fx), fade(fz));
+
+        lerp(
+            v,
+            lerp(u, grad(0, 0), grad(1, 0)),
+            lerp(u, grad(0, 1), grad(1, 1)),
+        )
+    }
+
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let grad = |dx: i32, dy: i32, dz: i32| {
+            let h = hash(self.seed, ix + dx, iy + dy, iz + dz);
+            let (gx, gy, gz) = GRADIENTS_3D[(h % 12) as usize];
+            gx * (fx - dx as f64) + gy * (fy - dy as f64) + gz * (fz - dz as f64)
+        };
+
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(0, 0, 0), grad(1, 0, 0)),
+                lerp(u, grad(0, 1, 0), grad(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, grad(0, 0, 1), grad(1, 0, 1)),
+                lerp(u, grad(0, 1, 1), grad(1, 1, 1)),
+            ),
+        )
+    }
+}
+
+/// Several octaves of Perlin noise summed together, each one with a
+/// higher frequency and a lower amplitude than the previous.
+#[derive(Debug, Clone)]
+pub struct Fbm {
+    octaves: Vec<Perlin>,
+    persistence: f64,
+    lacunarity: f64,
+}
+
+impl Fbm {
+    pub fn new(seed: u64, octaves: u32, persistence: f64, lacunarity: f64) -> Self {
+        Self {
+            octaves: (0..octaves.max(1) as u64)
+                .map(|i| Perlin::new(mix(seed, i)))
+                .collect(),
+            persistence,
+            lacunarity,
+        }
+    }
+
+    /// Normalized back to roughly `-1.0..=1.0`.
+    pub fn get2(&self, x: f64, z: f64) -> f64 {
+        self.sum(|perlin, f| perlin.get2(x * f, z * f))
+    }
+
+    /// Normalized back to roughly `-1.0..=1.0`.
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.sum(|perlin, f| perlin.get3(x * f, y * f, z * f))
+    }
+
+    fn sum(&self, sample: impl Fn(&Perlin, f64) -> f64) -> f64 {
+        let (mut total, mut max) = (0.0, 0.0);
+        let (mut amplitude, mut frequency) = (1.0, 1.0);
+
+        for perlin in &self.octaves {
+            total += sample(perlin, frequency) * amplitude;
+            max += amplitude;
+
+            amplitude *= self.persistence;
+            frequency *= self.lacunarity;
+        }
+
+        total / max
+    }
+}
+
+/// Small deterministic random number generator.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        splitmix(self.0)
+    }
+
+    /// Uniform in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform in `min..max`.
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+
+    /// Uniform in `min..max`, `max` must be greater than `min`.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        min + (self.next_u64() % (max - min) as u64) as i32
+    }
+}
+
+/// Sine from basic arithmetic only, `f64::sin` may differ between platforms.
+pub fn sin(x: f64) -> f64 {
+    use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+    // reduce to -PI..=PI, then to -PI/2..=PI/2 where the series converges quickly
+    let mut x = x - (x / TAU).round() * TAU;
+    if x > FRAC_PI_2 {
+        x = PI - x;
+    } else if x < -FRAC_PI_2 {
+        x = -PI - x;
+    }
+
+    let x2 = x * x;
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..=8 {
+        term *= -x2 / ((2 * n) as f64 * (2 * n + 1) as f64);
+        sum += term;
+    }
+
+    sum
+}
+
+/// Cosine from basic arithmetic only, see [`sin`].
+pub fn cos(x: f64) -> f64 {
+    sin(x + std::f64::consts::FRAC_PI_2)
+}
+
+/// Derives an independent seed, used to give every noise layer its own values.
+pub fn mix(seed: u64, salt: u64) -> u64 {
+    splitmix(seed ^ splitmix(salt.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+}
+
+/// Well distributed hash of a lattice point.
+pub fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let h = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u32 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ (z as u32 as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+
+    splitmix(h)
+}
+
+fn splitmix(mut h: u64) -> u64 {
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+const D: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+const GRADIENTS_2D: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (D, D),
+    (-D, D),
+    (D, -D),
+    (-D, -D),
+];
+
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_reproducible() {
+        let perlin = Perlin::new(42);
+        assert_eq!(perlin.get2(1.5, 2.25), -0.40087890625);
+        assert_eq!(perlin.get3(-3.75, 0.5, 10.125), 0.026569753885269165);
+
+        let fbm = Fbm::new(42, 4, 0.5, 2.0);
+        assert_eq!(fbm.get2(100.3, -20.7), -0.15288753011037082);
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        let fbm = Fbm::new(7, 5, 0.5, 2.0);
+
+        for i in 0..10_000 {
+            let (x, z) = (i as f64 * 0.173, i as f64 * -0.311);
+            assert!(fbm.get2(x, z).abs() <= 1.0);
+            assert!(fbm.get3(x, z, x + z).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn integer_lattice_points_are_zero() {
+        let perlin = Perlin::new(3);
+        assert_eq!(perlin.get2(5.0, -8.0), 0.0);
+        assert_eq!(perlin.get3(5.0, -8.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn sin_and_cos_match_std() {
+        for i in -2000..2000 {
+            let x = i as f64 * 0.0173;
+            assert!((sin(x) - x.sin()).abs() < 1e-12, "sin({x})");
+            assert!((cos(x) - x.cos()).abs() < 1e-12, "cos({x})");
+        }
+    }
+
+    #[test]
+    fn rng_stays_in_range() {
+        let mut rng = Rng::new(11);
+
+        for _ in 0..10_000 {
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            assert!((-3..7).contains(&rng.range_i32(-3, 7)));
+        }
+    }
+}
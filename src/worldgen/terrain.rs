@@ -0,0 +1,506 @@
+use super::biome::{Biome, ClimateMap};
+use super::caves::{CaveCarver, CaveSettings};
+use super::decoration::{DecorationSettings, Decorator};
+use super::noise::{mix, Fbm, Perlin};
+use crate::block::BlockRegistry;
+use crate::world::{BlockId, Chunk, ChunkPos, CHUNK_SIZE, MAX_Y, MIN_Y};
+
+/// Shape of the generated terrain.
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    /// Water fills every air block up to and including this height.
+    pub sea_level: i32,
+    /// Size of the largest hills is roughly `1 / frequency` blocks.
+    pub frequency: f64,
+    pub octaves: u32,
+    pub persistence: f64,
+    pub lacunarity: f64,
+    /// Biomes are roughly `1 / biome_frequency` blocks across.
+    pub biome_frequency: f64,
+    /// Climate distance over which neighbouring biomes blend their heights.
+    pub biome_blend: f64,
+    /// Filler blocks between the surface and the stone below it.
+    pub dirt_depth: i32,
+    /// How many blocks 3D noise may push the surface around, making cliffs and overhangs.
+    pub overhang_strength: f64,
+    pub overhang_frequency: f64,
+    pub caves: CaveSettings,
+    pub decorations: DecorationSettings,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            sea_level: 62,
+            frequency: 1.0 / 256.0,
+            octaves: 5,
+            persistence: 0.5,
+            lacunarity: 2.0,
+            biome_frequency: 1.0 / 512.0,
+            biome_blend: 0.25,
+            dirt_depth: 3,
+            overhang_strength: 6.0,
+            overhang_frequency: 1.0 / 24.0,
+            caves: CaveSettings::default(),
+            decorations: DecorationSettings::default(),
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Changes the numeric setting called `name`, for tuning from the command line.
+    pub fn set(&mut self, name: &str, value: &str) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind};
+
+        fn bad_value(name: &str, value: &str) -> Error {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("bad value `{value}` for `{name}`"),
+            )
+        }
+
+        fn parse<T: std::str::FromStr>(name: &str, value: &str) -> std::io::Result<T> {
+            value.parse().map_err(|_| bad_value(name, value))
+        }
+
+        match name {
+            "sea_level" => self.sea_level = parse(name, value)?,
+            "frequency" => self.frequency = parse(name, value)?,
+            "octaves" => self.octaves = parse(name, value)?,
+            "persistence" => self.persistence = parse(name, value)?,
+            "lacunarity" => self.lacunarity = parse(name, value)?,
+            "biome_frequency" => self.biome_frequency = parse(name, value)?,
+            "biome_blend" => {
+                // biome weights are divided by it
+                let blend: f64 = parse(name, value)?;
+                if blend.is_nan() || blend <= 0.0 {
+                    return Err(bad_value(name, value));
+                }
+                self.biome_blend = blend;
+            }
+            "dirt_depth" => self.dirt_depth = parse(name, value)?,
+            "overhang_strength" => self.overhang_strength = parse(name, value)?,
+            "overhang_frequency" => self.overhang_frequency = parse(name, value)?,
+            "vegetation" => self.decorations.vegetation = parse(name, value)?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("unknown terrain setting `{name}`"),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Blocks a biome covers its columns with.
+#[derive(Debug, Clone, Copy)]
+struct BiomeBlocks {
+    surface: BlockId,
+    filler: BlockId,
+    underwater: BlockId,
+}
+
+/// Noise heightmap terrain shaped by biomes and 3D noise, carved by caves and
+/// decorated, a pure function of the seed and the chunk position.
+#[derive(Debug, Clone)]
+pub struct TerrainGenerator {
+    settings: TerrainSettings,
+    climate: ClimateMap,
+    height_noise: Fbm,
+    overhang_noise: Perlin,
+    carver: CaveCarver,
+    decorator: Decorator,
+
+    biomes: [BiomeBlocks; 6],
+    stone: BlockId,
+    water: BlockId,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64, registry: &BlockRegistry, settings: TerrainSettings) -> Self {
+        Self {
+            height_noise: Fbm::new(
+                mix(seed, 1),
+                settings.octaves,
+                settings.persistence,
+                settings.lacunarity,
+            ),
+            overhang_noise: Perlin::new(mix(seed, 2)),
+            carver: CaveCarver::new(mix(seed, 3), settings.caves.clone()),
+            decorator: Decorator::new(mix(seed, 5), registry, settings.decorations.clone()),
+            climate: ClimateMap::new(mix(seed, 4), settings.biome_frequency, settings.biome_blend),
+            settings,
+
+            biomes: Biome::ALL.map(|biome| {
+                let settings = biome.settings();
+                BiomeBlocks {
+                    surface: super::require(registry, settings.surface),
+                    filler: super::require(registry, settings.filler),
+                    underwater: super::require(registry, settings.underwater),
+                }
+            }),
+            stone: super::require(registry, "stone"),
+            water: super::require(registry, "water"),
+        }
+    }
+
+    pub fn get_settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    pub fn get_decorator(&self) -> &Decorator {
+        &self.decorator
+    }
+
+    pub fn get_biome(&self, x: i32, z: i32) -> Biome {
+        self.climate.get_biome(x, z)
+    }
+
+    /// Y coordinate the terrain surface of the column is centred on, ignoring water.
+    /// Overhangs and caves may move the actual surface a few blocks.
+    pub fn get_height(&self, x: i32, z: i32) -> i32 {
+        let f = self.settings.frequency;
+        let noise = self.height_noise.get2(x as f64 * f, z as f64 * f);
+
+        // blending the shape of every nearby biome keeps borders free of cliffs
+        let height: f64 = Biome::ALL
+            .iter()
+            .zip(self.climate.get_weights(x, z))
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|(biome, weight)| {
+                let settings = biome.settings();
+                weight * (settings.base_height + noise * settings.height_variation)
+            })
+            .sum();
+
+        (height.floor() as i32).clamp(MIN_Y, MAX_Y - 1)
+    }
+
+    /// Y coordinate of the highest solid block of the column, overhangs included
+    /// but ignoring caves and decorations.
+    pub fn get_surface(&self, x: i32, z: i32) -> i32 {
+        let height = self.get_height(x, z);
+        let band = self.settings.overhang_strength.ceil() as i32;
+
+        (height - band..=height + band)
+            .rev()
+            .find(|&y| self.is_solid(x, y, z, height))
+            .unwrap_or(height - band - 1)
+    }
+
+    pub fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos.origin();
+        let mut heights = [[0; 16]; 16];
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let height = self.get_height(origin.x + x, origin.z + z);
+                let biome = self.get_biome(origin.x + x, origin.z + z);
+                heights[x as usize][z as usize] = height;
+
+                self.fill_column(&mut chunk, x, z, height, biome);
+            }
+        }
+
+        self.carver.carve(&mut chunk, &heights, self.water);
+        self.decorator.decorate(&mut chunk, self);
+
+        chunk
+    }
+
+    /// Fills the column top down, so every surface, overhangs included,
+    /// gets the biome's surface and filler over the stone.
+    fn fill_column(&self, chunk: &mut Chunk, x: i32, z: i32, height: i32, biome: Biome) {
+        let sea_level = self.settings.sea_level;
+        let band = self.settings.overhang_strength.ceil() as i32;
+        let origin = chunk.get_position().origin();
+        let blocks = self.biomes[biome as usize];
+
+        // solid blocks since the last air block above
+        let mut depth = 0;
+        let mut under_water = false;
+
+        for y in (MIN_Y..=(height + band).max(sea_level).min(MAX_Y - 1)).rev() {
+            if !self.is_solid(origin.x + x, y, origin.z + z, height) {
+                depth = 0;
+                under_water = y <= sea_level;
+
+                if under_water {
+                    chunk.set_block(x, y, z, self.water);
+                }
+
+                continue;
+            }
+
+            let block = if depth >= self.settings.dirt_depth {
+                self.stone
+            } else if under_water {
+                blocks.underwater
+            } else if depth == 0 {
+                blocks.surface
+            } else {
+                blocks.filler
+            };
+
+            chunk.set_block(x, y, z, block);
+            depth += 1;
+        }
+    }
+
+    /// Whether the terrain density at the block is positive, the heightmap pushed
+    /// around by 3D noise within `overhang_strength` blocks of the surface.
+    fn is_solid(&self, x: i32, y: i32, z: i32, height: i32) -> bool {
+        let strength = self.settings.overhang_strength;
+        let offset = (height - y) as f64;
+
+        if offset.abs() > strength {
+            return offset > 0.0;
+        }
+
+        let f = self.settings.overhang_frequency;
+        let noise = self
+            .overhang_noise
+            .get3(x as f64 * f, y as f64 * f, z as f64 * f);
+
+        offset + 0.5 + noise * strength > 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_file("res/blocks.json").unwrap()
+    }
+
+    fn blocks(chunk: &Chunk) -> Vec<(glam::IVec3, BlockId)> {
+        chunk.blocks().collect()
+    }
+
+    /// FNV-1a over every block of the chunk.
+    /// Hashes block names rather than ids, so adding block states elsewhere in the
+    /// registry doesn't change it.
+    fn checksum(chunk: &Chunk) -> u64 {
+        let registry = registry();
+        chunk
+            .blocks()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, (pos, block)| {
+                let name = registry.describe_state(block).unwrap();
+                [pos.x, pos.y, pos.z]
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .chain(name.bytes())
+                    .fold(hash, |h, byte| {
+                        (h ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+                    })
+            })
+    }
+
+    #[test]
+    fn biome_blend_must_be_positive() {
+        let mut settings = TerrainSettings::default();
+        for value in ["0", "-0.5", "NaN", "much"] {
+            assert!(settings.set("biome_blend", value).is_err(), "{value}");
+        }
+        assert_eq!(settings.biome_blend, TerrainSettings::default().biome_blend);
+
+        settings.set("biome_blend", "0.5").unwrap();
+        assert_eq!(settings.biome_blend, 0.5);
+    }
+
+    #[test]
+    fn same_seed_gives_same_chunk() {
+        let registry = registry();
+        let a = TerrainGenerator::new(1234, &registry, TerrainSettings::default());
+        let b = TerrainGenerator::new(1234, &registry, TerrainSettings::default());
+
+        for pos in [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(-3, 7),
+            ChunkPos::new(1000, -1000),
+        ] {
+            assert_eq!(blocks(&a.generate(pos)), blocks(&b.generate(pos)));
+        }
+    }
+
+    #[test]
+    fn generation_order_does_not_matter() {
+        let registry = registry();
+        let generator = TerrainGenerator::new(99, &registry, TerrainSettings::default());
+        let positions = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(1, 0),
+            ChunkPos::new(0, -1),
+        ];
+
+        let forward: Vec<_> = positions
+            .iter()
+            .map(|&p| blocks(&generator.generate(p)))
+            .collect();
+        let mut backward: Vec<_> = positions
+            .iter()
+            .rev()
+            .map(|&p| blocks(&generator.generate(p)))
+            .collect();
+        backward.reverse();
+
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn different_seeds_give_different_terrain() {
+        let registry = registry();
+        let a = TerrainGenerator::new(1, &registry, TerrainSettings::default());
+        let b = TerrainGenerator::new(2, &registry, TerrainSettings::default());
+
+        let pos = ChunkPos::new(4, 4);
+        assert_ne!(blocks(&a.generate(pos)), blocks(&b.generate(pos)));
+    }
+
+    #[test]
+    fn output_is_stable_across_machines() {
+        let registry = registry();
+        let generator = TerrainGenerator::new(12345, &registry, TerrainSettings::default());
+
+        assert_eq!(
+            checksum(&generator.generate(ChunkPos::new(0, 0))),
+            0xd85b_3bd9_bf7b_2e88
+        );
+        assert_eq!(
+            checksum(&generator.generate(ChunkPos::new(-5, 11))),
+            0x9dc0_5494_a6e3_e987
+        );
+    }
+
+    #[test]
+    fn columns_are_layered() {
+        let registry = registry();
+        let id = |name| registry.get_id(name).unwrap();
+        let settings = TerrainSettings {
+            overhang_strength: 0.0,
+            caves: CaveSettings::disabled(),
+            decorations: DecorationSettings::disabled(),
+            ..TerrainSettings::default()
+        };
+        let generator = TerrainGenerator::new(7, &registry, settings.clone());
+
+        let pos = ChunkPos::new(2, -2);
+        let chunk = generator.generate(pos);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let origin = pos.origin();
+                let height = generator.get_height(origin.x + x, origin.z + z);
+
+                let biome = generator.get_biome(origin.x + x, origin.z + z).settings();
+
+                let (top, filler) = if height >= settings.sea_level {
+                    (id(biome.surface), id(biome.filler))
+                } else {
+                    (id(biome.underwater), id(biome.underwater))
+                };
+                assert_eq!(chunk.get_block(x, height, z), top);
+                assert_eq!(chunk.get_block(x, height - 1, z), filler);
+                assert_eq!(
+                    chunk.get_block(x, height - settings.dirt_depth, z),
+                    id("stone")
+                );
+                assert_eq!(chunk.get_block(x, MIN_Y, z), id("stone"));
+
+                for y in height + 1..=settings.sea_level {
+                    assert_eq!(chunk.get_block(x, y, z), id("water"));
+                }
+
+                let above = height.max(settings.sea_level) + 1;
+                assert_eq!(chunk.get_block(x, above, z), BlockId::AIR);
+            }
+        }
+    }
+
+    #[test]
+    fn biome_borders_have_no_cliffs() {
+        let registry = registry();
+        let generator = TerrainGenerator::new(3, &registry, TerrainSettings::default());
+
+        let mut biomes = HashSet::new();
+        let mut steepest = 0;
+
+        for x in (-4096..4096).step_by(4) {
+            let z = x / 3;
+            let a = generator.get_height(x, z);
+            let b = generator.get_height(x + 1, z);
+
+            biomes.insert(generator.get_biome(x, z));
+            steepest = steepest.max((a - b).abs());
+        }
+
+        assert!(biomes.len() > 1);
+        assert!(steepest <= 2);
+    }
+
+    #[test]
+    fn caves_open_up_space_underground() {
+        let registry = registry();
+        let settings = TerrainSettings::default();
+        let generator = TerrainGenerator::new(5, &registry, settings.clone());
+
+        let mut underground_air = 0;
+        for cx in 0..4 {
+            for cz in 0..4 {
+                let pos = ChunkPos::new(cx, cz);
+                let chunk = generator.generate(pos);
+
+                for (x, z) in (0..CHUNK_SIZE).flat_map(|x| (0..CHUNK_SIZE).map(move |z| (x, z))) {
+                    let origin = pos.origin();
+                    let height = generator.get_height(origin.x + x, origin.z + z);
+
+                    underground_air += (MIN_Y..height - 10)
+                        .filter(|&y| chunk.get_block(x, y, z).is_air())
+                        .count();
+
+                    // nothing is carved below the cave range
+                    for y in MIN_Y..settings.caves.min_y {
+                        assert!(!chunk.get_block(x, y, z).is_air());
+                    }
+                }
+            }
+        }
+
+        assert!(underground_air > 0);
+    }
+
+    #[test]
+    fn overhangs_leave_air_below_solid_blocks() {
+        let registry = registry();
+        let generator = TerrainGenerator::new(
+            5,
+            &registry,
+            TerrainSettings {
+                caves: CaveSettings::disabled(),
+                decorations: DecorationSettings::disabled(),
+                ..TerrainSettings::default()
+            },
+        );
+
+        let overhangs = (0..4)
+            .flat_map(|cx| (0..4).map(move |cz| ChunkPos::new(cx, cz)))
+            .map(|pos| generator.generate(pos))
+            .map(|chunk| {
+                chunk
+                    .blocks()
+                    .filter(|(pos, _)| {
+                        let local = *pos - chunk.get_position().origin();
+                        chunk.get_block(local.x, pos.y - 1, local.z).is_air()
+                    })
+                    .count()
+            })
+            .sum::<usize>();
+
+        assert!(overhangs > 0);
+    }
+}